@@ -0,0 +1,118 @@
+//! Allocations and time spent parsing typical inbox traffic
+//!
+//! Run with `cargo bench`; Counts come from a global allocator wrapping the system allocator.
+//! Each case is reported against a baseline doing the same work: Parsing into the owned vocabulary types rather than borrowed views,
+//! and the boxed representation FoldedSlice replaced.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use turtleactivitypub::linkeddata::activity_streams::{MaybeObject, TaggedObject};
+use turtleactivitypub::linkeddata::borrowed::ObjectRef;
+use turtleactivitypub::linkeddata::util::FoldedSlice;
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static REALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        REALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+const ITERATIONS: usize = 2000;
+
+/// Allocations, reallocations and time per iteration
+struct Measurement {
+    allocations: f64,
+    reallocations: f64,
+    elapsed: Duration,
+}
+
+/// Run `f` `ITERATIONS` times
+fn measure(mut f: impl FnMut()) -> Measurement {
+    f();
+    let (allocations, reallocations) = (ALLOCATIONS.load(Ordering::Relaxed), REALLOCATIONS.load(Ordering::Relaxed));
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    let elapsed = start.elapsed();
+    Measurement {
+        allocations: (ALLOCATIONS.load(Ordering::Relaxed) - allocations) as f64 / ITERATIONS as f64,
+        reallocations: (REALLOCATIONS.load(Ordering::Relaxed) - reallocations) as f64 / ITERATIONS as f64,
+        elapsed: elapsed / ITERATIONS as u32,
+    }
+}
+
+/// Report `f` against `baseline`, which does the same work the way it is compared to
+fn compare(name: &str, baseline: impl FnMut(), f: impl FnMut()) {
+    let (baseline, measured) = (measure(baseline), measure(f));
+    println!(
+        "{:<24} {:>7.1} -> {:>7.1} allocations {:>5.1} -> {:>5.1} reallocations {:>9.2?} -> {:>9.2?}",
+        name, baseline.allocations, measured.allocations, baseline.reallocations, measured.reallocations, baseline.elapsed, measured.elapsed,
+    );
+}
+
+/// The representation FoldedSlice replaced: A single value boxed on its own, or a boxed slice
+#[allow(dead_code)]
+enum BoxedFoldedSlice<T> {
+    One(Box<T>),
+    Many(Box<[T]>),
+}
+
+impl<T> From<Vec<T>> for BoxedFoldedSlice<T> {
+    fn from(many: Vec<T>) -> Self {
+        match many.len() {
+            1 => BoxedFoldedSlice::One(Box::new(many.into_iter().next().unwrap())),
+            _ => BoxedFoldedSlice::Many(many.into_boxed_slice()),
+        }
+    }
+}
+
+fn main() {
+    let inbox = [
+        include_str!("../src/tests/create.json"),
+        include_str!("../src/tests/pleroma-create.json"),
+        include_str!("../src/tests/misskey-create.json"),
+        include_str!("../src/tests/data.json"),
+        r#"{"@context": "https://www.w3.org/ns/activitystreams", "id": "https://remote.invalid/follows/1", "type": "Follow", "actor": "https://remote.invalid/users/alice", "object": "https://local.invalid/users/bob"}"#,
+        r#"{"@context": "https://www.w3.org/ns/activitystreams", "id": "https://remote.invalid/likes/1", "type": "Like", "actor": "https://remote.invalid/users/alice", "object": "https://local.invalid/notes/1", "to": ["https://local.invalid/users/bob"]}"#,
+    ];
+    // Routing needs only the borrowed view, rather than the whole document in owned vocabulary types
+    println!("TaggedObject -> ObjectRef");
+    for (document, name) in inbox.iter().zip(["Mastodon Create", "Pleroma Create", "Misskey Create", "Note", "Follow", "Like"]) {
+        compare(
+            &format!("parse {}", name),
+            || { black_box(serde_json::from_str::<TaggedObject>(black_box(document)).unwrap()); },
+            || { black_box(serde_json::from_str::<ObjectRef>(black_box(document)).unwrap()); },
+        );
+    }
+    println!("boxed -> FoldedSlice");
+    compare(
+        "from one",
+        || { black_box(BoxedFoldedSlice::<MaybeObject>::from(vec![MaybeObject::URI(String::new())])); },
+        || { black_box(FoldedSlice::<MaybeObject>::from(vec![MaybeObject::URI(String::new())])); },
+    );
+    compare(
+        "from many",
+        || { black_box(BoxedFoldedSlice::<MaybeObject>::from(vec![MaybeObject::URI(String::new()), MaybeObject::URI(String::new())])); },
+        || { black_box(FoldedSlice::<MaybeObject>::from(vec![MaybeObject::URI(String::new()), MaybeObject::URI(String::new())])); },
+    );
+}
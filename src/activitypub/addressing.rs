@@ -0,0 +1,82 @@
+//! Addressing; The special public collection, and visibility of objects derived from their `to`, `bto`, `cc`, `bcc` and `audience`
+//!
+//! Visibility levels follow common usage (e.g. by Mastodon): Public objects address the public collection in `to`, unlisted objects only in `cc`.
+
+use crate::linkeddata::activity_streams::{MaybeObject, TaggedObject};
+use crate::linkeddata::activity_streams::objects::Object;
+use crate::linkeddata::util::FoldedSlice;
+
+/// The special collection containing everyone
+pub const PUBLIC: &str = "https://www.w3.org/ns/activitystreams#Public";
+
+/// Whether `id` refers to the public collection; Compacted forms `as:Public` and `Public` are accepted as well
+pub fn is_public_collection(id: &str) -> bool {
+    matches!(id, PUBLIC | "as:Public" | "Public")
+}
+
+fn addresses(property: &Option<FoldedSlice<MaybeObject>>, predicate: impl Fn(&str) -> bool) -> bool {
+    property.iter().flat_map(|values| values.iter()).filter_map(MaybeObject::id).any(predicate)
+}
+
+impl Object {
+    /// All addressing properties
+    fn addressing(&self) -> [&Option<FoldedSlice<MaybeObject>>; 5] {
+        [&self.to, &self.bto, &self.cc, &self.bcc, &self.audience]
+    }
+
+    fn addresses(&self, predicate: impl Fn(&str) -> bool) -> bool {
+        self.addressing().into_iter().any(|property| addresses(property, &predicate))
+    }
+
+    /// Addressed to the public collection in `to`
+    pub fn is_public(&self) -> bool {
+        addresses(&self.to, is_public_collection)
+    }
+
+    /// Addressed to the public collection, but not in `to`; Visible to everyone, but not listed in public timelines
+    pub fn is_unlisted(&self) -> bool {
+        !self.is_public() && self.addresses(is_public_collection)
+    }
+
+    /// Not public, but addressed to the author's `followers` collection
+    pub fn is_followers_only(&self, followers: &str) -> bool {
+        !self.addresses(is_public_collection) && self.addresses(|id| id == followers)
+    }
+
+    /// Addressed to neither the public collection nor the author's `followers` collection; Only to specific actors or other collections
+    pub fn is_direct(&self, followers: &str) -> bool {
+        !self.addresses(|id| is_public_collection(id) || id == followers)
+    }
+
+    /// Whether `actor` may see this object
+    ///
+    /// That is the case if the object is public or unlisted, if `actor` is its author or a recipient, or if it is addressed to a collection that `actor` is a member of according to `is_member`.
+    pub fn is_visible_to(&self, actor: &str, is_member: impl Fn(&str) -> bool) -> bool {
+        addresses(&self.attributedTo, |id| id == actor) || self.addresses(|id| is_public_collection(id) || id == actor || is_member(id))
+    }
+}
+
+impl TaggedObject {
+    /// See [`Object::is_public`]; Links are never public
+    pub fn is_public(&self) -> bool {
+        self.object_fields().is_some_and(Object::is_public)
+    }
+
+    pub fn is_unlisted(&self) -> bool {
+        self.object_fields().is_some_and(Object::is_unlisted)
+    }
+
+    pub fn is_followers_only(&self, followers: &str) -> bool {
+        self.object_fields().is_some_and(|object| object.is_followers_only(followers))
+    }
+
+    pub fn is_direct(&self, followers: &str) -> bool {
+        self.object_fields().is_some_and(|object| object.is_direct(followers))
+    }
+
+    /// See [`Object::is_visible_to`]; For activities, the actor may see the activity as well
+    pub fn is_visible_to(&self, actor: &str, is_member: impl Fn(&str) -> bool) -> bool {
+        let is_actor = self.activity_fields().is_some_and(|activity| addresses(&activity.actor, |id| id == actor));
+        is_actor || self.object_fields().is_some_and(|object| object.is_visible_to(actor, is_member))
+    }
+}
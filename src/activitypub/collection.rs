@@ -0,0 +1,278 @@
+//! Traversal of (paged) collections, e.g. walking an outbox, and paging of served collections
+
+use std::collections::{HashSet, VecDeque};
+use reqwest::Url;
+use crate::activitypub::resolver::{ResolveError, Resolver};
+use crate::linkeddata::activity_streams::MaybeObject;
+use crate::linkeddata::activity_streams::objects::{Collection, CollectionPage, Object, OrderedCollection, OrderedCollectionPage};
+use crate::linkeddata::activity_streams::properties::{MaybeCollection, MaybeCollectionPage, TaggedCollection, TaggedCollectionPage};
+
+/// Lazy iterator over the items of a collection, fetching pages as needed
+///
+/// Inline items of the collection itself are returned if present; Otherwise pages are followed from `first` through `next`, or from `last` through `prev` when reversed.
+/// Iteration ends after the first error, or when a page is encountered a second time.
+pub struct CollectionItems<'r> {
+    resolver: &'r dyn Resolver,
+    reverse: bool,
+    items: VecDeque<MaybeObject>,
+    /// Index of the next item to be returned, if known
+    next_index: Option<u64>,
+    index: Option<u64>,
+    page: Option<Box<MaybeCollectionPage>>,
+    visited: HashSet<String>,
+    failed: bool,
+}
+
+impl<'r> CollectionItems<'r> {
+    pub fn new(collection: TaggedCollection, resolver: &'r dyn Resolver) -> Self {
+        CollectionItems::start(collection, resolver, false)
+    }
+
+    /// Iterate from the last item to the first
+    pub fn reversed(collection: TaggedCollection, resolver: &'r dyn Resolver) -> Self {
+        CollectionItems::start(collection, resolver, true)
+    }
+
+    fn start(collection: TaggedCollection, resolver: &'r dyn Resolver, reverse: bool) -> Self {
+        let mut iterator = CollectionItems {
+            resolver,
+            reverse,
+            items: VecDeque::new(),
+            next_index: None,
+            index: None,
+            page: None,
+            visited: HashSet::new(),
+            failed: false,
+        };
+        let (items, total_items, first, last) = match collection {
+            TaggedCollection::Collection(Collection { totalItems, first, last, items, .. }) => (items, totalItems, first, last),
+            TaggedCollection::OrderedCollection(OrderedCollection { totalItems, first, last, orderedItems, .. }) => (orderedItems, totalItems, first, last),
+            TaggedCollection::CollectionPage(page) => {
+                iterator.load(TaggedCollectionPage::CollectionPage(page));
+                return iterator;
+            }
+            TaggedCollection::OrderedCollectionPage(page) => {
+                iterator.load(TaggedCollectionPage::OrderedCollectionPage(page));
+                return iterator;
+            }
+        };
+        iterator.next_index = match reverse {
+            false => Some(0),
+            true => total_items.and_then(|total| total.checked_sub(1))
+        };
+        match items {
+            Some(items) => iterator.items = Vec::from(items).into(),
+            None => iterator.page = if reverse { last } else { first }
+        }
+        iterator
+    }
+
+    /// Index within the collection of the item most recently returned, if known from `startIndex` or the position in the traversal
+    pub fn index(&self) -> Option<u64> {
+        self.index
+    }
+
+    fn load(&mut self, page: TaggedCollectionPage) {
+        let (id, items, start_index, next, prev) = match page {
+            TaggedCollectionPage::CollectionPage(CollectionPage { next, prev, collection_fields, .. }) =>
+                (collection_fields.object_fields.id, collection_fields.items, None, next, prev),
+            TaggedCollectionPage::OrderedCollectionPage(OrderedCollectionPage { startIndex, next, prev, collection_fields, .. }) =>
+                (collection_fields.object_fields.id, collection_fields.orderedItems, startIndex, next, prev),
+        };
+        if let Some(id) = id {
+            if !self.visited.insert(id) {
+                return;
+            }
+        }
+        let items = items.map(Vec::from).unwrap_or_default();
+        if let Some(start_index) = start_index {
+            self.next_index = match self.reverse {
+                false => Some(start_index),
+                true => start_index.checked_add(items.len() as u64).and_then(|end| end.checked_sub(1))
+            };
+        }
+        self.items = items.into();
+        self.page = if self.reverse { prev } else { next };
+    }
+}
+
+impl Iterator for CollectionItems<'_> {
+    type Item = Result<MaybeObject, ResolveError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let item = if self.reverse { self.items.pop_back() } else { self.items.pop_front() };
+            if let Some(item) = item {
+                self.index = self.next_index;
+                self.next_index = match self.reverse {
+                    false => self.next_index.and_then(|index| index.checked_add(1)),
+                    true => self.next_index.and_then(|index| index.checked_sub(1))
+                };
+                return Some(Ok(item));
+            }
+            if self.failed {
+                return None;
+            }
+            let page = self.page.take()?;
+            let uri = match &*page {
+                MaybeCollectionPage::URI(uri) if self.visited.contains(uri) => return None,
+                MaybeCollectionPage::URI(uri) => Some(uri.clone()),
+                _ => None
+            };
+            match page.resolve_page(self.resolver) {
+                Ok(page) => {
+                    self.load(page);
+                    self.visited.extend(uri);
+                }
+                Err(error) => {
+                    self.failed = true;
+                    return Some(Err(error));
+                }
+            }
+        }
+    }
+}
+
+impl TaggedCollection {
+    pub fn iter_items(self, resolver: &dyn Resolver) -> CollectionItems<'_> {
+        CollectionItems::new(self, resolver)
+    }
+
+    pub fn iter_items_rev(self, resolver: &dyn Resolver) -> CollectionItems<'_> {
+        CollectionItems::reversed(self, resolver)
+    }
+}
+
+impl MaybeCollection {
+    /// Resolve this collection and iterate over its items
+    pub fn iter_items(self, resolver: &dyn Resolver) -> Result<CollectionItems<'_>, ResolveError> {
+        Ok(self.resolve_collection(resolver)?.iter_items(resolver))
+    }
+}
+
+/// One page of items from a cursor-based source
+#[derive(Debug, Default)]
+pub struct CursorPage {
+    pub items: Vec<MaybeObject>,
+    /// Cursor of the following page, if any
+    pub next: Option<String>,
+    /// Cursor of the preceding page, if any
+    pub prev: Option<String>,
+}
+
+/// Builds the OrderedCollection and OrderedCollectionPage documents of a served collection
+///
+/// Pages are identified by offset (`?offset=N`) or by an opaque cursor (`?page=true&cursor=C`), appended to the collection's id.
+#[derive(Debug, Clone)]
+pub struct CollectionPager {
+    id: String,
+    page_size: usize,
+}
+
+impl CollectionPager {
+    /// Panics if `page_size` is 0
+    pub fn new(id: &str, page_size: usize) -> Self {
+        assert!(page_size > 0, "page size must be positive");
+        CollectionPager { id: id.to_string(), page_size }
+    }
+
+    fn page_id(&self, query: &[(&str, &str)]) -> String {
+        match Url::parse(&self.id) {
+            Ok(mut url) => {
+                url.query_pairs_mut().extend_pairs(query);
+                url.to_string()
+            }
+            // Not a URL; Append the query as-is
+            Err(_) => {
+                let query = query.iter().map(|(name, value)| format!("{}={}", name, value)).collect::<Vec<_>>().join("&");
+                format!("{}?{}", self.id, query)
+            }
+        }
+    }
+
+    pub fn offset_page_id(&self, offset: u64) -> String {
+        self.page_id(&[("offset", &offset.to_string())])
+    }
+
+    /// Id of the page at `cursor`, or of the first page if `None`
+    pub fn cursor_page_id(&self, cursor: Option<&str>) -> String {
+        match cursor {
+            Some(cursor) => self.page_id(&[("page", "true"), ("cursor", cursor)]),
+            None => self.page_id(&[("page", "true")])
+        }
+    }
+
+    fn page_reference(id: String) -> Option<Box<MaybeCollectionPage>> {
+        Some(Box::new(MaybeCollectionPage::URI(id)))
+    }
+
+    fn collection(&self, total_items: Option<u64>, first: String, last: Option<String>) -> TaggedCollection {
+        TaggedCollection::OrderedCollection(OrderedCollection {
+            totalItems: total_items,
+            first: CollectionPager::page_reference(first),
+            last: last.and_then(CollectionPager::page_reference),
+            object_fields: Object { id: Some(self.id.clone()), ..Object::default() },
+            ..OrderedCollection::default()
+        })
+    }
+
+    fn page(&self, id: String, items: Vec<MaybeObject>, start_index: Option<u64>, next: Option<String>, prev: Option<String>) -> TaggedCollectionPage {
+        TaggedCollectionPage::OrderedCollectionPage(OrderedCollectionPage {
+            startIndex: start_index,
+            partOf: Some(Box::new(MaybeCollection::URI(self.id.clone()))),
+            next: next.and_then(CollectionPager::page_reference),
+            prev: prev.and_then(CollectionPager::page_reference),
+            collection_fields: OrderedCollection {
+                orderedItems: Some(items.into()),
+                object_fields: Object { id: Some(id), ..Object::default() },
+                ..OrderedCollection::default()
+            },
+        })
+    }
+
+    /// The collection document for offset-based paging
+    pub fn offset_collection(&self, total_items: u64) -> TaggedCollection {
+        let last = total_items.saturating_sub(1) / self.page_size as u64 * self.page_size as u64;
+        self.collection(Some(total_items), self.offset_page_id(0), Some(self.offset_page_id(last)))
+    }
+
+    /// The collection document for cursor-based paging; Without a cursor for the end of the collection, there is no `last` page
+    pub fn cursor_collection(&self, total_items: Option<u64>, last_cursor: Option<&str>) -> TaggedCollection {
+        self.collection(total_items, self.cursor_page_id(None), last_cursor.map(|cursor| self.cursor_page_id(Some(cursor))))
+    }
+
+    /// The page starting at `offset`, given the items from `offset` onwards
+    fn page_at<I: Iterator<Item = MaybeObject>>(&self, items: I, offset: u64) -> TaggedCollectionPage {
+        let mut items = items.take(self.page_size + 1).collect::<Vec<_>>();
+        let has_next = items.len() > self.page_size;
+        items.truncate(self.page_size);
+        let next = offset.checked_add(self.page_size as u64).filter(|_| has_next).map(|next| self.offset_page_id(next));
+        let prev = (offset > 0).then(|| self.offset_page_id(offset.saturating_sub(self.page_size as u64)));
+        self.page(self.offset_page_id(offset), items, Some(offset), next, prev)
+    }
+
+    /// The page starting at `offset`, taking items from an iterator over the whole collection
+    pub fn offset_page<I: IntoIterator<Item = MaybeObject>>(&self, items: I, offset: u64) -> TaggedCollectionPage {
+        self.page_at(items.into_iter().skip(usize::try_from(offset).unwrap_or(usize::MAX)), offset)
+    }
+
+    /// The page starting at `offset` of a slice, converting only the items on the page
+    pub fn slice_page<T, F: Fn(&T) -> MaybeObject>(&self, items: &[T], offset: u64, to_item: F) -> TaggedCollectionPage {
+        let start = usize::try_from(offset).unwrap_or(usize::MAX).min(items.len());
+        self.page_at(items[start..].iter().map(to_item), offset)
+    }
+
+    /// The page at `cursor` (the first page if `None`), with items and adjacent cursors provided by `fetch`
+    ///
+    /// `fetch` is called with the cursor and the page size.
+    pub fn cursor_page<F: FnOnce(Option<&str>, usize) -> CursorPage>(&self, cursor: Option<&str>, fetch: F) -> TaggedCollectionPage {
+        let CursorPage { items, next, prev } = fetch(cursor, self.page_size);
+        self.page(
+            self.cursor_page_id(cursor),
+            items,
+            None,
+            next.map(|next| self.cursor_page_id(Some(&next))),
+            prev.map(|prev| self.cursor_page_id(Some(&prev))),
+        )
+    }
+}
@@ -0,0 +1,350 @@
+//! Server-to-server delivery; Resolution of an activity's audience to inboxes, and signed POSTs to those inboxes with retries
+
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+use chrono::{DateTime, Duration, Utc};
+use reqwest::blocking::Client;
+use reqwest::header::{CONTENT_TYPE, DATE, HOST};
+use reqwest::Url;
+use serde::Deserialize;
+use serde_json::Value;
+use crate::activitypub::addressing::is_public_collection;
+use crate::activitypub::http_signature::{digest_header, http_date, HttpSignatureKey};
+use crate::activitypub::hydrate::BudgetedResolver;
+use crate::activitypub::outbox::push_ids;
+use crate::activitypub::resolver::{resolve, ResolveError, Resolver};
+use crate::linkeddata::activity_streams::TaggedObject;
+use crate::linkeddata::activity_streams::properties::TaggedCollection;
+use crate::linkeddata::signature::SignatureError;
+
+/// Content-Type of delivered activities
+pub const ACTIVITY_JSON_CONTENT_TYPE: &str = "application/ld+json; profile=\"https://www.w3.org/ns/activitystreams\"";
+
+/// Maximum number of documents fetched by [`Audience::resolve`]; Collection pages and their members' actor documents both count
+pub const DEFAULT_AUDIENCE_FETCH_BUDGET: usize = 10_000;
+
+/// The addressed recipients of an activity; `to`, `bto`, `cc`, `bcc` and `audience`
+pub fn recipients(activity: &TaggedObject) -> Vec<String> {
+    let mut recipients = Vec::new();
+    if let Some(object) = activity.object_fields() {
+        for addressing in [&object.to, &object.bto, &object.cc, &object.bcc, &object.audience] {
+            push_ids(&mut recipients, addressing);
+        }
+    }
+    recipients
+}
+
+/// The inbox properties of an actor document
+#[derive(Debug, Deserialize)]
+struct ActorInboxes {
+    inbox: Option<String>,
+    endpoints: Option<Endpoints>,
+}
+
+#[allow(non_snake_case)]    // Field names map directly to those in the received JSON
+#[derive(Debug, Deserialize)]
+struct Endpoints {
+    sharedInbox: Option<String>,
+}
+
+/// Inboxes of an activity's audience
+#[derive(Debug, Default)]
+pub struct Audience {
+    pub inboxes: Vec<String>,
+    /// Recipients that could not be resolved to an inbox
+    pub errors: Vec<(String, ResolveError)>,
+}
+
+impl Audience {
+    fn add_actor(&mut self, actor: &str, inboxes: ActorInboxes, shared_inboxes: bool) {
+        let shared_inbox = inboxes.endpoints.and_then(|endpoints| endpoints.sharedInbox).filter(|_| shared_inboxes);
+        match shared_inbox.or(inboxes.inbox) {
+            Some(inbox) if !self.inboxes.contains(&inbox) => self.inboxes.push(inbox),
+            Some(_) => {}
+            None => self.errors.push((actor.to_string(), ResolveError::UnexpectedType("expected an actor with an inbox")))
+        }
+    }
+
+    /// Resolve recipients to inboxes, skipping the public collection (which has no inbox) and `sender`
+    ///
+    /// Collections (such as followers) are expanded one level deep; Their members must be actors.
+    /// With `shared_inboxes`, actors' `sharedInbox` endpoint is used where available, so that servers hosting several recipients receive the activity once.
+    /// At most [`DEFAULT_AUDIENCE_FETCH_BUDGET`] documents are fetched.
+    pub fn resolve(resolver: &dyn Resolver, sender: &str, recipients: &[String], shared_inboxes: bool) -> Audience {
+        Audience::resolve_with_budget(resolver, sender, recipients, shared_inboxes, DEFAULT_AUDIENCE_FETCH_BUDGET)
+    }
+
+    /// [`Audience::resolve`], fetching at most `budget` documents; Once exhausted, the remaining recipients are left unresolved
+    pub fn resolve_with_budget(resolver: &dyn Resolver, sender: &str, recipients: &[String], shared_inboxes: bool, budget: usize) -> Audience {
+        let resolver = &BudgetedResolver::new(resolver, budget);
+        let mut audience = Audience::default();
+        for recipient in recipients.iter().filter(|recipient| !is_public_collection(recipient) && *recipient != sender) {
+            let document = match resolve::<Value>(resolver, recipient) {
+                Ok(document) => document,
+                Err(ResolveError::FetchBudgetExhausted) => {
+                    audience.errors.push((recipient.clone(), ResolveError::FetchBudgetExhausted));
+                    break;
+                }
+                Err(error) => {
+                    audience.errors.push((recipient.clone(), error));
+                    continue;
+                }
+            };
+            if document.get("inbox").is_some() {
+                match ActorInboxes::deserialize(&document) {
+                    Ok(inboxes) => audience.add_actor(recipient, inboxes, shared_inboxes),
+                    Err(error) => audience.errors.push((recipient.clone(), ResolveError::Deserialization(error)))
+                }
+                continue;
+            }
+            let collection = match TaggedCollection::deserialize(document) {
+                Ok(collection) => collection,
+                Err(_) => {
+                    audience.errors.push((recipient.clone(), ResolveError::UnexpectedType("expected an actor or a collection")));
+                    continue;
+                }
+            };
+            for member in collection.iter_items(resolver) {
+                let member = match member {
+                    Ok(member) => member,
+                    Err(error) => {
+                        audience.errors.push((recipient.clone(), error));
+                        break;
+                    }
+                };
+                let Some(member) = member.id().filter(|member| *member != sender) else { continue };
+                match resolve::<ActorInboxes>(resolver, member) {
+                    Ok(inboxes) => audience.add_actor(member, inboxes, shared_inboxes),
+                    Err(ResolveError::FetchBudgetExhausted) => {
+                        audience.errors.push((member.to_string(), ResolveError::FetchBudgetExhausted));
+                        return audience;
+                    }
+                    Err(error) => audience.errors.push((member.to_string(), error))
+                }
+            }
+            if matches!(audience.errors.last(), Some((_, ResolveError::FetchBudgetExhausted))) {
+                break;
+            }
+        }
+        audience
+    }
+}
+
+#[derive(Debug)]
+pub enum DeliveryError {
+    InvalidInbox(String),
+    Http(reqwest::Error),
+    /// The inbox responded with a non-success status code
+    Status(u16),
+    Signature(SignatureError),
+}
+
+impl DeliveryError {
+    /// Whether the delivery may succeed when retried later
+    pub fn is_transient(&self) -> bool {
+        match self {
+            DeliveryError::Http(_) => true,
+            DeliveryError::Status(status) => matches!(status, 408 | 429) || *status >= 500,
+            DeliveryError::InvalidInbox(_) | DeliveryError::Signature(_) => false
+        }
+    }
+}
+
+impl Display for DeliveryError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeliveryError::InvalidInbox(inbox) => write!(f, "invalid inbox: {}", inbox),
+            DeliveryError::Http(error) => write!(f, "HTTP error: {}", error),
+            DeliveryError::Status(status) => write!(f, "inbox responded with status {}", status),
+            DeliveryError::Signature(error) => write!(f, "could not sign request: {}", error),
+        }
+    }
+}
+
+impl Error for DeliveryError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            DeliveryError::Http(error) => Some(error),
+            DeliveryError::Signature(error) => Some(error),
+            _ => None
+        }
+    }
+}
+
+impl From<reqwest::Error> for DeliveryError {
+    fn from(error: reqwest::Error) -> Self {
+        DeliveryError::Http(error)
+    }
+}
+
+/// A pending POST of an activity to an inbox
+#[derive(Debug, Clone)]
+pub struct Delivery {
+    pub inbox: String,
+    /// The serialized activity; Shared between the deliveries of one activity
+    pub body: Arc<[u8]>,
+    /// Number of failed attempts so far
+    pub attempts: u32,
+    /// The delivery is not attempted before this time
+    pub not_before: DateTime<Utc>,
+}
+
+impl Delivery {
+    /// Deliveries of an activity to each inbox, due immediately
+    pub fn to_inboxes(activity: &Value, inboxes: &[String], now: DateTime<Utc>) -> Vec<Delivery> {
+        let body = Arc::<[u8]>::from(serde_json::to_vec(activity).expect("JSON values serialize"));
+        inboxes.iter()
+            .map(|inbox| Delivery { inbox: inbox.clone(), body: body.clone(), attempts: 0, not_before: now })
+            .collect()
+    }
+}
+
+/// Storage of pending deliveries; Implement this to persist deliveries across restarts
+pub trait DeliveryQueue {
+    fn push(&mut self, delivery: Delivery);
+
+    /// Take a delivery that is due at `now`, if any
+    fn pop_due(&mut self, now: DateTime<Utc>) -> Option<Delivery>;
+}
+
+/// Delivery queue held in memory
+#[derive(Debug, Default)]
+pub struct InMemoryQueue {
+    deliveries: Vec<Delivery>,
+}
+
+impl InMemoryQueue {
+    pub fn new() -> Self {
+        InMemoryQueue::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.deliveries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.deliveries.is_empty()
+    }
+}
+
+impl DeliveryQueue for InMemoryQueue {
+    fn push(&mut self, delivery: Delivery) {
+        self.deliveries.push(delivery);
+    }
+
+    fn pop_due(&mut self, now: DateTime<Utc>) -> Option<Delivery> {
+        let index = self.deliveries.iter()
+            .enumerate()
+            .filter(|(_, delivery)| delivery.not_before <= now)
+            .min_by_key(|(_, delivery)| delivery.not_before)
+            .map(|(index, _)| index)?;
+        Some(self.deliveries.remove(index))
+    }
+}
+
+/// Outcome of [`Deliverer::run`]
+#[derive(Debug, Default)]
+pub struct DeliveryReport {
+    /// Inboxes the activity was delivered to
+    pub delivered: Vec<String>,
+    /// Failed deliveries that were queued again
+    pub retrying: Vec<(String, DeliveryError)>,
+    /// Deliveries that failed permanently, or ran out of attempts
+    pub failed: Vec<(String, DeliveryError)>,
+}
+
+/// Performs deliveries as signed POST requests
+#[derive(Debug, Clone)]
+pub struct Deliverer {
+    client: Client,
+    key: HttpSignatureKey,
+    max_attempts: u32,
+    backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl Deliverer {
+    /// Deliverer signing with `key`; Deliveries are attempted up to 8 times, with exponential backoff starting at one minute, and waits of at most a day
+    pub fn new(key: HttpSignatureKey) -> Self {
+        Deliverer { client: Client::new(), key, max_attempts: 8, backoff: Duration::minutes(1), max_backoff: Duration::days(1) }
+    }
+
+    pub fn with_client(self, client: Client) -> Self {
+        Deliverer { client, ..self }
+    }
+
+    /// Attempt deliveries up to `max_attempts` times, waiting `backoff` after the first failure, doubling with each further failure
+    pub fn with_retry(self, max_attempts: u32, backoff: Duration) -> Self {
+        Deliverer { max_attempts, backoff, ..self }
+    }
+
+    /// Wait at most `max_backoff` between attempts
+    pub fn with_max_backoff(self, max_backoff: Duration) -> Self {
+        Deliverer { max_backoff, ..self }
+    }
+
+    /// POST a body to an inbox, signing `(request-target)`, host, date, digest and content-type
+    pub fn deliver(&self, inbox: &str, body: &[u8]) -> Result<(), DeliveryError> {
+        let url = Url::parse(inbox).map_err(|_| DeliveryError::InvalidInbox(inbox.to_string()))?;
+        let host = match url.port() {
+            Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+            None => url.host_str().unwrap_or_default().to_string()
+        };
+        let path = match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_string()
+        };
+        let date = http_date(Utc::now());
+        let digest = digest_header(body);
+        let signature = self.key
+            .sign("POST", &path, &[("host", &host), ("date", &date), ("digest", &digest), ("content-type", ACTIVITY_JSON_CONTENT_TYPE)])
+            .map_err(DeliveryError::Signature)?;
+        let response = self.client.post(url)
+            .header(HOST, host)
+            .header(DATE, date)
+            .header("Digest", digest)
+            .header(CONTENT_TYPE, ACTIVITY_JSON_CONTENT_TYPE)
+            .header("Signature", signature)
+            .body(body.to_vec())
+            .send()?;
+        match response.status() {
+            status if status.is_success() => Ok(()),
+            status => Err(DeliveryError::Status(status.as_u16()))
+        }
+    }
+
+    /// Wait before the next attempt of a delivery that failed `attempts` times
+    fn backoff(&self, attempts: u32) -> Duration {
+        attempts.checked_sub(1)
+            .and_then(|exponent| 2i64.checked_pow(exponent))
+            .and_then(|factor| self.backoff.num_milliseconds().checked_mul(factor))
+            .map_or(self.max_backoff, |backoff| Duration::milliseconds(backoff).min(self.max_backoff))
+    }
+
+    /// Attempt all deliveries that are due at `now`, each at most once; Transient failures are queued again with backoff
+    pub fn run(&self, queue: &mut dyn DeliveryQueue, now: DateTime<Utc>) -> DeliveryReport {
+        let mut report = DeliveryReport::default();
+        let mut retries = Vec::new();
+        while let Some(mut delivery) = queue.pop_due(now) {
+            match self.deliver(&delivery.inbox, &delivery.body) {
+                Ok(()) => report.delivered.push(delivery.inbox),
+                Err(error) => {
+                    delivery.attempts = delivery.attempts.saturating_add(1);
+                    if error.is_transient() && delivery.attempts < self.max_attempts {
+                        delivery.not_before = now.checked_add_signed(self.backoff(delivery.attempts)).unwrap_or(DateTime::<Utc>::MAX_UTC);
+                        report.retrying.push((delivery.inbox.clone(), error));
+                        retries.push(delivery);
+                    } else {
+                        report.failed.push((delivery.inbox, error));
+                    }
+                }
+            }
+        }
+        // Queued after the run, so that deliveries due again at once are not retried until the next run
+        for delivery in retries {
+            queue.push(delivery);
+        }
+        report
+    }
+}
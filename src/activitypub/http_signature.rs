@@ -0,0 +1,108 @@
+//! HTTP Signatures (draft-cavage-http-signatures) with rsa-sha256, as used for signed fetches and deliveries
+//!
+//! Headers are signed in the order given; `(request-target)` is included as a pseudo-header when listed.
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use chrono::{DateTime, Utc};
+use rsa::{RsaPrivateKey, RsaPublicKey};
+use rsa::pkcs1v15::{Signature, SigningKey, VerifyingKey};
+use rsa::signature::{SignatureEncoding, Signer, Verifier};
+use sha2::{Digest, Sha256};
+use crate::linkeddata::signature::SignatureError;
+
+/// Key used to sign outgoing requests on behalf of an actor
+#[derive(Debug, Clone)]
+pub struct HttpSignatureKey {
+    /// The key's ID, usually of the form `actor#main-key`
+    pub key_id: String,
+    pub private_key: RsaPrivateKey,
+}
+
+/// Format a time as an HTTP date (RFC 7231 IMF-fixdate)
+pub fn http_date(time: DateTime<Utc>) -> String {
+    time.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+/// `Digest` header value for a request body
+pub fn digest_header(body: &[u8]) -> String {
+    format!("SHA-256={}", BASE64.encode(Sha256::digest(body)))
+}
+
+fn signing_string(method: &str, path: &str, headers: &[(&str, &str)], signed: &[&str]) -> Result<String, SignatureError> {
+    signed.iter()
+        .map(|name| match *name {
+            "(request-target)" => Ok(format!("(request-target): {} {}", method.to_lowercase(), path)),
+            name => headers.iter()
+                .find(|(header, _)| header.eq_ignore_ascii_case(name))
+                .map(|(_, value)| format!("{}: {}", name.to_lowercase(), value.trim()))
+                .ok_or(SignatureError::MalformedSignature("signed header is missing from request"))
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map(|lines| lines.join("\n"))
+}
+
+impl HttpSignatureKey {
+    /// Compute the `Signature` header value for a request, signing `(request-target)` followed by all given headers
+    pub fn sign(&self, method: &str, path: &str, headers: &[(&str, &str)]) -> Result<String, SignatureError> {
+        let signed = std::iter::once("(request-target)")
+            .chain(headers.iter().map(|(name, _)| *name))
+            .collect::<Vec<_>>();
+        let input = signing_string(method, path, headers, &signed)?;
+        let signature = SigningKey::<Sha256>::new(self.private_key.clone()).sign(input.as_bytes());
+        Ok(format!(
+            "keyId=\"{}\",algorithm=\"rsa-sha256\",headers=\"{}\",signature=\"{}\"",
+            self.key_id,
+            signed.join(" ").to_lowercase(),
+            BASE64.encode(signature.to_bytes())
+        ))
+    }
+}
+
+/// Parsed `Signature` header
+#[derive(Debug, Clone)]
+pub struct SignatureHeader {
+    pub key_id: String,
+    pub algorithm: Option<String>,
+    pub headers: Vec<String>,
+    pub signature: String,
+}
+
+impl SignatureHeader {
+    pub fn parse(header: &str) -> Result<SignatureHeader, SignatureError> {
+        let (mut key_id, mut algorithm, mut headers, mut signature) = (None, None, None, None);
+        for parameter in header.split(',') {
+            let (name, value) = parameter.trim().split_once('=').ok_or(SignatureError::MalformedSignature("invalid signature parameter"))?;
+            let value = value.trim_matches('"').to_string();
+            match name {
+                "keyId" => key_id = Some(value),
+                "algorithm" => algorithm = Some(value),
+                "headers" => headers = Some(value.split_whitespace().map(str::to_string).collect()),
+                "signature" => signature = Some(value),
+                _ => {}
+            }
+        }
+        Ok(SignatureHeader {
+            key_id: key_id.ok_or(SignatureError::MalformedSignature("signature has no keyId"))?,
+            algorithm,
+            // Per the draft, only the Date header is signed if no header list is given
+            headers: headers.unwrap_or_else(|| vec!["date".to_string()]),
+            signature: signature.ok_or(SignatureError::MalformedSignature("signature has no signature value"))?,
+        })
+    }
+
+    /// Verify this signature for a request
+    pub fn verify(&self, method: &str, path: &str, headers: &[(&str, &str)], key: &RsaPublicKey) -> Result<(), SignatureError> {
+        match self.algorithm.as_deref() {
+            None | Some("rsa-sha256") | Some("hs2019") => {}
+            Some(algorithm) => return Err(SignatureError::UnsupportedSignatureType(algorithm.to_string()))
+        }
+        let signed = self.headers.iter().map(String::as_str).collect::<Vec<_>>();
+        let input = signing_string(method, path, headers, &signed)?;
+        let signature = BASE64.decode(&self.signature).map_err(|_| SignatureError::MalformedSignature("signature is not base64"))?;
+        let signature = Signature::try_from(signature.as_slice()).map_err(|_| SignatureError::MalformedSignature("signature is not an RSA signature"))?;
+        VerifyingKey::<Sha256>::new(key.clone())
+            .verify(input.as_bytes(), &signature)
+            .map_err(|_| SignatureError::Invalid)
+    }
+}
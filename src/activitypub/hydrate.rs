@@ -0,0 +1,213 @@
+//! Hydration; Replacing URI references in an object's properties by the objects they identify, e.g. to render a thread
+//!
+//! Flattening is the inverse, replacing embedded objects by their id to obtain a normalised form for storage.
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use serde_json::Value;
+use crate::activitypub::resolver::{ResolveError, Resolver};
+use crate::linkeddata::activity_streams::{MaybeObject, TaggedObject};
+use crate::linkeddata::activity_streams::objects::{Mention, Relationship};
+
+/// Maximum number of documents fetched by [`TaggedObject::hydrate`]
+pub const DEFAULT_FETCH_BUDGET: usize = 64;
+
+/// Object-valued properties that can be hydrated
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum HydrateProperty {
+    Actor,
+    Attachment,
+    AttributedTo,
+    Context,
+    Generator,
+    InReplyTo,
+    Instrument,
+    Location,
+    Object,
+    Origin,
+    Preview,
+    Result,
+    Subject,
+    Tag,
+    Target,
+}
+
+impl HydrateProperty {
+    /// Properties needed to render a thread
+    pub const THREAD: &'static [HydrateProperty] = &[HydrateProperty::InReplyTo, HydrateProperty::AttributedTo, HydrateProperty::Object, HydrateProperty::Tag];
+
+    pub const ALL: &'static [HydrateProperty] = &[
+        HydrateProperty::Actor, HydrateProperty::Attachment, HydrateProperty::AttributedTo, HydrateProperty::Context, HydrateProperty::Generator,
+        HydrateProperty::InReplyTo, HydrateProperty::Instrument, HydrateProperty::Location, HydrateProperty::Object, HydrateProperty::Origin,
+        HydrateProperty::Preview, HydrateProperty::Result, HydrateProperty::Subject, HydrateProperty::Tag, HydrateProperty::Target,
+    ];
+
+    /// The values of this property on `object`, if it has the property and it is set
+    pub fn values_mut(self, object: &mut TaggedObject) -> Option<&mut [MaybeObject]> {
+        match (self, object) {
+            (HydrateProperty::Object, TaggedObject::Relationship(Relationship { object, .. })) => object.as_deref_mut(),
+            (HydrateProperty::Subject, TaggedObject::Relationship(Relationship { subject, .. })) => subject.as_deref_mut().map(std::slice::from_mut),
+            (HydrateProperty::Preview, TaggedObject::Mention(Mention(link))) => link.preview.as_deref_mut(),
+            (HydrateProperty::Actor, object) => object.activity_fields_mut()?.actor.as_deref_mut(),
+            (HydrateProperty::Instrument, object) => object.activity_fields_mut()?.instrument.as_deref_mut(),
+            (HydrateProperty::Object, object) => object.activity_fields_mut()?.object.as_deref_mut(),
+            (HydrateProperty::Origin, object) => object.activity_fields_mut()?.origin.as_deref_mut(),
+            (HydrateProperty::Result, object) => object.activity_fields_mut()?.result.as_deref_mut(),
+            (HydrateProperty::Target, object) => object.activity_fields_mut()?.target.as_deref_mut(),
+            (HydrateProperty::Attachment, object) => object.object_fields_mut()?.attachment.as_deref_mut(),
+            (HydrateProperty::AttributedTo, object) => object.object_fields_mut()?.attributedTo.as_deref_mut(),
+            (HydrateProperty::Context, object) => object.object_fields_mut()?.context.as_deref_mut(),
+            (HydrateProperty::Generator, object) => object.object_fields_mut()?.generator.as_deref_mut(),
+            (HydrateProperty::InReplyTo, object) => object.object_fields_mut()?.inReplyTo.as_deref_mut(),
+            (HydrateProperty::Location, object) => object.object_fields_mut()?.location.as_deref_mut(),
+            (HydrateProperty::Preview, object) => object.object_fields_mut()?.preview.as_deref_mut(),
+            (HydrateProperty::Tag, object) => object.object_fields_mut()?.tag.as_deref_mut(),
+            (HydrateProperty::Subject, _) => None,
+        }
+    }
+}
+
+/// Resolver wrapper that caches fetched documents and limits the number of fetches
+pub(crate) struct BudgetedResolver<'r> {
+    resolver: &'r dyn Resolver,
+    budget: Cell<usize>,
+    documents: RefCell<HashMap<String, Value>>,
+}
+
+impl<'r> BudgetedResolver<'r> {
+    pub(crate) fn new(resolver: &'r dyn Resolver, budget: usize) -> Self {
+        BudgetedResolver { resolver, budget: Cell::new(budget), documents: RefCell::new(HashMap::new()) }
+    }
+}
+
+impl Resolver for BudgetedResolver<'_> {
+    fn fetch(&self, uri: &str) -> Result<Value, ResolveError> {
+        if let Some(document) = self.documents.borrow().get(uri) {
+            return Ok(document.clone());
+        }
+        match self.budget.get() {
+            0 => Err(ResolveError::FetchBudgetExhausted),
+            budget => {
+                self.budget.set(budget - 1);
+                let document = self.resolver.fetch(uri)?;
+                self.documents.borrow_mut().insert(uri.to_string(), document.clone());
+                Ok(document)
+            }
+        }
+    }
+}
+
+/// Recursively replaces URI references by fetched objects
+///
+/// Each document is fetched at most once; References to an object's own ancestors are left as URIs, so cyclic threads terminate.
+pub struct Hydrator<'r> {
+    resolver: BudgetedResolver<'r>,
+    properties: &'r [HydrateProperty],
+    errors: Vec<(String, ResolveError)>,
+}
+
+impl<'r> Hydrator<'r> {
+    pub fn new(resolver: &'r dyn Resolver, properties: &'r [HydrateProperty]) -> Self {
+        Hydrator {
+            resolver: BudgetedResolver::new(resolver, DEFAULT_FETCH_BUDGET),
+            properties,
+            errors: Vec::new(),
+        }
+    }
+
+    /// Limit the number of documents fetched; Once exhausted, remaining references are left as URIs
+    pub fn with_fetch_budget(self, budget: usize) -> Self {
+        self.resolver.budget.set(budget);
+        self
+    }
+
+    pub fn remaining_budget(&self) -> usize {
+        self.resolver.budget.get()
+    }
+
+    /// References that could not be resolved, with the reason; These are left as URIs
+    pub fn errors(&self) -> &[(String, ResolveError)] {
+        &self.errors
+    }
+
+    pub fn into_errors(self) -> Vec<(String, ResolveError)> {
+        self.errors
+    }
+
+    /// Hydrate up to `depth` levels of references; Depth 1 only replaces the references of `object` itself
+    pub fn hydrate(&mut self, object: &mut TaggedObject, depth: usize) {
+        self.hydrate_object(object, depth, &mut Vec::new());
+    }
+
+    fn hydrate_object(&mut self, object: &mut TaggedObject, depth: usize, ancestors: &mut Vec<String>) {
+        if depth == 0 {
+            return;
+        }
+        let id = object.id().map(str::to_string);
+        if let Some(id) = &id {
+            ancestors.push(id.clone());
+        }
+        for &property in self.properties {
+            if let Some(values) = property.values_mut(object) {
+                for value in values {
+                    self.hydrate_value(value, depth, ancestors);
+                }
+            }
+        }
+        if id.is_some() {
+            ancestors.pop();
+        }
+    }
+
+    fn hydrate_value(&mut self, value: &mut MaybeObject, depth: usize, ancestors: &mut Vec<String>) {
+        match value {
+            MaybeObject::URI(uri) if ancestors.contains(uri) => {}
+            MaybeObject::URI(uri) => match MaybeObject::URI(uri.clone()).resolve(&self.resolver) {
+                Ok(mut resolved) => {
+                    if let MaybeObject::TaggedObject(object) = &mut resolved {
+                        self.hydrate_object(object, depth - 1, ancestors);
+                    }
+                    *value = resolved;
+                }
+                Err(error) => self.errors.push((uri.clone(), error))
+            },
+            MaybeObject::TaggedObject(object) => self.hydrate_object(object, depth - 1, ancestors),
+            MaybeObject::TaggedLink(_) => {}
+        }
+    }
+}
+
+impl TaggedObject {
+    /// Hydrate the given properties up to `depth` levels, with the default fetch budget
+    ///
+    /// Returns the references that could not be resolved.
+    pub fn hydrate(&mut self, resolver: &dyn Resolver, depth: usize, properties: &[HydrateProperty]) -> Vec<(String, ResolveError)> {
+        let mut hydrator = Hydrator::new(resolver, properties);
+        hydrator.hydrate(self, depth);
+        hydrator.into_errors()
+    }
+
+    /// Replace embedded objects in the given properties by their id, recursively
+    ///
+    /// Returns the extracted objects by id, themselves flattened; Embedded objects without an id cannot be referenced and are left in place.
+    /// If several embedded copies share an id, the first one is kept.
+    pub fn flatten(&mut self, properties: &[HydrateProperty]) -> HashMap<String, TaggedObject> {
+        let mut extracted = HashMap::new();
+        flatten_into(self, properties, &mut extracted);
+        extracted
+    }
+}
+
+fn flatten_into(object: &mut TaggedObject, properties: &[HydrateProperty], extracted: &mut HashMap<String, TaggedObject>) {
+    for &property in properties {
+        let Some(values) = property.values_mut(object) else { continue };
+        for value in values {
+            let MaybeObject::TaggedObject(embedded) = value else { continue };
+            flatten_into(embedded, properties, extracted);
+            let Some(id) = embedded.id().map(str::to_string) else { continue };
+            if let MaybeObject::TaggedObject(embedded) = std::mem::replace(value, MaybeObject::URI(id.clone())) {
+                extracted.entry(id).or_insert(embedded);
+            }
+        }
+    }
+}
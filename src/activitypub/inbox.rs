@@ -0,0 +1,277 @@
+//! Server-to-server inbox processing; Routing of received activities to typed handlers
+//!
+//! [`dispatch`] applies the side effects the ActivityPub specification prescribes for an activity through the storage hooks of [`InboxHandler`], then calls the handler for the activity's type.
+//! Both are no-ops by default, so an application only implements what it needs.
+
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+use crate::activitypub::resolver::same_origin;
+use crate::linkeddata::LinkedData;
+use crate::linkeddata::activity_streams::{MaybeObject, TaggedObject};
+use crate::linkeddata::activity_streams::objects::*;
+use crate::linkeddata::util::FoldedSlice;
+
+#[derive(Debug)]
+pub enum InboxError {
+    /// The received object is not an activity
+    NotAnActivity,
+    /// The activity has no actor
+    MissingActor,
+    /// The activity's actor may not perform it, e.g. undoing another actor's activity
+    Unauthorized(&'static str),
+}
+
+impl Display for InboxError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InboxError::NotAnActivity => write!(f, "received object is not an activity"),
+            InboxError::MissingActor => write!(f, "activity has no actor"),
+            InboxError::Unauthorized(reason) => write!(f, "unauthorized activity: {}", reason),
+        }
+    }
+}
+
+impl Error for InboxError {}
+
+/// The first value of an activity property, as a URI
+fn first_id(property: &Option<FoldedSlice<MaybeObject>>) -> Option<&str> {
+    property.as_deref().and_then(<[_]>::first).and_then(MaybeObject::id)
+}
+
+/// Handlers for received activities, and the storage hooks through which their side effects are applied
+///
+/// All activities passed to handlers have an actor; Received activities should be verified (e.g. through HTTP or Linked Data signatures) before being dispatched.
+#[allow(unused_variables)]
+pub trait InboxHandler {
+    type Error: From<InboxError>;
+
+    /// Whether `id` refers to an actor or object on this server
+    fn is_local(&self, id: &str) -> bool {
+        false
+    }
+
+    /// The actor a Follow sent by a local actor is addressed to, if `follow` is the id of one awaiting acceptance
+    fn pending_follow(&self, follow: &str) -> Option<String> {
+        None
+    }
+
+    /// The pending Follow with id `follow` was accepted by `followed`, the actor it is addressed to
+    fn add_following(&mut self, follow: &str, followed: &str) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// `follower` no longer follows `actor`
+    fn remove_follower(&mut self, actor: &str, follower: &str) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Add a Like activity to the `likes` collection of a local object
+    fn add_like(&mut self, object: &str, like: &str) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn remove_like(&mut self, object: &str, like: &str) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Add an Announce activity to the `shares` collection of a local object
+    fn add_share(&mut self, object: &str, announce: &str) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn remove_share(&mut self, object: &str, announce: &str) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// The actor a stored collection is attributed to, if known
+    fn collection_owner(&self, collection: &str) -> Option<String> {
+        None
+    }
+
+    /// Add an object to a collection owned by `actor`, e.g. a copy of its featured collection
+    ///
+    /// Only called if [`collection_owner`](InboxHandler::collection_owner) is `actor`, or, if unknown, the collection is on the actor's origin;
+    /// Other Adds are left to [`on_add`](InboxHandler::on_add).
+    fn add_to_collection(&mut self, actor: &str, target: &str, object: &str) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn remove_from_collection(&mut self, actor: &str, target: &str, object: &str) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Remove a stored copy of a remote object, replacing it by a Tombstone
+    fn delete_object(&mut self, object: &str) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn on_create(&mut self, create: &Create) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn on_update(&mut self, update: &Update) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn on_delete(&mut self, delete: &Delete) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Called for every Follow; To accept, the application sends an Accept and records the follower
+    fn on_follow(&mut self, follow: &Follow) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn on_accept(&mut self, accept: &Accept) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn on_reject(&mut self, reject: &Reject) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn on_add(&mut self, add: &Add) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn on_remove(&mut self, remove: &Remove) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn on_like(&mut self, like: &Like) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn on_announce(&mut self, announce: &Announce) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn on_undo(&mut self, undo: &Undo) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn on_block(&mut self, block: &Block) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Called for activity types without a dedicated handler
+    fn on_other(&mut self, activity: &TaggedObject) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// Whether `actor` owns the collection `target`
+fn owns_collection<H: InboxHandler + ?Sized>(handler: &H, actor: &str, target: &str) -> bool {
+    match handler.collection_owner(target) {
+        Some(owner) => owner == actor,
+        None => same_origin(actor, target)
+    }
+}
+
+/// Apply the side effects of a received activity, then pass it to the handler for its type
+pub fn dispatch<H: InboxHandler + ?Sized, C: Debug>(handler: &mut H, document: &LinkedData<TaggedObject, C>) -> Result<(), H::Error> {
+    let activity = &document.data;
+    let fields = activity.activity_fields().ok_or(InboxError::NotAnActivity)?;
+    let actor = first_id(&fields.actor).ok_or(InboxError::MissingActor)?;
+    let object = first_id(&fields.object);
+    let id = fields.object_fields.id.as_deref();
+
+    match activity {
+        TaggedObject::Create(create) => handler.on_create(create),
+        TaggedObject::Update(update) => handler.on_update(update),
+        TaggedObject::Delete(delete) => {
+            if let Some(object) = object {
+                if !same_origin(actor, object) {
+                    return Err(InboxError::Unauthorized("actor cannot delete an object of another origin").into());
+                }
+                handler.delete_object(object)?;
+            }
+            handler.on_delete(delete)
+        }
+        TaggedObject::Follow(follow) => handler.on_follow(follow),
+        TaggedObject::Accept(accept) => {
+            // The object is the Follow sent, either embedded or by id
+            let follow = match fields.object.as_deref().and_then(<[_]>::first) {
+                Some(MaybeObject::TaggedObject(TaggedObject::Follow(Follow(follow)))) => {
+                    if first_id(&follow.object) != Some(actor) {
+                        return Err(InboxError::Unauthorized("only the followed actor can accept a Follow").into());
+                    }
+                    follow.object_fields.id.as_deref()
+                }
+                Some(MaybeObject::URI(follow)) => Some(follow.as_str()),
+                _ => None
+            };
+            // The Follow is looked up, as an embedded one is only the Accept's claim of what was sent
+            if let Some(follow) = follow {
+                match handler.pending_follow(follow) {
+                    Some(followed) if followed == actor => handler.add_following(follow, actor)?,
+                    Some(_) => return Err(InboxError::Unauthorized("only the followed actor can accept a Follow").into()),
+                    None => {}
+                }
+            }
+            handler.on_accept(accept)
+        }
+        TaggedObject::Reject(reject) => handler.on_reject(reject),
+        TaggedObject::Add(add) => {
+            if let (Some(target), Some(object)) = (first_id(&fields.target), object) {
+                if owns_collection(handler, actor, target) {
+                    handler.add_to_collection(actor, target, object)?;
+                }
+            }
+            handler.on_add(add)
+        }
+        TaggedObject::Remove(remove) => {
+            if let (Some(target), Some(object)) = (first_id(&fields.target), object) {
+                if owns_collection(handler, actor, target) {
+                    handler.remove_from_collection(actor, target, object)?;
+                }
+            }
+            handler.on_remove(remove)
+        }
+        TaggedObject::Like(like) => {
+            if id.is_some_and(|id| !same_origin(actor, id)) {
+                return Err(InboxError::Unauthorized("actor cannot like with an id of another origin").into());
+            }
+            if let (Some(object), Some(id)) = (object, id) {
+                if handler.is_local(object) {
+                    handler.add_like(object, id)?;
+                }
+            }
+            handler.on_like(like)
+        }
+        TaggedObject::Announce(announce) => {
+            if id.is_some_and(|id| !same_origin(actor, id)) {
+                return Err(InboxError::Unauthorized("actor cannot announce with an id of another origin").into());
+            }
+            if let (Some(object), Some(id)) = (object, id) {
+                if handler.is_local(object) {
+                    handler.add_share(object, id)?;
+                }
+            }
+            handler.on_announce(announce)
+        }
+        TaggedObject::Undo(undo) => {
+            // Only embedded activities can be undone here; A bare URI would have to be looked up by the application
+            if let Some(MaybeObject::TaggedObject(undone)) = fields.object.as_deref().and_then(<[_]>::first) {
+                let undone_fields = undone.activity_fields().ok_or(InboxError::NotAnActivity)?;
+                if first_id(&undone_fields.actor) != Some(actor) {
+                    return Err(InboxError::Unauthorized("actor cannot undo an activity of another actor").into());
+                }
+                let undone_object = first_id(&undone_fields.object);
+                let undone_id = undone_fields.object_fields.id.as_deref();
+                if matches!(undone, TaggedObject::Like(_) | TaggedObject::Announce(_)) && undone_id.is_some_and(|id| !same_origin(actor, id)) {
+                    return Err(InboxError::Unauthorized("actor cannot undo an activity with an id of another origin").into());
+                }
+                match (undone, undone_object, undone_id) {
+                    (TaggedObject::Follow(_), Some(followed), _) if handler.is_local(followed) => handler.remove_follower(followed, actor)?,
+                    (TaggedObject::Like(_), Some(object), Some(like)) if handler.is_local(object) => handler.remove_like(object, like)?,
+                    (TaggedObject::Announce(_), Some(object), Some(announce)) if handler.is_local(object) => handler.remove_share(object, announce)?,
+                    _ => {}
+                }
+            }
+            handler.on_undo(undo)
+        }
+        TaggedObject::Block(block) => handler.on_block(block),
+        other => handler.on_other(other)
+    }
+}
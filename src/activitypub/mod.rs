@@ -0,0 +1,12 @@
+pub mod http_signature;
+pub mod resolver;
+pub mod hydrate;
+pub mod collection;
+pub mod inbox;
+pub mod outbox;
+pub mod delivery;
+pub mod addressing;
+pub mod webfinger;
+pub mod nodeinfo;
+pub mod validate;
+pub mod update;
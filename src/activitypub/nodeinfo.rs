@@ -0,0 +1,142 @@
+//! NodeInfo 2.0 and 2.1; Metadata about the software and usage of a server, discovered through `/.well-known/nodeinfo`
+
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::activitypub::resolver::{ResolveError, Resolver};
+use crate::activitypub::webfinger::{Jrd, JrdLink};
+
+pub const NODEINFO_WELL_KNOWN_PATH: &str = "/.well-known/nodeinfo";
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum NodeInfoVersion {
+    V2_0,
+    V2_1,
+}
+
+impl NodeInfoVersion {
+    /// Supported versions, newest first
+    pub const ALL: [NodeInfoVersion; 2] = [NodeInfoVersion::V2_1, NodeInfoVersion::V2_0];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            NodeInfoVersion::V2_0 => "2.0",
+            NodeInfoVersion::V2_1 => "2.1",
+        }
+    }
+
+    /// Link relation of this version's schema, used in the well-known document
+    pub fn rel(self) -> &'static str {
+        match self {
+            NodeInfoVersion::V2_0 => "http://nodeinfo.diaspora.software/ns/schema/2.0",
+            NodeInfoVersion::V2_1 => "http://nodeinfo.diaspora.software/ns/schema/2.1",
+        }
+    }
+
+    /// Content-Type to serve this version's document with
+    pub fn content_type(self) -> String {
+        format!("application/json; profile=\"{}#\"", self.rel())
+    }
+}
+
+#[allow(non_snake_case)]    // Field names map directly to those in the emitted/received JSON
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeInfo {
+    pub version: String,
+    pub software: Software,
+    pub protocols: Vec<String>,
+    pub services: Services,
+    pub openRegistrations: bool,
+    pub usage: Usage,
+    pub metadata: HashMap<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Software {
+    /// Canonical name of the software; Lowercase letters, digits and hyphens
+    pub name: String,
+    pub version: String,
+    /// Since 2.1
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repository: Option<String>,
+    /// Since 2.1
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub homepage: Option<String>,
+}
+
+/// Third party sites the server can retrieve messages from or publish messages to
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Services {
+    pub inbound: Vec<String>,
+    pub outbound: Vec<String>,
+}
+
+#[allow(non_snake_case)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Usage {
+    pub users: UserUsage,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub localPosts: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub localComments: Option<u64>,
+}
+
+#[allow(non_snake_case)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UserUsage {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub activeHalfyear: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub activeMonth: Option<u64>,
+}
+
+impl NodeInfo {
+    /// NodeInfo 2.1 document of an ActivityPub server
+    pub fn activitypub(software: Software, usage: Usage, open_registrations: bool) -> NodeInfo {
+        NodeInfo {
+            version: NodeInfoVersion::V2_1.as_str().to_string(),
+            software,
+            protocols: vec!["activitypub".to_string()],
+            services: Services::default(),
+            openRegistrations: open_registrations,
+            usage,
+            metadata: HashMap::new(),
+        }
+    }
+
+    /// This document as the given version; Properties the version does not define are removed
+    pub fn for_version(mut self, version: NodeInfoVersion) -> NodeInfo {
+        self.version = version.as_str().to_string();
+        if version < NodeInfoVersion::V2_1 {
+            self.software.repository = None;
+            self.software.homepage = None;
+        }
+        self
+    }
+
+    /// The `/.well-known/nodeinfo` document, linking to `{base_url}/nodeinfo/{version}` for each supported version
+    pub fn well_known(base_url: &str) -> Jrd {
+        Jrd {
+            links: NodeInfoVersion::ALL.iter()
+                .map(|version| JrdLink {
+                    rel: version.rel().to_string(),
+                    href: Some(format!("{}/nodeinfo/{}", base_url.trim_end_matches('/'), version.as_str())),
+                    ..JrdLink::default()
+                })
+                .collect(),
+            ..Jrd::default()
+        }
+    }
+
+    /// Discover and retrieve the NodeInfo of a host, preferring the newest supported version
+    pub fn discover(resolver: &dyn Resolver, host: &str) -> Result<NodeInfo, ResolveError> {
+        let well_known = resolver.fetch(&format!("https://{}{}", host, NODEINFO_WELL_KNOWN_PATH))?;
+        let well_known = Jrd::deserialize(well_known).map_err(ResolveError::Deserialization)?;
+        let href = NodeInfoVersion::ALL.iter()
+            .find_map(|version| well_known.links.iter().find(|link| link.rel == version.rel()).and_then(|link| link.href.as_deref()))
+            .ok_or_else(|| ResolveError::NotFound(host.to_string()))?;
+        NodeInfo::deserialize(resolver.fetch(href)?).map_err(ResolveError::Deserialization)
+    }
+}
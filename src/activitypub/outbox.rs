@@ -0,0 +1,136 @@
+//! Client-to-server outbox processing
+//!
+//! Objects posted to an outbox are wrapped in a Create, given server-assigned ids, and have their addressing reconciled before storage and delivery.
+
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use crate::linkeddata::activity_streams::{MaybeObject, TaggedObject};
+use crate::linkeddata::activity_streams::objects::{Activity, Create, Object};
+use crate::linkeddata::util::FoldedSlice;
+
+#[derive(Debug)]
+pub enum OutboxError {
+    /// The posted activity names an actor other than the outbox's owner
+    ActorMismatch(String),
+}
+
+impl Display for OutboxError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OutboxError::ActorMismatch(actor) => write!(f, "activity was posted on behalf of another actor: {}", actor),
+        }
+    }
+}
+
+impl Error for OutboxError {}
+
+/// Storage of the application, used by [`process`]
+pub trait OutboxStore {
+    type Error: From<OutboxError>;
+
+    /// Generate a new, unique id for an activity or object posted by `actor`
+    fn new_id(&mut self, actor: &str, object: &TaggedObject) -> Result<String, Self::Error>;
+
+    /// Store a processed activity in the outbox of `actor`
+    ///
+    /// The activity still has its `bto` and `bcc`, which are for the author only and must not be served to others.
+    fn store(&mut self, actor: &str, activity: &TaggedObject) -> Result<(), Self::Error>;
+}
+
+/// An activity ready for delivery
+#[derive(Debug)]
+pub struct ProcessedActivity {
+    /// The activity, without `bto` and `bcc`
+    pub activity: TaggedObject,
+    /// All addressed recipients, including the stripped `bto` and `bcc`
+    pub recipients: Vec<String>,
+}
+
+/// The addressing properties of an object; `to`, `bto`, `cc`, `bcc` and `audience`
+pub(crate) fn addressing_mut(object: &mut Object) -> [&mut Option<FoldedSlice<MaybeObject>>; 5] {
+    [&mut object.to, &mut object.bto, &mut object.cc, &mut object.bcc, &mut object.audience]
+}
+
+pub(crate) fn push_ids(ids: &mut Vec<String>, property: &Option<FoldedSlice<MaybeObject>>) {
+    for id in property.iter().flat_map(|values| values.iter()).filter_map(MaybeObject::id) {
+        if !ids.iter().any(|known| known == id) {
+            ids.push(id.to_string());
+        }
+    }
+}
+
+/// Set both properties to the union of their recipients
+fn merge_addressing(a: &mut Option<FoldedSlice<MaybeObject>>, b: &mut Option<FoldedSlice<MaybeObject>>) {
+    let mut ids = Vec::new();
+    push_ids(&mut ids, a);
+    push_ids(&mut ids, b);
+    let merged = || (!ids.is_empty()).then(|| ids.iter().cloned().map(MaybeObject::URI).collect::<FoldedSlice<_>>());
+    *a = merged();
+    *b = merged();
+}
+
+/// Process an object or activity posted to the outbox of `actor`
+///
+/// - Objects that are not activities are wrapped in a Create by `actor`
+/// - Client-provided ids are replaced; The object of a Create gets a new id as well, and is attributed to `actor` if not attributed
+/// - The recipients of a Create and its object are copied to one another
+/// - The activity is stored, then `bto` and `bcc` are removed for delivery, after being recorded as recipients
+pub fn process<S: OutboxStore + ?Sized>(store: &mut S, actor: &str, posted: TaggedObject) -> Result<ProcessedActivity, S::Error> {
+    let mut activity = match posted.activity_fields() {
+        Some(_) => posted,
+        None => TaggedObject::Create(Create(Activity {
+            object: Some(FoldedSlice::from(MaybeObject::TaggedObject(posted))),
+            ..Activity::default()
+        }))
+    };
+
+    let fields = activity.activity_fields_mut().expect("activity was wrapped");
+    match fields.actor.as_deref().and_then(<[_]>::first).and_then(MaybeObject::id) {
+        None => fields.actor = Some(FoldedSlice::from(MaybeObject::URI(actor.to_string()))),
+        Some(posted_actor) if posted_actor == actor => {}
+        Some(posted_actor) => return Err(OutboxError::ActorMismatch(posted_actor.to_string()).into())
+    }
+
+    if let TaggedObject::Create(Create(create)) = &mut activity {
+        if let Some(MaybeObject::TaggedObject(object)) = create.object.as_deref_mut().and_then(<[_]>::first_mut) {
+            let id = store.new_id(actor, object)?;
+            if let Some(object_fields) = object.object_fields_mut() {
+                object_fields.id = Some(id);
+                if object_fields.attributedTo.is_none() {
+                    object_fields.attributedTo = Some(FoldedSlice::from(MaybeObject::URI(actor.to_string())));
+                }
+                for (activity_addressing, object_addressing) in addressing_mut(&mut create.object_fields).into_iter().zip(addressing_mut(object_fields)) {
+                    merge_addressing(activity_addressing, object_addressing);
+                }
+            }
+        }
+    }
+
+    let id = store.new_id(actor, &activity)?;
+    let object_fields = activity.object_fields_mut().expect("activities have object properties");
+    object_fields.id = Some(id);
+    let mut recipients = Vec::new();
+    for addressing in addressing_mut(object_fields) {
+        push_ids(&mut recipients, addressing);
+    }
+
+    store.store(actor, &activity)?;
+    strip_blind_recipients(&mut activity);
+    Ok(ProcessedActivity { activity, recipients })
+}
+
+/// Remove `bto` and `bcc` from an activity and its embedded object, for delivery
+fn strip_blind_recipients(activity: &mut TaggedObject) {
+    if let TaggedObject::Create(Create(create)) = activity {
+        if let Some(MaybeObject::TaggedObject(object)) = create.object.as_deref_mut().and_then(<[_]>::first_mut) {
+            if let Some(object_fields) = object.object_fields_mut() {
+                object_fields.bto = None;
+                object_fields.bcc = None;
+            }
+        }
+    }
+    if let Some(object_fields) = activity.object_fields_mut() {
+        object_fields.bto = None;
+        object_fields.bcc = None;
+    }
+}
@@ -0,0 +1,322 @@
+//! Dereferencing of URI references (`MaybeObject::URI` and friends) to the documents they identify
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::io::Read;
+use std::time::Duration;
+use chrono::Utc;
+use reqwest::blocking::Client;
+use reqwest::header::{ACCEPT, DATE, HOST};
+use reqwest::Url;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use crate::activitypub::http_signature::{http_date, HttpSignatureKey};
+use crate::linkeddata::activity_streams::{MaybeImage, MaybeLink, MaybeObject, TaggedImage, TaggedLink, TaggedObject};
+use crate::linkeddata::activity_streams::properties::{MaybeCollection, MaybeCollectionPage, TaggedCollection, TaggedCollectionPage};
+use crate::linkeddata::signature::SignatureError;
+
+/// Accept header for ActivityStreams documents; Servers may serve either media type
+pub const ACTIVITY_JSON_ACCEPT: &str = "application/activity+json, application/ld+json; profile=\"https://www.w3.org/ns/activitystreams\"";
+
+/// Time allowed for a whole request of [`HttpResolver`], including reading the response
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Largest response body [`HttpResolver`] reads by default
+pub const MAX_BODY_SIZE: u64 = 1024 * 1024;
+
+#[derive(Debug)]
+pub enum ResolveError {
+    InvalidUri(String),
+    Http(reqwest::Error),
+    /// The server responded with a non-success status code
+    Status(u16),
+    /// The response body is larger than the limit, in bytes
+    TooLarge(u64),
+    /// The response body could not be read
+    Io(std::io::Error),
+    /// No document is known for this URI
+    NotFound(String),
+    Deserialization(serde_json::Error),
+    /// The fetched document's id is not on the same origin as the URI it was fetched from
+    OriginMismatch { requested: String, id: String },
+    /// The fetched document is not of the expected kind, e.g. a Link where an Object was expected
+    UnexpectedType(&'static str),
+    Signature(SignatureError),
+    /// No more documents may be fetched for this operation
+    FetchBudgetExhausted,
+    Xml(roxmltree::Error),
+}
+
+impl Display for ResolveError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResolveError::InvalidUri(uri) => write!(f, "invalid URI: {}", uri),
+            ResolveError::Http(error) => write!(f, "HTTP error: {}", error),
+            ResolveError::Status(status) => write!(f, "server responded with status {}", status),
+            ResolveError::TooLarge(limit) => write!(f, "response is larger than {} bytes", limit),
+            ResolveError::Io(error) => write!(f, "could not read response: {}", error),
+            ResolveError::NotFound(uri) => write!(f, "not found: {}", uri),
+            ResolveError::Deserialization(error) => write!(f, "could not deserialize document: {}", error),
+            ResolveError::OriginMismatch { requested, id } => write!(f, "document fetched from {} has foreign id {}", requested, id),
+            ResolveError::UnexpectedType(reason) => write!(f, "unexpected document type: {}", reason),
+            ResolveError::Signature(error) => write!(f, "could not sign request: {}", error),
+            ResolveError::FetchBudgetExhausted => write!(f, "fetch budget exhausted"),
+            ResolveError::Xml(error) => write!(f, "could not parse XML document: {}", error),
+        }
+    }
+}
+
+impl Error for ResolveError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ResolveError::Http(error) => Some(error),
+            ResolveError::Io(error) => Some(error),
+            ResolveError::Deserialization(error) => Some(error),
+            ResolveError::Signature(error) => Some(error),
+            ResolveError::Xml(error) => Some(error),
+            _ => None
+        }
+    }
+}
+
+impl From<reqwest::Error> for ResolveError {
+    fn from(error: reqwest::Error) -> Self {
+        ResolveError::Http(error)
+    }
+}
+
+/// Source of ActivityStreams documents by URI
+pub trait Resolver {
+    /// Retrieve the JSON document identified by `uri`
+    fn fetch(&self, uri: &str) -> Result<Value, ResolveError>;
+
+    /// Retrieve a document that is not JSON, such as a host-meta XRD; Resolvers that only serve JSON do not support this
+    fn fetch_raw(&self, uri: &str, accept: &str) -> Result<Vec<u8>, ResolveError> {
+        let _ = accept;
+        Err(ResolveError::NotFound(uri.to_string()))
+    }
+}
+
+/// Whether both URIs are valid URLs with the same scheme, host and port
+pub fn same_origin(a: &str, b: &str) -> bool {
+    match (Url::parse(a), Url::parse(b)) {
+        (Ok(a), Ok(b)) => a.origin() == b.origin(),
+        _ => false
+    }
+}
+
+/// Fetch and deserialize a document
+///
+/// If the document has an `id`, it must be on the same origin as `uri`; Otherwise any server could impersonate objects of another.
+pub fn resolve<T: DeserializeOwned>(resolver: &dyn Resolver, uri: &str) -> Result<T, ResolveError> {
+    let document = resolver.fetch(uri)?;
+    if let Some(id) = document.get("id").and_then(Value::as_str) {
+        Url::parse(uri).map_err(|_| ResolveError::InvalidUri(uri.to_string()))?;
+        if !same_origin(uri, id) {
+            return Err(ResolveError::OriginMismatch { requested: uri.to_string(), id: id.to_string() });
+        }
+    }
+    serde_json::from_value(document).map_err(ResolveError::Deserialization)
+}
+
+/// Resolver performing blocking HTTP GET requests
+///
+/// Requests time out after [`REQUEST_TIMEOUT`], and responses larger than [`MAX_BODY_SIZE`] are rejected.
+#[derive(Debug, Clone)]
+pub struct HttpResolver {
+    client: Client,
+    key: Option<HttpSignatureKey>,
+    max_body_size: u64,
+}
+
+impl Default for HttpResolver {
+    fn default() -> Self {
+        let client = Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .connect_timeout(CONNECT_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+        HttpResolver::with_client(client)
+    }
+}
+
+impl HttpResolver {
+    pub fn new() -> Self {
+        HttpResolver::default()
+    }
+
+    /// Perform requests with the given client; Its timeouts apply instead of [`REQUEST_TIMEOUT`]
+    pub fn with_client(client: Client) -> Self {
+        HttpResolver { client, key: None, max_body_size: MAX_BODY_SIZE }
+    }
+
+    /// Reject responses larger than `max_body_size` bytes
+    pub fn max_body_size(self, max_body_size: u64) -> Self {
+        HttpResolver { max_body_size, ..self }
+    }
+
+    /// Sign requests with the given key ("authorized fetch"), as required by servers operating in secure mode
+    pub fn signed(self, key: HttpSignatureKey) -> Self {
+        HttpResolver { key: Some(key), ..self }
+    }
+}
+
+impl HttpResolver {
+    fn get(&self, uri: &str, accept: &str) -> Result<Vec<u8>, ResolveError> {
+        let url = Url::parse(uri).map_err(|_| ResolveError::InvalidUri(uri.to_string()))?;
+        let mut request = self.client.get(url.clone()).header(ACCEPT, accept);
+        if let Some(key) = &self.key {
+            let host = match url.port() {
+                Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+                None => url.host_str().unwrap_or_default().to_string()
+            };
+            let date = http_date(Utc::now());
+            let path = match url.query() {
+                Some(query) => format!("{}?{}", url.path(), query),
+                None => url.path().to_string()
+            };
+            let signature = key.sign("GET", &path, &[("host", &host), ("date", &date), ("accept", accept)])
+                .map_err(ResolveError::Signature)?;
+            request = request.header(HOST, host).header(DATE, date).header("Signature", signature);
+        }
+        let response = request.send()?;
+        if !response.status().is_success() {
+            return Err(ResolveError::Status(response.status().as_u16()));
+        }
+        if response.content_length().is_some_and(|length| length > self.max_body_size) {
+            return Err(ResolveError::TooLarge(self.max_body_size));
+        }
+        let mut body = Vec::new();
+        response.take(self.max_body_size.saturating_add(1)).read_to_end(&mut body).map_err(ResolveError::Io)?;
+        if body.len() as u64 > self.max_body_size {
+            return Err(ResolveError::TooLarge(self.max_body_size));
+        }
+        Ok(body)
+    }
+}
+
+impl Resolver for HttpResolver {
+    fn fetch(&self, uri: &str) -> Result<Value, ResolveError> {
+        serde_json::from_slice(&self.get(uri, ACTIVITY_JSON_ACCEPT)?).map_err(ResolveError::Deserialization)
+    }
+
+    fn fetch_raw(&self, uri: &str, accept: &str) -> Result<Vec<u8>, ResolveError> {
+        self.get(uri, accept)
+    }
+}
+
+/// Resolver serving documents from memory, for tests and offline use
+#[derive(Debug, Default)]
+pub struct InMemoryResolver {
+    documents: HashMap<String, Value>,
+    raw_documents: HashMap<String, Vec<u8>>,
+    fetched: RefCell<Vec<String>>,
+}
+
+impl InMemoryResolver {
+    pub fn new() -> Self {
+        InMemoryResolver::default()
+    }
+
+    pub fn insert(&mut self, uri: &str, document: Value) {
+        self.documents.insert(uri.to_string(), document);
+    }
+
+    /// Insert a document served only through [`Resolver::fetch_raw`]; Documents inserted as JSON are served through both
+    pub fn insert_raw(&mut self, uri: &str, document: Vec<u8>) {
+        self.raw_documents.insert(uri.to_string(), document);
+    }
+
+    /// URIs fetched so far, in order
+    pub fn fetched(&self) -> Vec<String> {
+        self.fetched.borrow().clone()
+    }
+}
+
+impl Resolver for InMemoryResolver {
+    fn fetch(&self, uri: &str) -> Result<Value, ResolveError> {
+        self.fetched.borrow_mut().push(uri.to_string());
+        self.documents.get(uri).cloned().ok_or_else(|| ResolveError::NotFound(uri.to_string()))
+    }
+
+    fn fetch_raw(&self, uri: &str, _accept: &str) -> Result<Vec<u8>, ResolveError> {
+        self.fetched.borrow_mut().push(uri.to_string());
+        match (self.raw_documents.get(uri), self.documents.get(uri)) {
+            (Some(document), _) => Ok(document.clone()),
+            (None, Some(document)) => serde_json::to_vec(document).map_err(ResolveError::Deserialization),
+            (None, None) => Err(ResolveError::NotFound(uri.to_string()))
+        }
+    }
+}
+
+impl MaybeObject {
+    /// Fetch this object if it is a URI reference; Embedded objects and links are returned as-is
+    pub fn resolve(self, resolver: &dyn Resolver) -> Result<MaybeObject, ResolveError> {
+        match self {
+            MaybeObject::URI(uri) => match resolve::<MaybeObject>(resolver, &uri)? {
+                MaybeObject::URI(_) => Err(ResolveError::UnexpectedType("expected an object or link, found a URI")),
+                resolved => Ok(resolved)
+            },
+            resolved => Ok(resolved)
+        }
+    }
+
+    /// Resolve this reference to an object
+    pub fn resolve_object(self, resolver: &dyn Resolver) -> Result<TaggedObject, ResolveError> {
+        match self.resolve(resolver)? {
+            MaybeObject::TaggedObject(object) => Ok(object),
+            _ => Err(ResolveError::UnexpectedType("expected an object, found a link"))
+        }
+    }
+}
+
+impl MaybeLink {
+    pub fn resolve_link(self, resolver: &dyn Resolver) -> Result<TaggedLink, ResolveError> {
+        match self {
+            MaybeLink::URI(uri) => resolve(resolver, &uri),
+            MaybeLink::TaggedLink(link) => Ok(link)
+        }
+    }
+}
+
+impl MaybeImage {
+    /// Fetch this image if it is a URI reference; Embedded images and links are returned as-is
+    pub fn resolve(self, resolver: &dyn Resolver) -> Result<MaybeImage, ResolveError> {
+        match self {
+            MaybeImage::URI(uri) => match resolve::<MaybeImage>(resolver, &uri)? {
+                MaybeImage::URI(_) => Err(ResolveError::UnexpectedType("expected an image or link, found a URI")),
+                resolved => Ok(resolved)
+            },
+            resolved => Ok(resolved)
+        }
+    }
+
+    pub fn resolve_image(self, resolver: &dyn Resolver) -> Result<TaggedImage, ResolveError> {
+        match self.resolve(resolver)? {
+            MaybeImage::TaggedImage(image) => Ok(image),
+            _ => Err(ResolveError::UnexpectedType("expected an image, found a link"))
+        }
+    }
+}
+
+impl MaybeCollection {
+    pub fn resolve_collection(self, resolver: &dyn Resolver) -> Result<TaggedCollection, ResolveError> {
+        match self {
+            MaybeCollection::URI(uri) => resolve(resolver, &uri),
+            MaybeCollection::TaggedLink(_) => Err(ResolveError::UnexpectedType("expected a collection, found a link")),
+            MaybeCollection::TaggedCollection(collection) => Ok(collection)
+        }
+    }
+}
+
+impl MaybeCollectionPage {
+    pub fn resolve_page(self, resolver: &dyn Resolver) -> Result<TaggedCollectionPage, ResolveError> {
+        match self {
+            MaybeCollectionPage::URI(uri) => resolve(resolver, &uri),
+            MaybeCollectionPage::TaggedLink(_) => Err(ResolveError::UnexpectedType("expected a collection page, found a link")),
+            MaybeCollectionPage::Tagged(page) => Ok(page)
+        }
+    }
+}
@@ -0,0 +1,127 @@
+//! Applying Update activities to stored objects
+//!
+//! Server-to-server Updates carry the whole new object, which replaces the stored one (ActivityPub 7.3).
+//! Client-to-server Updates carry only the top-level properties to change, and `null` for those to remove (ActivityPub 6.3.1).
+
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use serde_json::Value;
+use crate::activitypub::resolver::same_origin;
+use crate::linkeddata::activity_streams::{MaybeObject, TaggedObject};
+use crate::linkeddata::activity_streams::objects::Update;
+use crate::linkeddata::deserialize::{self, null_properties, DeserializeError};
+use crate::linkeddata::diff::{Diff, IMMUTABLE_PROPERTIES};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum UpdateMode<'a> {
+    /// Server-to-server; The object of the Update replaces the stored object
+    ///
+    /// With `trust_origin`, any actor on the origin of an object without `attributedTo` may update it; Otherwise only the object itself may.
+    Replace { trust_origin: bool },
+    /// Client-to-server; The properties of the object of the Update replace those of the stored object, and `removed` properties are removed
+    ///
+    /// Deserialized objects do not hold `null` properties; See [`removed_properties`] for those of the received document.
+    Merge { removed: &'a [&'a str] },
+}
+
+#[derive(Debug)]
+pub enum UpdateError {
+    /// The Update has no actor
+    MissingActor,
+    /// The object of the Update is not embedded, or has no id
+    MissingObject,
+    /// The Update is of another object than the stored one
+    ObjectMismatch { stored: String, updated: String },
+    /// The actor may not update the object
+    Unauthorized(&'static str),
+    /// The update changes a property that may not be changed, e.g. `attributedTo`
+    Immutable(String),
+    /// An object could not be serialized to be merged or compared
+    Serialization(serde_json::Error),
+    /// The updated object is invalid
+    Deserialization(DeserializeError),
+}
+
+impl Display for UpdateError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UpdateError::MissingActor => write!(f, "update has no actor"),
+            UpdateError::MissingObject => write!(f, "update has no embedded object with an id"),
+            UpdateError::ObjectMismatch { stored, updated } => write!(f, "update of {} cannot be applied to {}", updated, stored),
+            UpdateError::Unauthorized(reason) => write!(f, "unauthorized update: {}", reason),
+            UpdateError::Immutable(property) => write!(f, "update changes immutable property `{}`", property),
+            UpdateError::Serialization(error) => write!(f, "object cannot be serialized: {}", error),
+            UpdateError::Deserialization(error) => write!(f, "updated object is invalid: {}", error),
+        }
+    }
+}
+
+impl Error for UpdateError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            UpdateError::Serialization(error) => Some(error),
+            UpdateError::Deserialization(error) => Some(error),
+            _ => None
+        }
+    }
+}
+
+/// The properties a received Update document removes from its object
+pub fn removed_properties(update: &Value) -> Vec<&str> {
+    update.get("object").map(null_properties).unwrap_or_default()
+}
+
+/// Whether `actor` may update `object`: It must be the object itself, or on the object's origin and among its `attributedTo`
+///
+/// Objects without `attributedTo` may be updated by any actor on their origin if `trust_origin` is set.
+fn check_authority(actor: &str, object: &TaggedObject, id: &str, trust_origin: bool) -> Result<(), UpdateError> {
+    if actor == id {
+        return Ok(());
+    }
+    if !same_origin(actor, id) {
+        return Err(UpdateError::Unauthorized("actor cannot update an object of another origin"));
+    }
+    match object.object_fields().and_then(|fields| fields.attributedTo.as_deref()) {
+        Some(attributed_to) if attributed_to.iter().filter_map(MaybeObject::id).any(|id| id == actor) => Ok(()),
+        Some(_) => Err(UpdateError::Unauthorized("actor cannot update an object attributed to another actor")),
+        None if trust_origin => Ok(()),
+        None => Err(UpdateError::Unauthorized("actor cannot update an object without attributedTo other than itself"))
+    }
+}
+
+/// The stored object as changed by an Update
+///
+/// The actor of the Update must own the object, and the update may not change any of [`IMMUTABLE_PROPERTIES`].
+pub fn apply_update(stored: &TaggedObject, update: &Update, mode: UpdateMode) -> Result<TaggedObject, UpdateError> {
+    let fields = &update.0;
+    let actor = fields.actor.as_deref().and_then(<[_]>::first).and_then(MaybeObject::id).ok_or(UpdateError::MissingActor)?;
+    let updated = match fields.object.as_deref().and_then(<[_]>::first) {
+        Some(MaybeObject::TaggedObject(updated)) => updated,
+        _ => return Err(UpdateError::MissingObject)
+    };
+    let id = stored.object_fields().and_then(|fields| fields.id.as_deref()).ok_or(UpdateError::MissingObject)?;
+    match updated.object_fields().and_then(|fields| fields.id.as_deref()) {
+        Some(updated_id) if updated_id == id => {}
+        Some(updated_id) => return Err(UpdateError::ObjectMismatch { stored: id.to_string(), updated: updated_id.to_string() }),
+        None => return Err(UpdateError::MissingObject)
+    }
+    check_authority(actor, stored, id, matches!(mode, UpdateMode::Replace { trust_origin: true }))?;
+
+    let result = match mode {
+        UpdateMode::Replace { .. } => updated.clone(),
+        UpdateMode::Merge { removed } => {
+            let mut merged = serde_json::to_value(stored).map_err(UpdateError::Serialization)?;
+            let properties = serde_json::to_value(updated).map_err(UpdateError::Serialization)?;
+            if let (Some(merged), Value::Object(properties)) = (merged.as_object_mut(), properties) {
+                merged.extend(properties);
+                merged.retain(|key, _| !removed.contains(&key.as_str()));
+            }
+            deserialize::from_value(&merged).map_err(UpdateError::Deserialization)?
+        }
+    };
+    let diff = Diff::between(stored, &result).map_err(UpdateError::Serialization)?;
+    if let Some(property) = diff.properties().into_iter().find(|property| IMMUTABLE_PROPERTIES.contains(property)) {
+        return Err(UpdateError::Immutable(property.to_string()));
+    }
+    Ok(result)
+}
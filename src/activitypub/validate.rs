@@ -0,0 +1,470 @@
+//! Validation; Checks of parsed objects against the requirements of the ActivityStreams 2.0 Vocabulary and ActivityPub that deserialization does not enforce
+//!
+//! Each violated rule is reported as a [`Diagnostic`] locating the offending property by a JSON path, e.g. `$.object.deleted`.
+
+use std::fmt::{Display, Formatter};
+use chrono::{DateTime, Utc};
+use reqwest::Url;
+use crate::linkeddata::activity_streams::{MaybeImage, MaybeLink, MaybeObject, TaggedImage, TaggedLink, TaggedObject};
+use crate::linkeddata::activity_streams::objects;
+use crate::linkeddata::activity_streams::objects::{Collection, CollectionPage, Link, Object, OrderedCollection, OrderedCollectionPage, Place, Question, Relationship, Tombstone};
+use crate::linkeddata::activity_streams::properties::{MaybeCollection, MaybeCollectionPage, PropNext, PropPrev, TaggedCollection, TaggedCollectionPage};
+use crate::linkeddata::util::FoldedSlice;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// How strictly rules are applied
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ValidationProfile {
+    /// Violations of both MUST and SHOULD requirements are errors
+    Strict,
+    /// Violations of MUST requirements are errors, violations of SHOULD requirements are warnings; Suits objects received from other servers
+    Lenient,
+}
+
+impl ValidationProfile {
+    fn severity(self, rule: Rule) -> Severity {
+        match (self, rule.is_required()) {
+            (ValidationProfile::Lenient, false) => Severity::Warning,
+            _ => Severity::Error
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Rule {
+    /// Objects and links are identified by absolute URIs
+    AbsoluteUri,
+    /// Objects other than transient ones have an `id`
+    MissingId,
+    /// Activities have an `actor`
+    MissingActor,
+    /// Transitive activities have an `object`
+    MissingObject,
+    /// Add and Remove have a `target`
+    MissingTarget,
+    /// Intransitive activities (Arrive, Travel and Question) have no `object`
+    IntransitiveObject,
+    /// A Question has either `oneOf` or `anyOf`, not both
+    QuestionChoices,
+    /// A Tombstone was not `deleted` in the future
+    DeletedInFuture,
+    /// `endTime` is not before `startTime`
+    EndBeforeStart,
+    /// `updated` is not before `published`
+    UpdatedBeforePublished,
+    /// `duration` is an xsd:duration, e.g. `PT5M`
+    InvalidDuration,
+    /// `mediaType` is a MIME type
+    InvalidMediaType,
+    /// A Link has an `href`
+    MissingHref,
+    /// `totalItems` is not less than the number of inline items
+    TotalItemsTooSmall,
+    /// `latitude` is within -90 to 90 and `longitude` within -180 to 180
+    CoordinatesOutOfRange,
+    /// `units` is one of `cm`, `feet`, `inches`, `km`, `m`, `miles`, or a URI
+    InvalidUnits,
+    /// A Relationship has a `subject`, an `object` and a `relationship`
+    IncompleteRelationship,
+    /// A Profile `describes` an object
+    MissingDescribes,
+}
+
+impl Rule {
+    /// Whether the rule is a MUST requirement, rather than a SHOULD
+    pub fn is_required(self) -> bool {
+        !matches!(self, Rule::MissingId | Rule::MissingTarget | Rule::UpdatedBeforePublished | Rule::InvalidDuration | Rule::InvalidMediaType
+            | Rule::InvalidUnits | Rule::IncompleteRelationship | Rule::MissingDescribes)
+    }
+}
+
+impl Display for Rule {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let description = match self {
+            Rule::AbsoluteUri => "identifier is not an absolute URI",
+            Rule::MissingId => "object has no id",
+            Rule::MissingActor => "activity has no actor",
+            Rule::MissingObject => "activity has no object",
+            Rule::MissingTarget => "activity has no target",
+            Rule::IntransitiveObject => "intransitive activity has an object",
+            Rule::QuestionChoices => "question has both oneOf and anyOf",
+            Rule::DeletedInFuture => "tombstone was deleted in the future",
+            Rule::EndBeforeStart => "endTime is before startTime",
+            Rule::UpdatedBeforePublished => "updated is before published",
+            Rule::InvalidDuration => "duration is not an xsd:duration",
+            Rule::InvalidMediaType => "mediaType is not a MIME type",
+            Rule::MissingHref => "link has no href",
+            Rule::TotalItemsTooSmall => "totalItems is less than the number of items",
+            Rule::CoordinatesOutOfRange => "coordinates are out of range",
+            Rule::InvalidUnits => "units are neither a known unit nor a URI",
+            Rule::IncompleteRelationship => "relationship lacks a subject, object or relationship",
+            Rule::MissingDescribes => "profile does not describe an object",
+        };
+        f.write_str(description)
+    }
+}
+
+/// A violated rule
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// JSON path of the offending property, or of the object lacking it
+    pub path: String,
+    pub rule: Rule,
+    pub severity: Severity,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "{} at {}: {}", severity, self.path, self.rule)
+    }
+}
+
+/// Whether any diagnostic is an error
+pub fn has_errors(diagnostics: &[Diagnostic]) -> bool {
+    diagnostics.iter().any(|diagnostic| diagnostic.severity == Severity::Error)
+}
+
+fn is_absolute_uri(uri: &str) -> bool {
+    Url::parse(uri).is_ok()
+}
+
+/// Whether `duration` matches the lexical form of xsd:duration, e.g. `P1Y2M3DT4H5M6.7S`
+fn is_xsd_duration(duration: &str) -> bool {
+    let Some(rest) = duration.strip_prefix('-').unwrap_or(duration).strip_prefix('P') else { return false };
+    let (date, time) = match rest.split_once('T') {
+        Some((_, "")) => return false,
+        Some((date, time)) => (date, Some(time)),
+        None => (rest, None)
+    };
+    // Components are digits followed by a designator, in order; Only seconds may have a fraction
+    fn components(mut part: &str, designators: &[char], fraction: char) -> Option<usize> {
+        let mut count = 0;
+        let mut allowed = designators;
+        while !part.is_empty() {
+            let end = part.find(|c: char| !c.is_ascii_digit() && c != '.')?;
+            let (number, designator) = (&part[..end], part[end..].chars().next()?);
+            let position = allowed.iter().position(|&allowed| allowed == designator)?;
+            let valid_number = match number.split_once('.') {
+                Some((whole, decimals)) => designator == fraction && !whole.is_empty() && !decimals.is_empty() && !decimals.contains('.'),
+                None => !number.is_empty()
+            };
+            if !valid_number {
+                return None;
+            }
+            allowed = &allowed[position + 1..];
+            part = &part[end + designator.len_utf8()..];
+            count += 1;
+        }
+        Some(count)
+    }
+    let date = components(date, &['Y', 'M', 'D'], '\0');
+    let time = time.map_or(Some(0), |time| components(time, &['H', 'M', 'S'], 'S').filter(|&count| count > 0));
+    matches!((date, time), (Some(date), Some(time)) if date + time > 0)
+}
+
+fn is_media_type(media_type: &str) -> bool {
+    let essence = media_type.split(';').next().unwrap_or_default().trim();
+    matches!(essence.split_once('/'), Some((kind, subtype)) if !kind.is_empty() && !subtype.is_empty() && !subtype.contains('/'))
+}
+
+/// Collects diagnostics while walking an object
+struct Validator {
+    profile: ValidationProfile,
+    now: DateTime<Utc>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Validator {
+    fn report(&mut self, path: &str, rule: Rule) {
+        self.diagnostics.push(Diagnostic { path: path.to_string(), rule, severity: self.profile.severity(rule) });
+    }
+
+    fn uri(&mut self, path: &str, uri: &str) {
+        if !is_absolute_uri(uri) {
+            self.report(path, Rule::AbsoluteUri);
+        }
+    }
+
+    /// Validate each value of a property; Indices are only part of the path if the property holds several values
+    fn values<T>(&mut self, path: &str, property: &str, values: &Option<FoldedSlice<T>>, mut validate: impl FnMut(&mut Self, &str, &T)) {
+        match values {
+            Some(values) if values.is_one() => validate(self, &format!("{}.{}", path, property), &values[0]),
+            Some(values) => for (index, value) in values.iter().enumerate() {
+                validate(self, &format!("{}.{}[{}]", path, property, index), value)
+            },
+            None => {}
+        }
+    }
+
+    fn maybe_objects(&mut self, path: &str, property: &str, values: &Option<FoldedSlice<MaybeObject>>) {
+        self.values(path, property, values, Self::maybe_object);
+    }
+
+    fn maybe_object(&mut self, path: &str, value: &MaybeObject) {
+        match value {
+            MaybeObject::URI(uri) => self.uri(path, uri),
+            MaybeObject::TaggedLink(TaggedLink::Link(link)) => self.link(path, link),
+            MaybeObject::TaggedObject(object) => self.object(path, object, false),
+        }
+    }
+
+    fn maybe_collection(&mut self, path: &str, value: &MaybeCollection) {
+        match value {
+            MaybeCollection::URI(uri) => self.uri(path, uri),
+            MaybeCollection::TaggedLink(TaggedLink::Link(link)) => self.link(path, link),
+            MaybeCollection::TaggedCollection(collection) => {
+                let (object, collection) = match collection {
+                    TaggedCollection::Collection(collection) => (&collection.object_fields, CollectionProperties::of_collection(collection)),
+                    TaggedCollection::OrderedCollection(collection) => (&collection.object_fields, CollectionProperties::of_ordered(collection)),
+                    TaggedCollection::CollectionPage(page) => (&page.collection_fields.object_fields, CollectionProperties::of_page(page)),
+                    TaggedCollection::OrderedCollectionPage(page) => (&page.collection_fields.object_fields, CollectionProperties::of_ordered_page(page)),
+                };
+                self.object_properties(path, object);
+                self.collection_properties(path, collection);
+            }
+        }
+    }
+
+    fn maybe_page(&mut self, path: &str, value: &MaybeCollectionPage) {
+        match value {
+            MaybeCollectionPage::URI(uri) => self.uri(path, uri),
+            MaybeCollectionPage::TaggedLink(TaggedLink::Link(link)) => self.link(path, link),
+            MaybeCollectionPage::Tagged(TaggedCollectionPage::CollectionPage(page)) => {
+                self.object_properties(path, &page.collection_fields.object_fields);
+                self.collection_properties(path, CollectionProperties::of_page(page));
+            }
+            MaybeCollectionPage::Tagged(TaggedCollectionPage::OrderedCollectionPage(page)) => {
+                self.object_properties(path, &page.collection_fields.object_fields);
+                self.collection_properties(path, CollectionProperties::of_ordered_page(page));
+            }
+        }
+    }
+
+    fn maybe_images(&mut self, path: &str, property: &str, values: &Option<FoldedSlice<MaybeImage>>) {
+        self.values(path, property, values, |validator, path, image| match image {
+            MaybeImage::URI(uri) => validator.uri(path, uri),
+            MaybeImage::TaggedLink(TaggedLink::Link(link)) => validator.link(path, link),
+            MaybeImage::TaggedImage(TaggedImage::Image(image)) => validator.object_properties(path, &image.0),
+        });
+    }
+
+    fn maybe_links(&mut self, path: &str, property: &str, values: &Option<FoldedSlice<MaybeLink>>) {
+        self.values(path, property, values, |validator, path, link| match link {
+            MaybeLink::URI(uri) => validator.uri(path, uri),
+            MaybeLink::TaggedLink(TaggedLink::Link(link)) => validator.link(path, link),
+        });
+    }
+
+    fn collection_properties(&mut self, path: &str, collection: CollectionProperties) {
+        let count = collection.items.as_deref().map_or(0, <[_]>::len) as u64;
+        if collection.total_items.is_some_and(|total_items| total_items < count) {
+            self.report(&format!("{}.totalItems", path), Rule::TotalItemsTooSmall);
+        }
+        self.maybe_objects(path, collection.items_property, collection.items);
+        for (property, page) in collection.pages {
+            if let Some(page) = page {
+                self.maybe_page(&format!("{}.{}", path, property), page);
+            }
+        }
+        if let Some(part_of) = collection.part_of {
+            self.maybe_collection(&format!("{}.partOf", path), part_of);
+        }
+    }
+
+    fn link(&mut self, path: &str, link: &Link) {
+        if let Some(id) = &link.id {
+            self.uri(&format!("{}.id", path), id);
+        }
+        match &link.href {
+            Some(href) => self.uri(&format!("{}.href", path), href),
+            None => self.report(path, Rule::MissingHref)
+        }
+        if link.mediaType.as_deref().is_some_and(|media_type| !is_media_type(media_type)) {
+            self.report(&format!("{}.mediaType", path), Rule::InvalidMediaType);
+        }
+        self.maybe_objects(path, "preview", &link.preview);
+    }
+
+    fn object(&mut self, path: &str, object: &TaggedObject, root: bool) {
+        if let TaggedObject::Mention(mention) = object {
+            return self.link(path, &mention.0);
+        }
+        if root && object.id().is_none() {
+            self.report(path, Rule::MissingId);
+        }
+        if let Some(fields) = object.object_fields() {
+            self.object_properties(path, fields);
+        }
+        if let Some(activity) = object.activity_fields() {
+            let intransitive = matches!(object, TaggedObject::Arrive(_) | TaggedObject::Travel(_) | TaggedObject::Question(_));
+            if activity.actor.as_deref().is_none_or(<[_]>::is_empty) {
+                self.report(path, Rule::MissingActor);
+            }
+            match (&activity.object, intransitive) {
+                (Some(_), true) => self.report(&format!("{}.object", path), Rule::IntransitiveObject),
+                (None, false) if !matches!(object, TaggedObject::Activity(_)) => self.report(path, Rule::MissingObject),
+                _ => {}
+            }
+            if matches!(object, TaggedObject::Add(_) | TaggedObject::Remove(_)) && activity.target.is_none() {
+                self.report(path, Rule::MissingTarget);
+            }
+            for (property, values) in [("actor", &activity.actor), ("object", &activity.object), ("target", &activity.target),
+                ("result", &activity.result), ("origin", &activity.origin), ("instrument", &activity.instrument)] {
+                self.maybe_objects(path, property, values);
+            }
+        }
+        match object {
+            TaggedObject::Question(Question { oneOf, anyOf, .. }) => {
+                if oneOf.is_some() && anyOf.is_some() {
+                    self.report(path, Rule::QuestionChoices);
+                }
+                self.maybe_objects(path, "oneOf", oneOf);
+                self.maybe_objects(path, "anyOf", anyOf);
+            }
+            TaggedObject::Tombstone(Tombstone { deleted: Some(deleted), .. }) if deleted.to_utc() > self.now => {
+                self.report(&format!("{}.deleted", path), Rule::DeletedInFuture);
+            }
+            TaggedObject::Place(place) => self.place(path, place),
+            TaggedObject::Relationship(Relationship { subject, object, relationship, .. }) => {
+                if subject.is_none() || object.is_none() || relationship.is_none() {
+                    self.report(path, Rule::IncompleteRelationship);
+                }
+                if let Some(subject) = subject {
+                    self.maybe_object(&format!("{}.subject", path), subject);
+                }
+                self.maybe_objects(path, "object", object);
+                self.values(path, "relationship", relationship, |validator, path, object| validator.object(path, object, false));
+            }
+            TaggedObject::Profile(objects::Profile { describes, .. }) => match describes {
+                Some(describes) => self.object(&format!("{}.describes", path), describes, false),
+                None => self.report(path, Rule::MissingDescribes)
+            },
+            TaggedObject::Collection(collection) => self.collection_properties(path, CollectionProperties::of_collection(collection)),
+            TaggedObject::OrderedCollection(collection) => self.collection_properties(path, CollectionProperties::of_ordered(collection)),
+            TaggedObject::CollectionPage(page) => self.collection_properties(path, CollectionProperties::of_page(page)),
+            TaggedObject::OrderedCollectionPage(page) => self.collection_properties(path, CollectionProperties::of_ordered_page(page)),
+            _ => {}
+        }
+    }
+
+    fn place(&mut self, path: &str, place: &Place) {
+        if place.latitude.is_some_and(|latitude| !(-90.0..=90.0).contains(&latitude)) {
+            self.report(&format!("{}.latitude", path), Rule::CoordinatesOutOfRange);
+        }
+        if place.longitude.is_some_and(|longitude| !(-180.0..=180.0).contains(&longitude)) {
+            self.report(&format!("{}.longitude", path), Rule::CoordinatesOutOfRange);
+        }
+        let known_unit = |units: &str| matches!(units, "cm" | "feet" | "inches" | "km" | "m" | "miles") || is_absolute_uri(units);
+        if place.units.as_deref().is_some_and(|units| !known_unit(units)) {
+            self.report(&format!("{}.units", path), Rule::InvalidUnits);
+        }
+    }
+
+    /// Properties every object has
+    fn object_properties(&mut self, path: &str, object: &Object) {
+        if let Some(id) = &object.id {
+            self.uri(&format!("{}.id", path), id);
+        }
+        if let (Some(start_time), Some(end_time)) = (&object.startTime, &object.endTime) {
+            if end_time < start_time {
+                self.report(&format!("{}.endTime", path), Rule::EndBeforeStart);
+            }
+        }
+        if let (Some(published), Some(updated)) = (&object.published, &object.updated) {
+            if updated < published {
+                self.report(&format!("{}.updated", path), Rule::UpdatedBeforePublished);
+            }
+        }
+        if object.duration.as_deref().is_some_and(|duration| !is_xsd_duration(duration)) {
+            self.report(&format!("{}.duration", path), Rule::InvalidDuration);
+        }
+        if object.mediaType.as_deref().is_some_and(|media_type| !is_media_type(media_type)) {
+            self.report(&format!("{}.mediaType", path), Rule::InvalidMediaType);
+        }
+        for (property, values) in [("attachment", &object.attachment), ("attributedTo", &object.attributedTo), ("audience", &object.audience),
+            ("context", &object.context), ("generator", &object.generator), ("inReplyTo", &object.inReplyTo), ("location", &object.location),
+            ("preview", &object.preview), ("tag", &object.tag), ("to", &object.to), ("bto", &object.bto), ("cc", &object.cc), ("bcc", &object.bcc)] {
+            self.maybe_objects(path, property, values);
+        }
+        self.maybe_images(path, "icon", &object.icon);
+        self.maybe_images(path, "image", &object.image);
+        self.maybe_links(path, "url", &object.url);
+        if let Some(replies) = &object.replies {
+            self.maybe_collection(&format!("{}.replies", path), replies);
+        }
+    }
+}
+
+impl TaggedObject {
+    /// Check this object, and the objects embedded in it, against the rules of the vocabulary and protocol
+    pub fn validate(&self, profile: ValidationProfile) -> Vec<Diagnostic> {
+        self.validate_at(profile, Utc::now())
+    }
+
+    /// See [`TaggedObject::validate`]; Dates are checked against `now`
+    pub fn validate_at(&self, profile: ValidationProfile, now: DateTime<Utc>) -> Vec<Diagnostic> {
+        let mut validator = Validator { profile, now, diagnostics: Vec::new() };
+        validator.object("$", self, true);
+        validator.diagnostics
+    }
+}
+
+/// The collection properties shared by collections and their pages
+struct CollectionProperties<'a> {
+    total_items: Option<u64>,
+    items_property: &'static str,
+    items: &'a Option<FoldedSlice<MaybeObject>>,
+    pages: [(&'static str, Option<&'a MaybeCollectionPage>); 5],
+    part_of: Option<&'a MaybeCollection>,
+}
+
+impl<'a> CollectionProperties<'a> {
+    fn of_collection(collection: &'a Collection) -> Self {
+        CollectionProperties {
+            total_items: collection.totalItems,
+            items_property: "items",
+            items: &collection.items,
+            pages: [("first", collection.first.as_deref()), ("last", collection.last.as_deref()), ("current", collection.current.as_deref()), ("next", None), ("prev", None)],
+            part_of: None,
+        }
+    }
+
+    fn of_ordered(collection: &'a OrderedCollection) -> Self {
+        CollectionProperties {
+            total_items: collection.totalItems,
+            items_property: "orderedItems",
+            items: &collection.orderedItems,
+            pages: [("first", collection.first.as_deref()), ("last", collection.last.as_deref()), ("current", collection.current.as_deref()), ("next", None), ("prev", None)],
+            part_of: None,
+        }
+    }
+
+    fn with_siblings(mut self, next: &'a Option<PropNext>, prev: &'a Option<PropPrev>) -> Self {
+        self.pages[3].1 = next.as_deref();
+        self.pages[4].1 = prev.as_deref();
+        self
+    }
+
+    fn of_page(page: &'a CollectionPage) -> Self {
+        CollectionProperties {
+            part_of: page.partOf.as_deref(),
+            ..CollectionProperties::of_collection(&page.collection_fields).with_siblings(&page.next, &page.prev)
+        }
+    }
+
+    fn of_ordered_page(page: &'a OrderedCollectionPage) -> Self {
+        CollectionProperties {
+            part_of: page.partOf.as_deref(),
+            ..CollectionProperties::of_ordered(&page.collection_fields).with_siblings(&page.next, &page.prev)
+        }
+    }
+}
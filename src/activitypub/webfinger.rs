@@ -0,0 +1,255 @@
+//! WebFinger (RFC 7033); Discovery of actors by `@user@host` handle, and the JSON Resource Descriptors served for local actors
+//!
+//! Servers with a non-standard WebFinger endpoint advertise it through host-meta (RFC 6415), in XRD (XML) or JRD (JSON) form.
+
+use std::collections::HashMap;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use crate::activitypub::resolver::{resolve, ResolveError, Resolver};
+use crate::linkeddata::activity_streams::TaggedObject;
+
+pub const JRD_CONTENT_TYPE: &str = "application/jrd+json";
+pub const XRD_CONTENT_TYPE: &str = "application/xrd+xml";
+pub const WEBFINGER_PATH: &str = "/.well-known/webfinger";
+pub const HOST_META_PATH: &str = "/.well-known/host-meta";
+pub const HOST_META_JSON_PATH: &str = "/.well-known/host-meta.json";
+/// Relation of the host-meta link holding the WebFinger URI template
+pub const LRDD_REL: &str = "lrdd";
+const XRD_NAMESPACE: &str = "http://docs.oasis-open.org/ns/xri/xrd-1.0";
+/// Relation of the link to an actor document
+pub const SELF_REL: &str = "self";
+/// Relation of the link to an actor's HTML profile page
+pub const PROFILE_PAGE_REL: &str = "http://webfinger.net/rel/profile-page";
+
+/// JSON Resource Descriptor
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Jrd {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subject: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub properties: HashMap<String, Option<String>>,
+    #[serde(default)]
+    pub links: Vec<JrdLink>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct JrdLink {
+    pub rel: String,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub media_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub href: Option<String>,
+    /// URI template, e.g. for the OStatus subscribe link
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub titles: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub properties: HashMap<String, Option<String>>,
+}
+
+/// Whether a media type denotes an ActivityStreams document
+fn is_activity_media_type(media_type: &str) -> bool {
+    media_type == "application/activity+json"
+        || (media_type.starts_with("application/ld+json") && media_type.contains("https://www.w3.org/ns/activitystreams"))
+}
+
+impl Jrd {
+    /// The JRD for a local actor, linking to its actor document and, if any, its profile page
+    pub fn for_actor(username: &str, host: &str, actor: &str, profile_page: Option<&str>) -> Jrd {
+        let mut links = vec![JrdLink {
+            rel: SELF_REL.to_string(),
+            media_type: Some("application/activity+json".to_string()),
+            href: Some(actor.to_string()),
+            ..JrdLink::default()
+        }];
+        links.extend(profile_page.map(|profile_page| JrdLink {
+            rel: PROFILE_PAGE_REL.to_string(),
+            media_type: Some("text/html".to_string()),
+            href: Some(profile_page.to_string()),
+            ..JrdLink::default()
+        }));
+        Jrd {
+            subject: Some(format!("acct:{}@{}", username, host)),
+            aliases: std::iter::once(actor).chain(profile_page).map(str::to_string).collect(),
+            properties: HashMap::new(),
+            links,
+        }
+    }
+
+    /// Parse an XRD document, such as an XML host-meta
+    pub fn from_xrd(xml: &str) -> Result<Jrd, ResolveError> {
+        let document = roxmltree::Document::parse(xml).map_err(ResolveError::Xml)?;
+        let root = document.root_element();
+        if !root.has_tag_name((XRD_NAMESPACE, "XRD")) {
+            return Err(ResolveError::UnexpectedType("expected an XRD document"));
+        }
+        let text = |node: roxmltree::Node| node.text().unwrap_or_default().trim().to_string();
+        let property = |node: roxmltree::Node| node.attribute("type").map(|property_type| (property_type.to_string(), node.text().map(str::to_string)));
+        fn elements<'a, 'input>(node: roxmltree::Node<'a, 'input>, name: &str) -> Vec<roxmltree::Node<'a, 'input>> {
+            node.children().filter(|child| child.has_tag_name((XRD_NAMESPACE, name))).collect()
+        }
+
+        let mut jrd = Jrd {
+            subject: elements(root, "Subject").first().map(|&subject| text(subject)),
+            aliases: elements(root, "Alias").into_iter().map(text).collect(),
+            properties: elements(root, "Property").into_iter().filter_map(property).collect(),
+            links: Vec::new(),
+        };
+        for link in elements(root, "Link") {
+            let Some(rel) = link.attribute("rel") else { continue };
+            jrd.links.push(JrdLink {
+                rel: rel.to_string(),
+                media_type: link.attribute("type").map(str::to_string),
+                href: link.attribute("href").map(str::to_string),
+                template: link.attribute("template").map(str::to_string),
+                titles: elements(link, "Title").into_iter()
+                    .map(|title| (title.attribute(("http://www.w3.org/XML/1998/namespace", "lang")).unwrap_or("und").to_string(), text(title)))
+                    .collect(),
+                properties: elements(link, "Property").into_iter().filter_map(property).collect(),
+            });
+        }
+        Ok(jrd)
+    }
+
+    /// The WebFinger URI template of a host-meta document
+    pub fn lrdd_template(&self) -> Option<&str> {
+        self.links.iter()
+            .filter(|link| link.rel == LRDD_REL)
+            .find_map(|link| link.template.as_deref())
+    }
+
+    /// The `self` link to an ActivityStreams actor document
+    pub fn actor_link(&self) -> Option<&str> {
+        self.links.iter()
+            .filter(|link| link.rel == SELF_REL && link.media_type.as_deref().is_some_and(is_activity_media_type))
+            .find_map(|link| link.href.as_deref())
+    }
+}
+
+/// Whether `host` is a host name or IP address, optionally with a port, and nothing else
+fn is_valid_host(host: &str) -> bool {
+    let allowed = |c: char| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | ':' | '[' | ']');
+    if host.is_empty() || !host.chars().all(allowed) {
+        return false;
+    }
+    match Url::parse(&format!("https://{}/", host)) {
+        Ok(url) => url.host_str().is_some() && url.path() == "/",
+        Err(_) => false
+    }
+}
+
+/// Split a handle into username and host; Accepts `@user@host`, `user@host` and `acct:user@host`
+///
+/// The host may only be a host name or IP address with an optional port; A path, query or user info is rejected.
+pub fn parse_handle(handle: &str) -> Option<(&str, &str)> {
+    let account = handle.strip_prefix("acct:").or_else(|| handle.strip_prefix('@')).unwrap_or(handle);
+    match account.split_once('@') {
+        Some((username, host)) if !username.is_empty() && is_valid_host(host) => Some((username, host)),
+        _ => None
+    }
+}
+
+/// Percent-encode everything but unreserved characters, for use in a URI template
+fn percent_encode(value: &str) -> String {
+    value.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (byte as char).to_string(),
+            byte => format!("%{:02X}", byte)
+        })
+        .collect()
+}
+
+/// The standard WebFinger URI template of a host
+pub fn default_template(host: &str) -> String {
+    format!("https://{}{}?resource={{uri}}", host, WEBFINGER_PATH)
+}
+
+/// Expand a WebFinger URI template (`{uri}`) for a resource
+pub fn expand_template(template: &str, resource: &str) -> String {
+    template.replace("{uri}", &percent_encode(resource))
+}
+
+/// The standard WebFinger URL querying an `acct:` resource on its host
+pub fn webfinger_url(username: &str, host: &str) -> Result<String, ResolveError> {
+    let url = expand_template(&default_template(host), &format!("acct:{}@{}", username, host));
+    Url::parse(&url).map_err(|_| ResolveError::InvalidUri(host.to_string()))?;
+    Ok(url)
+}
+
+/// Fetch a JRD, requesting it by its media type; WebFinger servers need not serve ActivityStreams media types
+fn fetch_jrd(resolver: &dyn Resolver, uri: &str) -> Result<Jrd, ResolveError> {
+    serde_json::from_slice(&resolver.fetch_raw(uri, JRD_CONTENT_TYPE)?).map_err(ResolveError::Deserialization)
+}
+
+/// Retrieve the host-meta of a host, in XRD form or else in JSON form
+pub fn host_meta(resolver: &dyn Resolver, host: &str) -> Result<Jrd, ResolveError> {
+    let xrd = resolver.fetch_raw(&format!("https://{}{}", host, HOST_META_PATH), XRD_CONTENT_TYPE)
+        .and_then(|xrd| Jrd::from_xrd(&String::from_utf8_lossy(&xrd)));
+    match xrd {
+        Ok(jrd) => Ok(jrd),
+        Err(_) => fetch_jrd(resolver, &format!("https://{}{}", host, HOST_META_JSON_PATH))
+    }
+}
+
+/// Query the JRD of a handle
+///
+/// If the standard endpoint fails, the endpoint advertised in the host's host-meta is tried.
+pub fn webfinger(resolver: &dyn Resolver, handle: &str) -> Result<Jrd, ResolveError> {
+    let (username, host) = parse_handle(handle).ok_or_else(|| ResolveError::InvalidUri(handle.to_string()))?;
+    let standard = webfinger_url(username, host)?;
+    match fetch_jrd(resolver, &standard) {
+        Ok(jrd) => Ok(jrd),
+        Err(error) => {
+            let Some(template) = host_meta(resolver, host).ok().and_then(|host_meta| host_meta.lrdd_template().map(str::to_string)) else { return Err(error) };
+            let advertised = expand_template(&template, &format!("acct:{}@{}", username, host));
+            if advertised == standard {
+                return Err(error);
+            }
+            fetch_jrd(resolver, &advertised)
+        }
+    }
+}
+
+/// The host of a URL as written in a handle, with its port unless it is the default
+fn authority(url: &str) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+    let host = url.host_str()?;
+    Some(match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string()
+    })
+}
+
+/// Resolve a handle to its actor document
+///
+/// An actor on another host than the handle's, e.g. of a domain delegating to its server, must be confirmed by a WebFinger query of the handle on that host.
+pub fn resolve_handle(resolver: &dyn Resolver, handle: &str) -> Result<TaggedObject, ResolveError> {
+    let (username, host) = parse_handle(handle).ok_or_else(|| ResolveError::InvalidUri(handle.to_string()))?;
+    let jrd = webfinger(resolver, handle)?;
+    let actor_link = jrd.actor_link().ok_or_else(|| ResolveError::NotFound(handle.to_string()))?;
+    let actor_host = authority(actor_link).ok_or_else(|| ResolveError::InvalidUri(actor_link.to_string()))?;
+    if !actor_host.eq_ignore_ascii_case(host) {
+        let confirmation = expand_template(&default_template(&actor_host), &format!("acct:{}@{}", username, host));
+        if fetch_jrd(resolver, &confirmation).ok().as_ref().and_then(Jrd::actor_link) != Some(actor_link) {
+            return Err(ResolveError::OriginMismatch { requested: handle.to_string(), id: actor_link.to_string() });
+        }
+    }
+    let actor = resolve::<TaggedObject>(resolver, actor_link)?;
+    match actor.is_actor() {
+        true => Ok(actor),
+        false => Err(ResolveError::UnexpectedType("expected an actor"))
+    }
+}
+
+/// The username of a WebFinger `resource` query for an account on `host`, e.g. `alice` for `acct:alice@host`
+///
+/// Servers look up the local actor by this username and respond with [`Jrd::for_actor`], or 404 if there is none.
+pub fn local_username<'r>(resource: &'r str, host: &str) -> Option<&'r str> {
+    match parse_handle(resource) {
+        Some((username, resource_host)) if resource_host.eq_ignore_ascii_case(host) => Some(username),
+        _ => None
+    }
+}
@@ -1,4 +1,5 @@
-mod linkeddata;
+pub mod activitypub;
+pub mod linkeddata;
 
 #[cfg(test)]
 mod tests;
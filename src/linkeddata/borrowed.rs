@@ -0,0 +1,125 @@
+//! Views of received documents that borrow their strings from the input, for routing bulk inbox traffic
+//!
+//! [`ObjectRef`] holds the properties needed to route an activity (its type, actor, object and addressing) and its content.
+//! URIs and text are [`Cow`]s borrowing from the input, and are only copied if the JSON string has escapes to undo, e.g. `https:\/\/`.
+//! Other properties are skipped without being read into memory.
+//!
+//! Errors are serde's and are not located; Parse a document into the vocabulary through [`deserialize`](crate::linkeddata::deserialize) for those.
+
+use std::borrow::Cow;
+use std::fmt::Formatter;
+use serde::{Deserialize, Deserializer};
+use serde::de::Visitor;
+use crate::linkeddata::util::FoldedSlice;
+
+/// Borrows strings without escapes, and copies the others
+struct CowStrVisitor;
+
+impl<'de> Visitor<'de> for CowStrVisitor {
+    type Value = Cow<'de, str>;
+
+    fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
+        formatter.write_str("a string")
+    }
+
+    fn visit_borrowed_str<E: serde::de::Error>(self, v: &'de str) -> Result<Self::Value, E> {
+        Ok(Cow::Borrowed(v))
+    }
+
+    fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        Ok(Cow::Owned(v.to_string()))
+    }
+
+    fn visit_string<E: serde::de::Error>(self, v: String) -> Result<Self::Value, E> {
+        Ok(Cow::Owned(v))
+    }
+}
+
+/// Serde only borrows into a `Cow` field as such, and copies into one within an `Option` or a newtype variant
+fn cow_str<'de: 'a, 'a, D: Deserializer<'de>>(deserializer: D) -> Result<Cow<'a, str>, D::Error> {
+    deserializer.deserialize_str(CowStrVisitor)
+}
+
+fn optional_cow_str<'de: 'a, 'a, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Cow<'a, str>>, D::Error> {
+    struct Text<'a>(Cow<'a, str>);
+
+    impl<'de> Deserialize<'de> for Text<'de> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            cow_str(deserializer).map(Text)
+        }
+    }
+
+    Option::<Text>::deserialize(deserializer).map(|text| text.map(|Text(text)| text))
+}
+
+/// A URI, or an embedded object or link
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum MaybeObjectRef<'a> {
+    URI(#[serde(borrow, deserialize_with = "cow_str")] Cow<'a, str>),
+    #[serde(borrow)]
+    Object(Box<ObjectRef<'a>>),
+}
+
+impl MaybeObjectRef<'_> {
+    /// The URI of the referenced object; For links, this is the link's target
+    pub fn id(&self) -> Option<&str> {
+        match self {
+            MaybeObjectRef::URI(uri) => Some(uri),
+            MaybeObjectRef::Object(object) => object.id.as_deref().or(object.href.as_deref())
+        }
+    }
+}
+
+pub type PropRef<'a> = FoldedSlice<MaybeObjectRef<'a>>;
+
+/// The routing properties and content of an object, activity or link
+#[allow(non_snake_case)]
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(default)]
+pub struct ObjectRef<'a> {
+    #[serde(borrow, deserialize_with = "optional_cow_str")]
+    pub id: Option<Cow<'a, str>>,
+    #[serde(borrow, rename = "type", deserialize_with = "optional_cow_str")]
+    pub kind: Option<Cow<'a, str>>,
+    #[serde(borrow, deserialize_with = "optional_cow_str")]
+    pub href: Option<Cow<'a, str>>,
+    #[serde(borrow)]
+    pub actor: Option<PropRef<'a>>,
+    #[serde(borrow)]
+    pub object: Option<PropRef<'a>>,
+    #[serde(borrow)]
+    pub target: Option<PropRef<'a>>,
+    #[serde(borrow)]
+    pub attributedTo: Option<PropRef<'a>>,
+    #[serde(borrow)]
+    pub inReplyTo: Option<PropRef<'a>>,
+    #[serde(borrow)]
+    pub to: Option<PropRef<'a>>,
+    #[serde(borrow)]
+    pub bto: Option<PropRef<'a>>,
+    #[serde(borrow)]
+    pub cc: Option<PropRef<'a>>,
+    #[serde(borrow)]
+    pub bcc: Option<PropRef<'a>>,
+    #[serde(borrow)]
+    pub audience: Option<PropRef<'a>>,
+    #[serde(borrow, deserialize_with = "optional_cow_str")]
+    pub published: Option<Cow<'a, str>>,
+    #[serde(borrow, deserialize_with = "optional_cow_str")]
+    pub name: Option<Cow<'a, str>>,
+    #[serde(borrow, deserialize_with = "optional_cow_str")]
+    pub summary: Option<Cow<'a, str>>,
+    #[serde(borrow, deserialize_with = "optional_cow_str")]
+    pub content: Option<Cow<'a, str>>,
+}
+
+impl<'a> ObjectRef<'a> {
+    /// The recipients of the object, from `to`, `bto`, `cc`, `bcc` and `audience` in that order
+    pub fn recipients(&self) -> impl Iterator<Item = &str> {
+        [&self.to, &self.bto, &self.cc, &self.bcc, &self.audience].into_iter()
+            .flatten()
+            .flat_map(|values| values.iter())
+            .filter_map(MaybeObjectRef::id)
+    }
+}
@@ -0,0 +1,463 @@
+//! Deserialization with located errors; Failures name the JSON path of the offending property and, for properties accepting several types, why each type was rejected
+//!
+//! Serde's derived untagged and internally tagged enums buffer their input and report only "data did not match any variant".
+//! The enums of the ActivityStreams vocabulary implement [`Deserialize`] through [`untagged_deserialize`] and [`tagged_deserialize`] instead,
+//! which try each variant on the buffered input and record a [`DeserializeError`] on failure.
+//! Since serde errors only carry a message, the record is passed to the enclosing attempt out of band, and [`from_value`] returns the outermost one.
+//!
+//! In [`Mode::Lenient`], invalid property values are dropped instead, and reported as warnings.
+//!
+//! Variants are only tried if they pass their [`Precheck`], and the last one is given the buffered input rather than a copy.
+
+use std::cell::{Cell, RefCell};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use serde::{Deserialize, Deserializer};
+use serde::de::{DeserializeOwned, DeserializeSeed, MapAccess, Visitor};
+use serde::de::value::BorrowedStrDeserializer;
+use serde_json::{Map, Value};
+use chrono::{FixedOffset, Offset, Utc};
+use crate::linkeddata::xsd::XsdDateTime;
+
+/// How invalid property values are handled
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Mode {
+    /// Any invalid value fails the whole document
+    #[default]
+    Strict,
+    /// Invalid property values, and invalid elements of arrays, are dropped; Only values that are needed to determine the object's type fail the document
+    Lenient,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeserializeError {
+    /// JSON path of the value that could not be deserialized, e.g. `$.object.attachment[1].url`
+    pub path: String,
+    pub message: String,
+    /// For values accepting several types, the failed attempt at each of them
+    pub attempts: Vec<VariantAttempt>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VariantAttempt {
+    pub variant: &'static str,
+    pub error: DeserializeError,
+}
+
+impl DeserializeError {
+    fn new(path: String, message: impl Display) -> Self {
+        DeserializeError { path, message: message.to_string(), attempts: Vec::new() }
+    }
+
+    /// Error of a value that does not record its own errors
+    pub(crate) fn leaf(message: impl Display) -> Self {
+        DeserializeError::new("$".to_string(), message)
+    }
+
+    /// Error of a value none of whose variants matched
+    pub fn no_variant(name: &str, attempts: Vec<VariantAttempt>) -> Self {
+        DeserializeError { path: "$".to_string(), message: format!("no variant of {} matched", name), attempts }
+    }
+
+    /// Error of an object whose `type` is unknown or missing
+    pub fn unknown_type(name: &str, value: &Value) -> Self {
+        DeserializeError::unknown_shape(name, &Shape::of(value))
+    }
+
+    pub(crate) fn unknown_shape(name: &str, shape: &Shape) -> Self {
+        match shape {
+            Shape::Object { object_type: Some(Value::String(object_type)) } => DeserializeError::new("$.type".to_string(), format!("unknown type `{}` for {}", object_type, name)),
+            Shape::Object { object_type: Some(_) } => DeserializeError::new("$.type".to_string(), "type is not a string"),
+            Shape::Object { object_type: None } => DeserializeError::new("$".to_string(), "missing property `type`"),
+            Shape::Other(kind) => DeserializeError::new("$".to_string(), format!("expected an object with a type, found {}", kind)),
+        }
+    }
+
+    /// This error relative to the value containing the failing one; `segment` is e.g. `.object` or `[1]`
+    pub(crate) fn prefixed(mut self, segment: &str) -> Self {
+        self.path.insert_str(1, segment);
+        for attempt in &mut self.attempts {
+            attempt.error = std::mem::replace(&mut attempt.error, DeserializeError::new(String::new(), "")).prefixed(segment);
+        }
+        self
+    }
+
+    /// The most specific failure; Follows the attempt that got deepest into the value
+    pub fn deepest(&self) -> &DeserializeError {
+        self.attempts.iter()
+            .map(|attempt| attempt.error.deepest())
+            .max_by_key(|error| error.path.len())
+            .filter(|error| error.path.len() > self.path.len())
+            .unwrap_or(self)
+    }
+}
+
+impl Display for DeserializeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)?;
+        for (index, attempt) in self.attempts.iter().enumerate() {
+            write!(f, "{}{}: {}", if index == 0 { " (" } else { "; " }, attempt.variant, attempt.error)?;
+        }
+        if !self.attempts.is_empty() {
+            write!(f, ")")?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for DeserializeError {}
+
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+thread_local! {
+    /// The error of the innermost enclosing attempt, recorded by a failing [`Deserialize`] impl of this crate
+    static FAILURE: RefCell<Option<DeserializeError>> = const { RefCell::new(None) };
+    static MODE: Cell<Mode> = const { Cell::new(Mode::Strict) };
+    /// Values dropped within the innermost enclosing [`collect`]
+    static WARNINGS: RefCell<Vec<DeserializeError>> = const { RefCell::new(Vec::new()) };
+    /// Offset taken for dates without one within the innermost enclosing [`with_assumed_offset`]
+    static ASSUMED_OFFSET: Cell<Option<FixedOffset>> = const { Cell::new(None) };
+}
+
+/// Run `deserialize`, taking dates without an offset to be at `offset` rather than UTC
+///
+/// Applies to every date deserialized within, whether through this module or directly through serde.
+pub fn with_assumed_offset<R>(offset: FixedOffset, deserialize: impl FnOnce() -> R) -> R {
+    let outer = ASSUMED_OFFSET.replace(Some(offset));
+    let result = deserialize();
+    ASSUMED_OFFSET.set(outer);
+    result
+}
+
+/// The offset taken for dates without one; UTC outside of [`with_assumed_offset`]
+pub fn assumed_offset() -> FixedOffset {
+    ASSUMED_OFFSET.get().unwrap_or(Utc.fix())
+}
+
+/// Take the recorded error of the innermost attempt
+pub(crate) fn take_failure() -> Option<DeserializeError> {
+    FAILURE.with(|failure| failure.borrow_mut().take())
+}
+
+/// Record `error` for the enclosing attempt, and convert it to the deserializer's error type
+pub fn fail<E: serde::de::Error>(error: DeserializeError) -> E {
+    let message = E::custom(&error);
+    FAILURE.with(|failure| *failure.borrow_mut() = Some(error));
+    message
+}
+
+pub fn is_lenient() -> bool {
+    MODE.get() == Mode::Lenient
+}
+
+/// Report a dropped value
+pub fn warn(warning: DeserializeError) {
+    WARNINGS.with(|warnings| warnings.borrow_mut().push(warning));
+}
+
+/// Run `f`, collecting the warnings it reports rather than passing them to the enclosing call
+pub fn collect<R>(f: impl FnOnce() -> R) -> (R, Vec<DeserializeError>) {
+    let outer = WARNINGS.take();
+    let result = f();
+    (result, WARNINGS.replace(outer))
+}
+
+/// Deserialize a value whose type records its own errors (an enum of this crate, a [`FoldedSlice`](crate::linkeddata::util::FoldedSlice)), or a scalar
+///
+/// Warnings of a failed attempt are discarded.
+pub fn attempt<T: DeserializeOwned>(value: &Value) -> Result<T, DeserializeError> {
+    attempt_with(|| T::deserialize(value))
+}
+
+/// [`attempt`] on an owned value, whose strings are moved into the result rather than copied
+pub fn attempt_owned<T: DeserializeOwned>(value: Value) -> Result<T, DeserializeError> {
+    attempt_with(|| T::deserialize(value))
+}
+
+fn attempt_with<T>(deserialize: impl FnOnce() -> Result<T, serde_json::Error>) -> Result<T, DeserializeError> {
+    take_failure();
+    let (result, warnings) = collect(deserialize);
+    match result {
+        Ok(deserialized) => {
+            warnings.into_iter().for_each(warn);
+            Ok(deserialized)
+        }
+        Err(error) => Err(take_failure().unwrap_or_else(|| DeserializeError::leaf(error)))
+    }
+}
+
+/// What a [`Precheck`] rejection is about: The kind of a value, and the `type` of an object
+///
+/// This is kept apart from the buffered value, which is moved into the last variant tried.
+#[derive(Debug, Clone)]
+pub enum Shape {
+    Object { object_type: Option<Value> },
+    Other(&'static str),
+}
+
+impl Shape {
+    pub fn of(value: &Value) -> Self {
+        match value {
+            Value::Object(properties) => Shape::Object { object_type: properties.get("type").cloned() },
+            value => Shape::Other(kind(value))
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            Shape::Object { .. } => "an object",
+            Shape::Other(kind) => kind
+        }
+    }
+}
+
+/// A cheap check of whether a value may deserialize as a variant of an untagged enum; Variants not accepted are not tried
+pub trait Precheck {
+    fn accepts(value: &Value) -> bool;
+
+    /// Why a value of this shape is not accepted
+    fn rejection(shape: &Shape) -> DeserializeError;
+}
+
+impl Precheck for String {
+    fn accepts(value: &Value) -> bool {
+        value.is_string()
+    }
+
+    fn rejection(shape: &Shape) -> DeserializeError {
+        DeserializeError::leaf(format!("expected a string, found {}", shape.kind()))
+    }
+}
+
+impl Precheck for bool {
+    fn accepts(value: &Value) -> bool {
+        value.is_boolean()
+    }
+
+    fn rejection(shape: &Shape) -> DeserializeError {
+        DeserializeError::leaf(format!("expected a boolean, found {}", shape.kind()))
+    }
+}
+
+impl Precheck for XsdDateTime {
+    fn accepts(value: &Value) -> bool {
+        value.is_string()
+    }
+
+    fn rejection(shape: &Shape) -> DeserializeError {
+        String::rejection(shape)
+    }
+}
+
+/// Deserialize a struct from an object in a single pass, locating a failure at the property it occurs in
+///
+/// Fields flattened with [`flattened`] are deserialized through this as well, so failures within them are located too.
+/// In lenient mode, failing properties are dropped as they are read, and reported as warnings located the same way.
+pub fn attempt_fields<T: DeserializeOwned>(value: &Value) -> Result<T, DeserializeError> {
+    match value {
+        Value::Object(properties) => attempt_with(|| T::deserialize(Fields(properties))),
+        value => attempt(value)
+    }
+}
+
+/// Deserialize a `#[serde(flatten)]` field through [`attempt_fields`]
+///
+/// Serde buffers the properties of flattened fields and reports their failures without a location; This locates them, relative to the properties shared with the enclosing struct.
+pub fn flattened<'de, D: Deserializer<'de>, T: DeserializeOwned>(deserializer: D) -> Result<T, D::Error> {
+    let value = Value::deserialize(deserializer)?;
+    attempt_fields(&value).map_err(fail)
+}
+
+/// The properties of an object as a deserializer of structs
+struct Fields<'de>(&'de Map<String, Value>);
+
+impl<'de> Deserializer<'de> for Fields<'de> {
+    type Error = serde_json::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_map(FieldAccess { properties: self.0.iter(), property: None })
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf option unit unit_struct seq tuple tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+/// Access to the properties of an object, recording which one a failure occurs in
+struct FieldAccess<'de> {
+    properties: serde_json::map::Iter<'de>,
+    /// The property whose key was read last
+    property: Option<(&'de str, &'de Value)>,
+}
+
+impl<'de> FieldAccess<'de> {
+    /// Deserialize the value of the current property; In lenient mode, a failing value is deserialized from `absent` instead if that succeeds
+    fn located<R>(&mut self, deserialize: impl FnOnce(&'de Value) -> Result<R, serde_json::Error>, absent: impl FnOnce() -> Option<R>) -> Result<R, serde_json::Error> {
+        let (key, value) = self.property.take().expect("a value follows its key");
+        take_failure();
+        let (result, warnings) = collect(|| deserialize(value));
+        let segment = || format!(".{}", key);
+        let error = match result {
+            Ok(deserialized) => {
+                warnings.into_iter().for_each(|warning| warn(warning.prefixed(&segment())));
+                return Ok(deserialized);
+            }
+            Err(error) => take_failure().unwrap_or_else(|| DeserializeError::leaf(error)).prefixed(&segment())
+        };
+        match is_lenient().then(absent).flatten() {
+            Some(dropped) => {
+                warn(error);
+                Ok(dropped)
+            }
+            None => Err(fail(error))
+        }
+    }
+}
+
+impl<'de> MapAccess<'de> for FieldAccess<'de> {
+    type Error = serde_json::Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error> {
+        let Some((key, value)) = self.properties.next() else { return Ok(None) };
+        self.property = Some((key, value));
+        seed.deserialize(BorrowedStrDeserializer::new(key)).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Self::Error> {
+        self.located(|value| seed.deserialize(value), || None)
+    }
+
+    /// Derived structs read their fields through this; Optional fields are absent when deserialized from `null`
+    fn next_value<V: Deserialize<'de>>(&mut self) -> Result<V, Self::Error> {
+        self.located(V::deserialize, || V::deserialize(&Value::Null).ok())
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.properties.len())
+    }
+}
+
+/// Deserialize a value, locating failures
+pub fn from_value<T: DeserializeOwned>(value: &Value) -> Result<T, DeserializeError> {
+    from_value_with(value, Mode::Strict).map(|(deserialized, _)| deserialized)
+}
+
+pub fn from_str<T: DeserializeOwned>(json: &str) -> Result<T, DeserializeError> {
+    from_str_with(json, Mode::Strict).map(|(deserialized, _)| deserialized)
+}
+
+pub fn from_slice<T: DeserializeOwned>(json: &[u8]) -> Result<T, DeserializeError> {
+    from_slice_with(json, Mode::Strict).map(|(deserialized, _)| deserialized)
+}
+
+/// Deserialize a value in the given mode; Returns the values dropped in lenient mode as warnings
+pub fn from_value_with<T: DeserializeOwned>(value: &Value, mode: Mode) -> Result<(T, Vec<DeserializeError>), DeserializeError> {
+    let outer = MODE.replace(mode);
+    let (result, warnings) = collect(|| attempt(value));
+    MODE.set(outer);
+    result.map(|deserialized| (deserialized, warnings))
+}
+
+pub fn from_str_with<T: DeserializeOwned>(json: &str, mode: Mode) -> Result<(T, Vec<DeserializeError>), DeserializeError> {
+    let value = serde_json::from_str::<Value>(json).map_err(DeserializeError::leaf)?;
+    from_value_with(&value, mode)
+}
+
+pub fn from_slice_with<T: DeserializeOwned>(json: &[u8], mode: Mode) -> Result<(T, Vec<DeserializeError>), DeserializeError> {
+    let value = serde_json::from_slice::<Value>(json).map_err(DeserializeError::leaf)?;
+    from_value_with(&value, mode)
+}
+
+/// Properties of an object that are explicitly `null`
+///
+/// Deserialized objects do not tell these apart from absent properties, and omit both when serialized; Partial updates use `null` to remove a property, though.
+pub fn null_properties(object: &Value) -> Vec<&str> {
+    object.as_object()
+        .map(|properties| properties.iter().filter(|(_, value)| value.is_null()).map(|(key, _)| key.as_str()).collect())
+        .unwrap_or_default()
+}
+
+/// Implement [`Deserialize`](serde::Deserialize) for an untagged enum of newtype variants, trying each variant that passes its [`Precheck`] in order
+macro_rules! untagged_deserialize {
+    ($name:ident { $($variant:ident($variant_type:ty)),+ $(,)? }) => {
+        impl<'de> serde::Deserialize<'de> for $name {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                use $crate::linkeddata::deserialize::{attempt_owned, fail, DeserializeError, Precheck, Shape, VariantAttempt};
+                let mut value = <serde_json::Value as serde::Deserialize>::deserialize(deserializer)?;
+                let accepted = [$(<$variant_type as Precheck>::accepts(&value)),+];
+                // Rejections are only needed if no variant matches, by which time the value may be gone
+                let shape = accepted.contains(&false).then(|| Shape::of(&value));
+                let mut remaining = accepted.iter().filter(|&&accepted| accepted).count();
+                let mut variants = accepted.into_iter();
+                let mut errors = Vec::new();
+                $(
+                    if variants.next().expect("one precheck per variant") {
+                        remaining -= 1;
+                        // The last candidate takes the buffered value
+                        let candidate = match remaining {
+                            0 => std::mem::take(&mut value),
+                            _ => value.clone()
+                        };
+                        match attempt_owned::<$variant_type>(candidate) {
+                            Ok(deserialized) => return Ok($name::$variant(deserialized)),
+                            Err(error) => errors.push(error)
+                        }
+                    }
+                )+
+                let mut variants = accepted.into_iter();
+                let mut errors = errors.into_iter();
+                let attempts = vec![$(VariantAttempt {
+                    variant: stringify!($variant),
+                    error: match variants.next().expect("one precheck per variant") {
+                        true => errors.next().expect("one error per variant tried"),
+                        false => <$variant_type as Precheck>::rejection(shape.as_ref().expect("shape of a rejected value"))
+                    }
+                }),+];
+                Err(fail(DeserializeError::no_variant(stringify!($name), attempts)))
+            }
+        }
+    };
+}
+
+/// Implement [`Deserialize`](serde::Deserialize) for an enum tagged by `type`, whose variants hold a struct of the same name
+macro_rules! tagged_deserialize {
+    ($name:ident { $($variant:ident),+ $(,)? }) => {
+        impl<'de> serde::Deserialize<'de> for $name {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                use $crate::linkeddata::deserialize::{attempt_fields, fail, DeserializeError};
+                let value = <serde_json::Value as serde::Deserialize>::deserialize(deserializer)?;
+                match value.get("type").and_then(serde_json::Value::as_str) {
+                    $(
+                        Some(stringify!($variant)) => attempt_fields::<$variant>(&value).map($name::$variant).map_err(fail),
+                    )+
+                    _ => Err(fail(DeserializeError::unknown_type(stringify!($name), &value)))
+                }
+            }
+        }
+
+        impl $crate::linkeddata::deserialize::Precheck for $name {
+            fn accepts(value: &serde_json::Value) -> bool {
+                matches!(value.get("type").and_then(serde_json::Value::as_str), $(Some(stringify!($variant)))|+)
+            }
+
+            fn rejection(shape: &$crate::linkeddata::deserialize::Shape) -> $crate::linkeddata::deserialize::DeserializeError {
+                $crate::linkeddata::deserialize::DeserializeError::unknown_shape(stringify!($name), shape)
+            }
+        }
+    };
+}
+
+pub(crate) use {tagged_deserialize, untagged_deserialize};
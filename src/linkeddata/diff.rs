@@ -0,0 +1,261 @@
+//! Structural differences between two versions of an object, e.g. a stored Note and the one of an incoming Update
+//!
+//! Objects are compared as serialized, like [`SemanticEq`](crate::linkeddata::util::SemanticEq): A change of representation alone,
+//! such as a single value becoming an array of just that value, is no difference.
+//! Arrays of the same length are compared element by element; Otherwise the whole array is changed.
+
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use serde_json::{Map, Value};
+use crate::linkeddata::activity_streams::TaggedObject;
+use crate::linkeddata::deserialize::{self, DeserializeError};
+use crate::linkeddata::util::{equivalent_in, unfold};
+
+/// Properties that updates of an object should not change
+pub const IMMUTABLE_PROPERTIES: &[&str] = &["id", "type", "attributedTo"];
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PathSegment {
+    Property(String),
+    Index(usize),
+}
+
+/// Location of a value within an object, displayed as e.g. `$.attachment[1].url`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct JsonPath(pub Vec<PathSegment>);
+
+impl JsonPath {
+    fn child(&self, segment: PathSegment) -> JsonPath {
+        let mut path = self.clone();
+        path.0.push(segment);
+        path
+    }
+
+    /// The top-level property this path is within
+    pub fn property(&self) -> Option<&str> {
+        match self.0.first() {
+            Some(PathSegment::Property(property)) => Some(property),
+            _ => None
+        }
+    }
+
+    /// The innermost property this path is within
+    fn last_property(&self) -> Option<&str> {
+        self.0.iter().rev().find_map(|segment| match segment {
+            PathSegment::Property(property) => Some(property.as_str()),
+            PathSegment::Index(_) => None
+        })
+    }
+}
+
+impl Display for JsonPath {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "$")?;
+        for segment in &self.0 {
+            match segment {
+                PathSegment::Property(property) => write!(f, ".{}", property)?,
+                PathSegment::Index(index) => write!(f, "[{}]", index)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A difference at one path; Values are serialized, with single-element arrays unfolded
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    Added { path: JsonPath, value: Value },
+    Removed { path: JsonPath, value: Value },
+    Changed { path: JsonPath, from: Value, to: Value },
+}
+
+impl Change {
+    pub fn path(&self) -> &JsonPath {
+        match self {
+            Change::Added { path, .. } | Change::Removed { path, .. } | Change::Changed { path, .. } => path
+        }
+    }
+
+    /// The change undoing this one
+    pub fn inverse(&self) -> Change {
+        match self.clone() {
+            Change::Added { path, value } => Change::Removed { path, value },
+            Change::Removed { path, value } => Change::Added { path, value },
+            Change::Changed { path, from, to } => Change::Changed { path, from: to, to: from },
+        }
+    }
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Change::Added { path, value } => write!(f, "added {}: {}", path, value),
+            Change::Removed { path, value } => write!(f, "removed {}: {}", path, value),
+            Change::Changed { path, from, to } => write!(f, "changed {}: {} to {}", path, from, to),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum PatchError {
+    /// The object does not hold the value a change expects at this path
+    Conflict(JsonPath),
+    /// The object could not be serialized to be patched
+    Serialization(serde_json::Error),
+    Deserialization(DeserializeError),
+}
+
+impl Display for PatchError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PatchError::Conflict(path) => write!(f, "object does not match the patch at {}", path),
+            PatchError::Serialization(error) => write!(f, "object cannot be serialized: {}", error),
+            PatchError::Deserialization(error) => write!(f, "patched object is invalid: {}", error),
+        }
+    }
+}
+
+impl Error for PatchError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PatchError::Serialization(error) => Some(error),
+            PatchError::Deserialization(error) => Some(error),
+            _ => None
+        }
+    }
+}
+
+/// The changes from one version of an object to another, ordered by path
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Diff {
+    pub changes: Vec<Change>,
+}
+
+impl Diff {
+    /// The changes from `old` to `new`; Fails if either cannot be serialized
+    pub fn between(old: &TaggedObject, new: &TaggedObject) -> Result<Diff, serde_json::Error> {
+        Ok(Diff::between_values(&serde_json::to_value(old)?, &serde_json::to_value(new)?))
+    }
+
+    /// The changes between two serialized objects
+    pub fn between_values(old: &Value, new: &Value) -> Diff {
+        let mut diff = Diff::default();
+        diff.compare(JsonPath::default(), &unfold(old.clone()), &unfold(new.clone()));
+        diff
+    }
+
+    fn compare(&mut self, path: JsonPath, old: &Value, new: &Value) {
+        match (old, new) {
+            (Value::Object(old), Value::Object(new)) => {
+                for key in old.keys().chain(new.keys()).collect::<BTreeSet<_>>() {
+                    let path = path.child(PathSegment::Property(key.clone()));
+                    match (old.get(key), new.get(key)) {
+                        (Some(old), Some(new)) => self.compare(path, old, new),
+                        (Some(value), None) => self.changes.push(Change::Removed { path, value: value.clone() }),
+                        (None, Some(value)) => self.changes.push(Change::Added { path, value: value.clone() }),
+                        (None, None) => {}
+                    }
+                }
+            }
+            (Value::Array(old), Value::Array(new)) if old.len() == new.len() => {
+                for (index, (old, new)) in old.iter().zip(new).enumerate() {
+                    self.compare(path.child(PathSegment::Index(index)), old, new);
+                }
+            }
+            (old, new) if equivalent_in(path.last_property(), old, new) => {}
+            (old, new) => self.changes.push(Change::Changed { path, from: old.clone(), to: new.clone() }),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// The top-level properties changed, in order
+    pub fn properties(&self) -> Vec<&str> {
+        let mut properties = self.changes.iter().filter_map(|change| change.path().property()).collect::<Vec<_>>();
+        properties.dedup();
+        properties
+    }
+
+    /// Whether any of `properties` is changed, e.g. [`IMMUTABLE_PROPERTIES`]
+    pub fn changes_any(&self, properties: &[&str]) -> bool {
+        self.properties().iter().any(|property| properties.contains(property))
+    }
+
+    /// The diff undoing this one; Storing these with each version of an object keeps its edit history
+    pub fn inverse(&self) -> Diff {
+        Diff { changes: self.changes.iter().rev().map(Change::inverse).collect() }
+    }
+
+    /// Apply the changes to an object, which must hold the values they change from
+    pub fn apply(&self, object: &TaggedObject) -> Result<TaggedObject, PatchError> {
+        let mut value = serde_json::to_value(object).map_err(PatchError::Serialization)?;
+        self.apply_to_value(&mut value)?;
+        deserialize::from_value(&value).map_err(PatchError::Deserialization)
+    }
+
+    pub fn apply_to_value(&self, value: &mut Value) -> Result<(), PatchError> {
+        for change in &self.changes {
+            let conflict = || PatchError::Conflict(change.path().clone());
+            let holds = |value: &Value, expected: &Value| holds(change.path().last_property(), value, expected);
+            let Some((last, parent)) = change.path().0.split_last() else {
+                match change {
+                    Change::Changed { from, to, .. } if holds(value, from) => *value = to.clone(),
+                    _ => return Err(conflict())
+                }
+                continue;
+            };
+            let parent = parent.iter().try_fold(&mut *value, step).ok_or_else(conflict)?;
+            match (change, last) {
+                (Change::Changed { from, to, .. }, segment) => match step(parent, segment) {
+                    Some(target) if holds(target, from) => *target = to.clone(),
+                    _ => return Err(conflict())
+                },
+                (Change::Added { value, .. }, PathSegment::Property(key)) => {
+                    let properties = object(parent).ok_or_else(conflict)?;
+                    if properties.get(key).is_some_and(|existing| !holds(existing, &Value::Null)) {
+                        return Err(conflict());
+                    }
+                    properties.insert(key.clone(), value.clone());
+                }
+                (Change::Removed { value, .. }, PathSegment::Property(key)) => {
+                    let properties = object(parent).ok_or_else(conflict)?;
+                    match properties.get(key) {
+                        Some(existing) if holds(existing, value) => properties.remove(key),
+                        _ => return Err(conflict())
+                    };
+                }
+                _ => return Err(conflict())
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether a serialized value of `property` is equivalent to an unfolded one; Null stands for an absent value
+fn holds(property: Option<&str>, value: &Value, expected: &Value) -> bool {
+    match unfold(value.clone()) {
+        Value::Array(values) if values.is_empty() => expected.is_null(),
+        value => equivalent_in(property, &value, expected)
+    }
+}
+
+/// The properties of an object, or of the only element of an array
+fn object(value: &mut Value) -> Option<&mut Map<String, Value>> {
+    match value {
+        Value::Array(values) => match values.as_mut_slice() {
+            [value] => value.as_object_mut(),
+            _ => None
+        },
+        value => value.as_object_mut()
+    }
+}
+
+fn step<'v>(value: &'v mut Value, segment: &PathSegment) -> Option<&'v mut Value> {
+    match segment {
+        PathSegment::Property(key) => object(value)?.get_mut(key),
+        PathSegment::Index(index) => value.as_array_mut()?.get_mut(*index),
+    }
+}
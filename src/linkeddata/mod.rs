@@ -1,7 +1,14 @@
 use std::fmt::Debug;
 use serde::{Serialize, Deserialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+pub mod borrowed;
+pub mod deserialize;
+pub mod diff;
+pub mod rdf;
+pub mod signature;
+pub mod xsd;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct LinkedData<T: Debug, C: Debug> {
     #[serde(flatten)]
     pub data: T,
@@ -22,12 +29,14 @@ pub trait AsLinkedData: Sized + Debug {
     }
 }
 
+#[allow(clippy::large_enum_variant)]    // TaggedObject is stored inline; Indirection is left to the property type aliases
 pub mod activity_streams {
-    use serde::{Deserialize, Serialize};
+    use serde::Serialize;
     use crate::linkeddata::{marker_types};
     use crate::linkeddata::activity_streams::objects::*;
+    use crate::linkeddata::deserialize::{tagged_deserialize, untagged_deserialize};
 
-    #[derive(Debug, Serialize, Deserialize)]
+    #[derive(Debug, Clone, PartialEq, Serialize)]
     #[serde(untagged)]
     pub enum MaybeObject {
         URI(marker_types::URI),
@@ -35,20 +44,26 @@ pub mod activity_streams {
         TaggedObject(TaggedObject),
     }
 
-    #[derive(Debug, Serialize, Deserialize)]
+    untagged_deserialize!(MaybeObject { URI(marker_types::URI), TaggedLink(TaggedLink), TaggedObject(TaggedObject) });
+
+    #[derive(Debug, Clone, PartialEq, Serialize)]
     #[serde(untagged)]
     pub enum MaybeLink {
         URI(marker_types::URI),
         TaggedLink(TaggedLink),
     }
 
-    #[derive(Debug, Serialize, Deserialize)]
+    untagged_deserialize!(MaybeLink { URI(marker_types::URI), TaggedLink(TaggedLink) });
+
+    #[derive(Debug, Clone, PartialEq, Serialize)]
     #[serde(tag = "type")]
     pub enum TaggedLink {
         Link(Link)
     }
 
-    #[derive(Debug, Serialize, Deserialize)]
+    tagged_deserialize!(TaggedLink { Link });
+
+    #[derive(Debug, Clone, PartialEq, Serialize)]
     #[serde(tag = "type")]
     pub enum TaggedObject {
         Object(Object),
@@ -109,7 +124,172 @@ pub mod activity_streams {
         Tombstone(Tombstone),
     }
 
-    #[derive(Debug, Serialize, Deserialize)]
+    tagged_deserialize!(TaggedObject {
+        Object, Activity, Collection, OrderedCollection, CollectionPage, OrderedCollectionPage, Accept, TentativeAccept,
+        Add, Arrive, Create, Delete, Follow, Ignore, Join, Leave,
+        Like, Offer, Invite, Reject, TentativeReject, Remove, Undo, Update,
+        View, Listen, Read, Move, Travel, Announce, Block, Flag,
+        Dislike, Question, Application, Group, Organization, Person, Service, Relationship,
+        Article, Document, Audio, Image, Video, Note, Page, Event,
+        Place, Mention, Profile, Tombstone,
+    });
+
+    impl TaggedObject {
+        /// The Object properties of this object; `None` for Mention, which is a Link
+        pub fn object_fields(&self) -> Option<&Object> {
+            match self {
+                TaggedObject::Object(object) => Some(object),
+                TaggedObject::CollectionPage(page) => Some(&page.collection_fields.object_fields),
+                TaggedObject::OrderedCollectionPage(page) => Some(&page.collection_fields.object_fields),
+                TaggedObject::Collection(Collection { object_fields, .. }) |
+                TaggedObject::OrderedCollection(OrderedCollection { object_fields, .. }) |
+                TaggedObject::Relationship(Relationship { object_fields, .. }) |
+                TaggedObject::Place(Place { object_fields, .. }) |
+                TaggedObject::Profile(Profile { object_fields, .. }) |
+                TaggedObject::Tombstone(Tombstone { object_fields, .. }) => Some(object_fields),
+                TaggedObject::Application(Application(object)) |
+                TaggedObject::Group(Group(object)) |
+                TaggedObject::Organization(Organization(object)) |
+                TaggedObject::Person(Person(object)) |
+                TaggedObject::Service(Service(object)) |
+                TaggedObject::Article(Article(object)) |
+                TaggedObject::Document(Document(object)) |
+                TaggedObject::Audio(Audio(object)) |
+                TaggedObject::Image(Image(object)) |
+                TaggedObject::Video(Video(object)) |
+                TaggedObject::Note(Note(object)) |
+                TaggedObject::Page(Page(object)) |
+                TaggedObject::Event(Event(object)) => Some(object),
+                TaggedObject::Mention(_) => None,
+                activity => activity.activity_fields().map(|activity| &activity.object_fields)
+            }
+        }
+
+        pub fn object_fields_mut(&mut self) -> Option<&mut Object> {
+            match self {
+                TaggedObject::Object(object) => Some(object),
+                TaggedObject::CollectionPage(page) => Some(&mut page.collection_fields.object_fields),
+                TaggedObject::OrderedCollectionPage(page) => Some(&mut page.collection_fields.object_fields),
+                TaggedObject::Collection(Collection { object_fields, .. }) |
+                TaggedObject::OrderedCollection(OrderedCollection { object_fields, .. }) |
+                TaggedObject::Relationship(Relationship { object_fields, .. }) |
+                TaggedObject::Place(Place { object_fields, .. }) |
+                TaggedObject::Profile(Profile { object_fields, .. }) |
+                TaggedObject::Tombstone(Tombstone { object_fields, .. }) => Some(object_fields),
+                TaggedObject::Application(Application(object)) |
+                TaggedObject::Group(Group(object)) |
+                TaggedObject::Organization(Organization(object)) |
+                TaggedObject::Person(Person(object)) |
+                TaggedObject::Service(Service(object)) |
+                TaggedObject::Article(Article(object)) |
+                TaggedObject::Document(Document(object)) |
+                TaggedObject::Audio(Audio(object)) |
+                TaggedObject::Image(Image(object)) |
+                TaggedObject::Video(Video(object)) |
+                TaggedObject::Note(Note(object)) |
+                TaggedObject::Page(Page(object)) |
+                TaggedObject::Event(Event(object)) => Some(object),
+                TaggedObject::Mention(_) => None,
+                activity => activity.activity_fields_mut().map(|activity| &mut activity.object_fields)
+            }
+        }
+
+        /// The Activity properties of this object, if it is an activity
+        pub fn activity_fields(&self) -> Option<&Activity> {
+            match self {
+                TaggedObject::Activity(activity) => Some(activity),
+                TaggedObject::Question(question) => Some(&question.activity_fields),
+                TaggedObject::Accept(Accept(activity)) |
+                TaggedObject::TentativeAccept(TentativeAccept(activity)) |
+                TaggedObject::Add(Add(activity)) |
+                TaggedObject::Arrive(Arrive(activity)) |
+                TaggedObject::Create(Create(activity)) |
+                TaggedObject::Delete(Delete(activity)) |
+                TaggedObject::Follow(Follow(activity)) |
+                TaggedObject::Ignore(Ignore(activity)) |
+                TaggedObject::Join(Join(activity)) |
+                TaggedObject::Leave(Leave(activity)) |
+                TaggedObject::Like(Like(activity)) |
+                TaggedObject::Offer(Offer(activity)) |
+                TaggedObject::Invite(Invite(activity)) |
+                TaggedObject::Reject(Reject(activity)) |
+                TaggedObject::TentativeReject(TentativeReject(activity)) |
+                TaggedObject::Remove(Remove(activity)) |
+                TaggedObject::Undo(Undo(activity)) |
+                TaggedObject::Update(Update(activity)) |
+                TaggedObject::View(View(activity)) |
+                TaggedObject::Listen(Listen(activity)) |
+                TaggedObject::Read(Read(activity)) |
+                TaggedObject::Move(Move(activity)) |
+                TaggedObject::Travel(Travel(activity)) |
+                TaggedObject::Announce(Announce(activity)) |
+                TaggedObject::Block(Block(activity)) |
+                TaggedObject::Flag(Flag(activity)) |
+                TaggedObject::Dislike(Dislike(activity)) => Some(activity),
+                _ => None
+            }
+        }
+
+        pub fn activity_fields_mut(&mut self) -> Option<&mut Activity> {
+            match self {
+                TaggedObject::Activity(activity) => Some(activity),
+                TaggedObject::Question(question) => Some(&mut question.activity_fields),
+                TaggedObject::Accept(Accept(activity)) |
+                TaggedObject::TentativeAccept(TentativeAccept(activity)) |
+                TaggedObject::Add(Add(activity)) |
+                TaggedObject::Arrive(Arrive(activity)) |
+                TaggedObject::Create(Create(activity)) |
+                TaggedObject::Delete(Delete(activity)) |
+                TaggedObject::Follow(Follow(activity)) |
+                TaggedObject::Ignore(Ignore(activity)) |
+                TaggedObject::Join(Join(activity)) |
+                TaggedObject::Leave(Leave(activity)) |
+                TaggedObject::Like(Like(activity)) |
+                TaggedObject::Offer(Offer(activity)) |
+                TaggedObject::Invite(Invite(activity)) |
+                TaggedObject::Reject(Reject(activity)) |
+                TaggedObject::TentativeReject(TentativeReject(activity)) |
+                TaggedObject::Remove(Remove(activity)) |
+                TaggedObject::Undo(Undo(activity)) |
+                TaggedObject::Update(Update(activity)) |
+                TaggedObject::View(View(activity)) |
+                TaggedObject::Listen(Listen(activity)) |
+                TaggedObject::Read(Read(activity)) |
+                TaggedObject::Move(Move(activity)) |
+                TaggedObject::Travel(Travel(activity)) |
+                TaggedObject::Announce(Announce(activity)) |
+                TaggedObject::Block(Block(activity)) |
+                TaggedObject::Flag(Flag(activity)) |
+                TaggedObject::Dislike(Dislike(activity)) => Some(activity),
+                _ => None
+            }
+        }
+
+        /// Whether this is one of the actor types; Application, Group, Organization, Person or Service
+        pub fn is_actor(&self) -> bool {
+            matches!(self, TaggedObject::Application(_) | TaggedObject::Group(_) | TaggedObject::Organization(_) | TaggedObject::Person(_) | TaggedObject::Service(_))
+        }
+
+        pub fn id(&self) -> Option<&str> {
+            match self {
+                TaggedObject::Mention(Mention(link)) => link.id.as_deref(),
+                object => object.object_fields().and_then(|object| object.id.as_deref())
+            }
+        }
+    }
+
+    impl MaybeObject {
+        /// The URI of the referenced object; For links, this is the link's target
+        pub fn id(&self) -> Option<&str> {
+            match self {
+                MaybeObject::URI(uri) => Some(uri),
+                MaybeObject::TaggedLink(TaggedLink::Link(link)) => link.href.as_deref(),
+                MaybeObject::TaggedObject(object) => object.id()
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq, Serialize)]
     #[serde(untagged)]
     pub enum MaybeImage {
         URI(marker_types::URI),
@@ -117,309 +297,424 @@ pub mod activity_streams {
         TaggedImage(TaggedImage),
     }
 
-    #[derive(Debug, Serialize, Deserialize)]
+    untagged_deserialize!(MaybeImage { URI(marker_types::URI), TaggedLink(TaggedLink), TaggedImage(TaggedImage) });
+
+    #[derive(Debug, Clone, PartialEq, Serialize)]
     #[serde(tag = "type")]
     pub enum TaggedImage {
         Image(Image)
     }
 
+    tagged_deserialize!(TaggedImage { Image });
+
     #[allow(non_snake_case)]    // These structs are serialized; The names of their fields map directly to those in the emitted/received JSON
-    mod objects {
+    pub mod objects {
         use serde::{Deserialize, Serialize};
         use crate::linkeddata::activity_streams::properties::*;
+        use crate::linkeddata::deserialize::flattened;
+        use crate::linkeddata::util::is_omitted;
 
-        #[derive(Debug, Serialize, Deserialize)]
+        #[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
         pub struct Object {
-            attachment: Option<PropAttachment>,
-            attributedTo: Option<PropAttributedTo>,
-            audience: Option<PropAudience>,
-            content: Option<PropContent>,
-            contentMap: Option<PropContentMap>,
-            context: Option<PropContext>,
-            name: Option<PropName>,
-            nameMap: Option<PropNameMap>,
-            endTime: Option<PropEndTime>,
-            generator: Option<PropGenerator>,
-            icon: Option<PropIcon>,
-            image: Option<PropImage>,
-            inReplyTo: Option<PropInReplyTo>,
-            location: Option<PropLocation>,
-            preview: Option<PropPreview>,
-            published: Option<PropPublished>,
-            replies: Option<PropReplies>,
-            startTime: Option<PropStartTime>,
-            summary: Option<PropSummary>,
-            summaryMap: Option<PropSummaryMap>,
-            tag: Option<PropTag>,
-            updated: Option<PropUpdated>,
-            url: Option<PropUrl>,
-            to: Option<PropTo>,
-            bto: Option<PropBTO>,
-            cc: Option<PropCC>,
-            bcc: Option<PropBCC>,
-            mediaType: Option<PropMediaType>,
-            duration: Option<PropDuration>,
-        }
-
-        #[derive(Debug, Serialize, Deserialize)]
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub id: Option<PropId>,
+            #[serde(skip_serializing_if = "is_omitted")]
+            pub attachment: Option<PropAttachment>,
+            #[serde(skip_serializing_if = "is_omitted")]
+            pub attributedTo: Option<PropAttributedTo>,
+            #[serde(skip_serializing_if = "is_omitted")]
+            pub audience: Option<PropAudience>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub content: Option<PropContent>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub contentMap: Option<PropContentMap>,
+            #[serde(skip_serializing_if = "is_omitted")]
+            pub context: Option<PropContext>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub name: Option<PropName>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub nameMap: Option<PropNameMap>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub endTime: Option<PropEndTime>,
+            #[serde(skip_serializing_if = "is_omitted")]
+            pub generator: Option<PropGenerator>,
+            #[serde(skip_serializing_if = "is_omitted")]
+            pub icon: Option<PropIcon>,
+            #[serde(skip_serializing_if = "is_omitted")]
+            pub image: Option<PropImage>,
+            #[serde(skip_serializing_if = "is_omitted")]
+            pub inReplyTo: Option<PropInReplyTo>,
+            #[serde(skip_serializing_if = "is_omitted")]
+            pub location: Option<PropLocation>,
+            #[serde(skip_serializing_if = "is_omitted")]
+            pub preview: Option<PropPreview>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub published: Option<PropPublished>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub replies: Option<PropReplies>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub startTime: Option<PropStartTime>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub summary: Option<PropSummary>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub summaryMap: Option<PropSummaryMap>,
+            #[serde(skip_serializing_if = "is_omitted")]
+            pub tag: Option<PropTag>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub updated: Option<PropUpdated>,
+            #[serde(skip_serializing_if = "is_omitted")]
+            pub url: Option<PropUrl>,
+            #[serde(skip_serializing_if = "is_omitted")]
+            pub to: Option<PropTo>,
+            #[serde(skip_serializing_if = "is_omitted")]
+            pub bto: Option<PropBTO>,
+            #[serde(skip_serializing_if = "is_omitted")]
+            pub cc: Option<PropCC>,
+            #[serde(skip_serializing_if = "is_omitted")]
+            pub bcc: Option<PropBCC>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub mediaType: Option<PropMediaType>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub duration: Option<PropDuration>,
+        }
+
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
         pub struct Link {
-            href: Option<PropHref>,
-            rel: Option<PropRel>,
-            mediaType: Option<PropMediaType>,
-            name: Option<PropName>,
-            nameMap: Option<PropNameMap>,
-            hreflang: Option<PropHrefLang>,
-            height: Option<PropHeight>,
-            width: Option<PropWidth>,
-            preview: Option<PropPreview>,
-        }
-
-        #[derive(Debug, Serialize, Deserialize)]
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub id: Option<PropId>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub href: Option<PropHref>,
+            #[serde(skip_serializing_if = "is_omitted")]
+            pub rel: Option<PropRel>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub mediaType: Option<PropMediaType>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub name: Option<PropName>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub nameMap: Option<PropNameMap>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub hreflang: Option<PropHrefLang>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub height: Option<PropHeight>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub width: Option<PropWidth>,
+            #[serde(skip_serializing_if = "is_omitted")]
+            pub preview: Option<PropPreview>,
+        }
+
+        #[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
         pub struct Activity {
-            actor: Option<PropActor>,
-            object: Option<PropObject>,
-            target: Option<PropTarget>,
-            result: Option<PropResult>,
-            origin: Option<PropOrigin>,
-            instrument: Option<PropInstrument>,
-            #[serde(flatten)]
-            object_fields: Object,
+            #[serde(skip_serializing_if = "is_omitted")]
+            pub actor: Option<PropActor>,
+            #[serde(skip_serializing_if = "is_omitted")]
+            pub object: Option<PropObject>,
+            #[serde(skip_serializing_if = "is_omitted")]
+            pub target: Option<PropTarget>,
+            #[serde(skip_serializing_if = "is_omitted")]
+            pub result: Option<PropResult>,
+            #[serde(skip_serializing_if = "is_omitted")]
+            pub origin: Option<PropOrigin>,
+            #[serde(skip_serializing_if = "is_omitted")]
+            pub instrument: Option<PropInstrument>,
+            #[serde(flatten, deserialize_with = "flattened")]
+            pub object_fields: Object,
         }
 
-        #[derive(Debug, Serialize, Deserialize)]
-        pub struct IntransitiveActivity(Activity);
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        pub struct IntransitiveActivity(pub Activity);
 
-        #[derive(Debug, Serialize, Deserialize)]
+        #[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
         pub struct Collection {
-            totalItems: Option<PropTotalItems>,
-            current: Option<PropCurrent>,
-            first: Option<PropFirst>,
-            last: Option<PropLast>,
-            items: Option<PropItems>,
-            #[serde(flatten)]
-            object_fields: Object,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub totalItems: Option<PropTotalItems>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub current: Option<PropCurrent>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub first: Option<PropFirst>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub last: Option<PropLast>,
+            #[serde(skip_serializing_if = "is_omitted")]
+            pub items: Option<PropItems>,
+            #[serde(flatten, deserialize_with = "flattened")]
+            pub object_fields: Object,
         }
 
-        #[derive(Debug, Serialize, Deserialize)]
+        #[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+        #[serde(from = "ReceivedOrderedCollection")]
         pub struct OrderedCollection {
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub totalItems: Option<PropTotalItems>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub current: Option<PropCurrent>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub first: Option<PropFirst>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub last: Option<PropLast>,
+            #[serde(skip_serializing_if = "is_omitted")]
+            pub orderedItems: Option<PropOrderedItems>,
+            #[serde(flatten, deserialize_with = "flattened")]
+            pub object_fields: Object,
+        }
+
+        /// An OrderedCollection as received; `items` is accepted for leniency, but `orderedItems` takes precedence and is emitted
+        #[derive(Deserialize)]
+        struct ReceivedOrderedCollection {
             totalItems: Option<PropTotalItems>,
             current: Option<PropCurrent>,
             first: Option<PropFirst>,
             last: Option<PropLast>,
-            items: Option<PropItems>,
-            orderedItems: Option<PropItems>,
-            #[serde(flatten)]
+            orderedItems: Option<PropOrderedItems>,
+            items: Option<PropOrderedItems>,
+            #[serde(flatten, deserialize_with = "flattened")]
             object_fields: Object,
         }
 
-        #[derive(Debug, Serialize, Deserialize)]
+        impl From<ReceivedOrderedCollection> for OrderedCollection {
+            fn from(received: ReceivedOrderedCollection) -> Self {
+                OrderedCollection {
+                    totalItems: received.totalItems,
+                    current: received.current,
+                    first: received.first,
+                    last: received.last,
+                    orderedItems: received.orderedItems.or(received.items),
+                    object_fields: received.object_fields,
+                }
+            }
+        }
+
+        #[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
         pub struct CollectionPage {
-            partOf: Option<PropPartOf>,
-            next: Option<PropNext>,
-            prev: Option<PropPrev>,
-            #[serde(flatten)]
-            collection_fields: Collection,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub partOf: Option<PropPartOf>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub next: Option<PropNext>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub prev: Option<PropPrev>,
+            #[serde(flatten, deserialize_with = "flattened")]
+            pub collection_fields: Collection,
         }
 
-        #[derive(Debug, Serialize, Deserialize)]
+        #[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
         pub struct OrderedCollectionPage {
-            startIndex: Option<PropStartIndex>,
-            partOf: Option<PropPartOf>,
-            next: Option<PropNext>,
-            prev: Option<PropPrev>,
-            #[serde(flatten)]
-            collection_fields: OrderedCollection,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub startIndex: Option<PropStartIndex>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub partOf: Option<PropPartOf>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub next: Option<PropNext>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub prev: Option<PropPrev>,
+            #[serde(flatten, deserialize_with = "flattened")]
+            pub collection_fields: OrderedCollection,
         }
 
-        #[derive(Debug, Serialize, Deserialize)]
-        pub struct Accept(Activity);
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        pub struct Accept(pub Activity);
 
-        #[derive(Debug, Serialize, Deserialize)]
-        pub struct TentativeAccept(Activity);   // Inherits from accept, but this would result in another level of indirection, so inherit Activity directly
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        pub struct TentativeAccept(pub Activity);   // Inherits from accept, but this would result in another level of indirection, so inherit Activity directly
 
-        #[derive(Debug, Serialize, Deserialize)]
-        pub struct Add(Activity);
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        pub struct Add(pub Activity);
 
-        #[derive(Debug, Serialize, Deserialize)]
-        pub struct Arrive(Activity);    // Inherits from IntransitiveActivity
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        pub struct Arrive(pub Activity);    // Inherits from IntransitiveActivity
 
-        #[derive(Debug, Serialize, Deserialize)]
-        pub struct Create(Activity);
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        pub struct Create(pub Activity);
 
-        #[derive(Debug, Serialize, Deserialize)]
-        pub struct Delete(Activity);
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        pub struct Delete(pub Activity);
 
-        #[derive(Debug, Serialize, Deserialize)]
-        pub struct Follow(Activity);
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        pub struct Follow(pub Activity);
 
-        #[derive(Debug, Serialize, Deserialize)]
-        pub struct Ignore(Activity);
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        pub struct Ignore(pub Activity);
 
-        #[derive(Debug, Serialize, Deserialize)]
-        pub struct Join(Activity);
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        pub struct Join(pub Activity);
 
-        #[derive(Debug, Serialize, Deserialize)]
-        pub struct Leave(Activity);
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        pub struct Leave(pub Activity);
 
-        #[derive(Debug, Serialize, Deserialize)]
-        pub struct Like(Activity);
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        pub struct Like(pub Activity);
 
-        #[derive(Debug, Serialize, Deserialize)]
-        pub struct Offer(Activity);
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        pub struct Offer(pub Activity);
 
-        #[derive(Debug, Serialize, Deserialize)]
-        pub struct Invite(Activity);    // Inherits from Offer
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        pub struct Invite(pub Activity);    // Inherits from Offer
 
-        #[derive(Debug, Serialize, Deserialize)]
-        pub struct Reject(Activity);
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        pub struct Reject(pub Activity);
 
-        #[derive(Debug, Serialize, Deserialize)]
-        pub struct TentativeReject(Activity);   // Inherits from Reject
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        pub struct TentativeReject(pub Activity);   // Inherits from Reject
 
-        #[derive(Debug, Serialize, Deserialize)]
-        pub struct Remove(Activity);
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        pub struct Remove(pub Activity);
 
-        #[derive(Debug, Serialize, Deserialize)]
-        pub struct Undo(Activity);
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        pub struct Undo(pub Activity);
 
-        #[derive(Debug, Serialize, Deserialize)]
-        pub struct Update(Activity);
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        pub struct Update(pub Activity);
 
-        #[derive(Debug, Serialize, Deserialize)]
-        pub struct View(Activity);
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        pub struct View(pub Activity);
 
-        #[derive(Debug, Serialize, Deserialize)]
-        pub struct Listen(Activity);
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        pub struct Listen(pub Activity);
 
-        #[derive(Debug, Serialize, Deserialize)]
-        pub struct Read(Activity);
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        pub struct Read(pub Activity);
 
-        #[derive(Debug, Serialize, Deserialize)]
-        pub struct Move(Activity);
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        pub struct Move(pub Activity);
 
-        #[derive(Debug, Serialize, Deserialize)]
-        pub struct Travel(Activity);    // Inherits from IntransitiveActivity
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        pub struct Travel(pub Activity);    // Inherits from IntransitiveActivity
 
-        #[derive(Debug, Serialize, Deserialize)]
-        pub struct Announce(Activity);
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        pub struct Announce(pub Activity);
 
-        #[derive(Debug, Serialize, Deserialize)]
-        pub struct Block(Activity);     // Inherits from Ignore
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        pub struct Block(pub Activity);     // Inherits from Ignore
 
-        #[derive(Debug, Serialize, Deserialize)]
-        pub struct Flag(Activity);
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        pub struct Flag(pub Activity);
 
-        #[derive(Debug, Serialize, Deserialize)]
-        pub struct Dislike(Activity);
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        pub struct Dislike(pub Activity);
 
-        #[derive(Debug, Serialize, Deserialize)]
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
         pub struct Question {
-            oneOf: Option<PropOneOf>,
-            anyOf: Option<PropAnyOf>,
-            closed: Option<PropClosed>,
-            #[serde(flatten)]
-            activity_fields: Activity,   // Inherits from IntransitiveActivity
+            #[serde(skip_serializing_if = "is_omitted")]
+            pub oneOf: Option<PropOneOf>,
+            #[serde(skip_serializing_if = "is_omitted")]
+            pub anyOf: Option<PropAnyOf>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub closed: Option<PropClosed>,
+            #[serde(flatten, deserialize_with = "flattened")]
+            pub activity_fields: Activity,   // Inherits from IntransitiveActivity
         }
 
-        #[derive(Debug, Serialize, Deserialize)]
-        pub struct Application(Object);
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        pub struct Application(pub Object);
 
-        #[derive(Debug, Serialize, Deserialize)]
-        pub struct Group(Object);
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        pub struct Group(pub Object);
 
-        #[derive(Debug, Serialize, Deserialize)]
-        pub struct Organization(Object);
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        pub struct Organization(pub Object);
 
-        #[derive(Debug, Serialize, Deserialize)]
-        pub struct Person(Object);
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        pub struct Person(pub Object);
 
-        #[derive(Debug, Serialize, Deserialize)]
-        pub struct Service(Object);
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        pub struct Service(pub Object);
 
-        #[derive(Debug, Serialize, Deserialize)]
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
         pub struct Relationship {
-            subject: Option<PropSubject>,
-            object: Option<PropObject>,
-            relationship: Option<PropRelationship>,
-            #[serde(flatten)]
-            object_fields: Object,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub subject: Option<PropSubject>,
+            #[serde(skip_serializing_if = "is_omitted")]
+            pub object: Option<PropObject>,
+            #[serde(skip_serializing_if = "is_omitted")]
+            pub relationship: Option<PropRelationship>,
+            #[serde(flatten, deserialize_with = "flattened")]
+            pub object_fields: Object,
         }
 
-        #[derive(Debug, Serialize, Deserialize)]
-        pub struct Article(Object);
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        pub struct Article(pub Object);
 
-        #[derive(Debug, Serialize, Deserialize)]
-        pub struct Document(Object);
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        pub struct Document(pub Object);
 
-        #[derive(Debug, Serialize, Deserialize)]
-        pub struct Audio(Object);    // Inherits from Document
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        pub struct Audio(pub Object);    // Inherits from Document
 
-        #[derive(Debug, Serialize, Deserialize)]
-        pub struct Image(Object);    // Inherits from Document
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        pub struct Image(pub Object);    // Inherits from Document
 
-        #[derive(Debug, Serialize, Deserialize)]
-        pub struct Video(Object);    // Inherits from Document
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        pub struct Video(pub Object);    // Inherits from Document
 
-        #[derive(Debug, Serialize, Deserialize)]
-        pub struct Note(Object);    // Inherits from Document
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        pub struct Note(pub Object);    // Inherits from Document
 
-        #[derive(Debug, Serialize, Deserialize)]
-        pub struct Page(Object);    // Inherits from Document
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        pub struct Page(pub Object);    // Inherits from Document
 
-        #[derive(Debug, Serialize, Deserialize)]
-        pub struct Event(Object);
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        pub struct Event(pub Object);
 
-        #[derive(Debug, Serialize, Deserialize)]
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
         pub struct Place {
-            accuracy: Option<PropAccuracy>,
-            altitude: Option<PropAltitude>,
-            latitude: Option<PropLatitude>,
-            longitude: Option<PropLongitude>,
-            radius: Option<PropRadius>,
-            units: Option<PropUnits>,
-            #[serde(flatten)]
-            object_fields: Object,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub accuracy: Option<PropAccuracy>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub altitude: Option<PropAltitude>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub latitude: Option<PropLatitude>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub longitude: Option<PropLongitude>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub radius: Option<PropRadius>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub units: Option<PropUnits>,
+            #[serde(flatten, deserialize_with = "flattened")]
+            pub object_fields: Object,
         }
 
-        #[derive(Debug, Serialize, Deserialize)]
-        pub struct Mention(Link);
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        pub struct Mention(pub Link);
 
-        #[derive(Debug, Serialize, Deserialize)]
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
         pub struct Profile {
-            describes: Option<PropDescribes>,
-            #[serde(flatten)]
-            object_fields: Object,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub describes: Option<PropDescribes>,
+            #[serde(flatten, deserialize_with = "flattened")]
+            pub object_fields: Object,
         }
 
-        #[derive(Debug, Serialize, Deserialize)]
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
         pub struct Tombstone {
-            formerType: Option<PropFormerType>,
-            deleted: Option<PropDeleted>,
-            #[serde(flatten)]
-            object_fields: Object,
+            #[serde(skip_serializing_if = "is_omitted")]
+            pub formerType: Option<PropFormerType>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub deleted: Option<PropDeleted>,
+            #[serde(flatten, deserialize_with = "flattened")]
+            pub object_fields: Object,
         }
     }
 
-    mod properties {
+    pub mod properties {
         use serde::{Deserialize, Serialize};
-        use chrono::{DateTime, FixedOffset};
         use crate::linkeddata::activity_streams::{MaybeImage, MaybeLink, MaybeObject, TaggedLink, TaggedObject};
         use crate::linkeddata::activity_streams::objects::*;
         use crate::linkeddata::util::FoldedSlice;
         use crate::linkeddata::marker_types;
         use crate::linkeddata::marker_types::{Duration, LocalizedString, RadiusAltitudeUnit, RFC5988};
+        use crate::linkeddata::deserialize::{tagged_deserialize, untagged_deserialize};
+        use crate::linkeddata::xsd::XsdDateTime;
 
-        #[derive(Debug, Serialize, Deserialize)]
+        #[derive(Debug, Clone, PartialEq, Serialize)]
         #[serde(untagged)]
         pub enum MaybeClosed {
             URI(marker_types::URI),
             TaggedLink(TaggedLink),
             TaggedObject(TaggedObject),
-            Date(DateTime<FixedOffset>),
+            Date(XsdDateTime),
             Bool(bool),
         }
 
-        #[derive(Debug, Serialize, Deserialize)]
+        untagged_deserialize!(MaybeClosed { URI(marker_types::URI), TaggedLink(TaggedLink), TaggedObject(TaggedObject), Date(XsdDateTime), Bool(bool) });
+
+        #[derive(Debug, Clone, PartialEq, Serialize)]
         #[serde(untagged)]
         pub enum MaybeCollection {
             URI(marker_types::URI),
@@ -427,7 +722,9 @@ pub mod activity_streams {
             TaggedCollection(TaggedCollection),
         }
 
-        #[derive(Debug, Serialize, Deserialize)]
+        untagged_deserialize!(MaybeCollection { URI(marker_types::URI), TaggedLink(TaggedLink), TaggedCollection(TaggedCollection) });
+
+        #[derive(Debug, Clone, PartialEq, Serialize)]
         #[serde(tag = "type")]
         pub enum TaggedCollection {
             Collection(Collection),
@@ -437,7 +734,9 @@ pub mod activity_streams {
             OrderedCollectionPage(OrderedCollectionPage),
         }
 
-        #[derive(Debug, Serialize, Deserialize)]
+        tagged_deserialize!(TaggedCollection { Collection, OrderedCollection, CollectionPage, OrderedCollectionPage });
+
+        #[derive(Debug, Clone, PartialEq, Serialize)]
         #[serde(untagged)]
         pub enum MaybeCollectionPage {
             URI(marker_types::URI),
@@ -445,13 +744,89 @@ pub mod activity_streams {
             Tagged(TaggedCollectionPage),
         }
 
-        #[derive(Debug, Serialize, Deserialize)]
+        untagged_deserialize!(MaybeCollectionPage { URI(marker_types::URI), TaggedLink(TaggedLink), Tagged(TaggedCollectionPage) });
+
+        #[derive(Debug, Clone, PartialEq, Serialize)]
         #[serde(tag = "type")]
         pub enum TaggedCollectionPage {
             CollectionPage(CollectionPage),
             OrderedCollectionPage(OrderedCollectionPage),
         }
 
+        tagged_deserialize!(TaggedCollectionPage { CollectionPage, OrderedCollectionPage });
+
+        impl TaggedCollection {
+            /// The inline items of this collection, in order if [`TaggedCollection::is_ordered`]
+            pub fn items(&self) -> Option<&[MaybeObject]> {
+                match self {
+                    TaggedCollection::Collection(collection) => collection.items.as_deref(),
+                    TaggedCollection::OrderedCollection(collection) => collection.orderedItems.as_deref(),
+                    TaggedCollection::CollectionPage(page) => page.collection_fields.items.as_deref(),
+                    TaggedCollection::OrderedCollectionPage(page) => page.collection_fields.orderedItems.as_deref(),
+                }
+            }
+
+            /// The inline items of this collection if their order is meaningful
+            pub fn ordered_items(&self) -> Option<&[MaybeObject]> {
+                self.is_ordered().then(|| self.items()).flatten()
+            }
+
+            pub fn is_ordered(&self) -> bool {
+                matches!(self, TaggedCollection::OrderedCollection(_) | TaggedCollection::OrderedCollectionPage(_))
+            }
+        }
+
+        impl TaggedCollectionPage {
+            /// The inline items of this page, in order if [`TaggedCollectionPage::is_ordered`]
+            pub fn items(&self) -> Option<&[MaybeObject]> {
+                match self {
+                    TaggedCollectionPage::CollectionPage(page) => page.collection_fields.items.as_deref(),
+                    TaggedCollectionPage::OrderedCollectionPage(page) => page.collection_fields.orderedItems.as_deref(),
+                }
+            }
+
+            /// The inline items of this page if their order is meaningful
+            pub fn ordered_items(&self) -> Option<&[MaybeObject]> {
+                self.is_ordered().then(|| self.items()).flatten()
+            }
+
+            pub fn is_ordered(&self) -> bool {
+                matches!(self, TaggedCollectionPage::OrderedCollectionPage(_))
+            }
+        }
+
+        /// Compare and hash a wrapper of a float that is never NaN by its value; `-0` equals `+0`
+        macro_rules! float_ordering {
+            ($name:ident) => {
+                impl PartialEq for $name {
+                    fn eq(&self, other: &Self) -> bool {
+                        self.value == other.value
+                    }
+                }
+
+                impl Eq for $name {}
+
+                impl PartialOrd for $name {
+                    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+                        Some(self.cmp(other))
+                    }
+                }
+
+                impl Ord for $name {
+                    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+                        self.value.partial_cmp(&other.value).expect("values are never NaN")
+                    }
+                }
+
+                impl std::hash::Hash for $name {
+                    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+                        // Adding +0 turns -0 into +0
+                        (self.value + 0.0).to_bits().hash(state);
+                    }
+                }
+            };
+        }
+
         #[derive(Copy, Clone, Debug, Serialize, Deserialize)]
         #[serde(try_from = "f64")]
         pub struct Percentage {
@@ -464,12 +839,14 @@ pub mod activity_streams {
             }
         }
 
+        float_ordering!(Percentage);
+
         impl TryFrom<f64> for Percentage {
             type Error = &'static str;
 
             fn try_from(value: f64) -> Result<Self, Self::Error> {
                 match value {
-                    value if value >= 0.0 && value <= 100.0 => Ok(Percentage { value }),
+                    value if (0.0..=100.0).contains(&value) => Ok(Percentage { value }),
                     _ => Err("Percentage must be +0 <= X <= +100")
                 }
             }
@@ -487,18 +864,21 @@ pub mod activity_streams {
             }
         }
 
+        float_ordering!(PositiveFloat);
+
         impl TryFrom<f64> for PositiveFloat {
             type Error = &'static str;
 
             fn try_from(value: f64) -> Result<Self, Self::Error> {
                 match value {
-                    value if value.is_sign_positive() => Ok(PositiveFloat { value }),
+                    value if value.is_sign_positive() && !value.is_nan() => Ok(PositiveFloat { value }),
                     _ => Err("Positive float must be >= +0")
                 }
             }
         }
 
-        // ID and Type attribute are handled by LinkedData struct and ActivityStreamType Enum Variants respectively
+        // Type attribute is handled by the TaggedObject/TaggedLink enum variants
+        pub type PropId = marker_types::URI;
         pub type PropActor = FoldedSlice<MaybeObject>;
         pub type PropAttachment = FoldedSlice<MaybeObject>;
         pub type PropAttributedTo = FoldedSlice<MaybeObject>;
@@ -517,6 +897,7 @@ pub mod activity_streams {
         pub type PropLast = Box<MaybeCollectionPage>;
         pub type PropLocation = FoldedSlice<MaybeObject>;
         pub type PropItems = FoldedSlice<MaybeObject>;
+        pub type PropOrderedItems = FoldedSlice<MaybeObject>;
         pub type PropOneOf = FoldedSlice<MaybeObject>;
         pub type PropAnyOf = FoldedSlice<MaybeObject>;
         pub type PropClosed = Box<MaybeClosed>;
@@ -545,9 +926,9 @@ pub mod activity_streams {
         pub type PropLatitude = f64;
         pub type PropLongitude = f64;
         pub type PropMediaType = marker_types::MIMEType;
-        pub type PropEndTime = DateTime<FixedOffset>;
-        pub type PropPublished = DateTime<FixedOffset>;
-        pub type PropStartTime = DateTime<FixedOffset>;
+        pub type PropEndTime = XsdDateTime;
+        pub type PropPublished = XsdDateTime;
+        pub type PropStartTime = XsdDateTime;
         pub type PropRadius = PositiveFloat;
         pub type PropRel = FoldedSlice<RFC5988>;
         pub type PropStartIndex = u64;
@@ -555,17 +936,18 @@ pub mod activity_streams {
         pub type PropSummaryMap = LocalizedString;
         pub type PropTotalItems = u64;
         pub type PropUnits = RadiusAltitudeUnit;
-        pub type PropUpdated = DateTime<FixedOffset>;
+        pub type PropUpdated = XsdDateTime;
         pub type PropWidth = u64;
         pub type PropSubject = Box<MaybeObject>;
         pub type PropRelationship = FoldedSlice<TaggedObject>;
         pub type PropDescribes = Box<TaggedObject>;
         pub type PropFormerType = FoldedSlice<TaggedObject>;
-        pub type PropDeleted = DateTime<FixedOffset>;
+        pub type PropDeleted = XsdDateTime;
     }
 }
 
-mod marker_types {
+/// Strings of the vocabulary are owned; [`borrowed`] has views of received documents that borrow URIs and content from the input instead
+pub mod marker_types {
     use std::collections::HashMap;
 
     pub type URI = String;
@@ -578,36 +960,322 @@ mod marker_types {
 }
 
 pub mod util {
-    use std::ops::Deref;
-    use serde::{Serialize, Deserialize};
+    use std::fmt::Formatter;
+    use std::hash::{Hash, Hasher};
+    use std::marker::PhantomData;
+    use std::ops::{Deref, DerefMut};
+    use serde::{Serialize, Serializer, Deserialize, Deserializer};
+    use serde::de::{IntoDeserializer, MapAccess, SeqAccess, Visitor};
+    use serde::de::value::{BorrowedStrDeserializer, MapAccessDeserializer};
+    use serde_json::Value;
+    use crate::linkeddata::deserialize::{collect, fail, is_lenient, take_failure, warn, DeserializeError};
+    use crate::linkeddata::xsd::XsdDateTime;
+    use crate::linkeddata::activity_streams::{MaybeImage, MaybeLink, MaybeObject, TaggedImage, TaggedLink, TaggedObject};
+    use crate::linkeddata::activity_streams::objects::*;
+    use crate::linkeddata::activity_streams::properties::{MaybeClosed, MaybeCollection, MaybeCollectionPage, TaggedCollection, TaggedCollectionPage};
+
+    /// How an empty [`FoldedSlice`] property is serialized
+    #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+    pub enum WhenEmpty {
+        /// As `[]`
+        #[default]
+        Array,
+        /// Not at all, as if the property were absent; Only applies to properties skipped by [`is_omitted`]
+        Omit,
+    }
 
-    #[derive(Debug, Serialize, Deserialize)]
-    #[serde(untagged)]
-    pub enum FoldedSlice<T> {
-        One(Box<T>),
-        Many(Box<[T]>),
+    /// One value or many; Serialized as the bare value or as an array, as it was deserialized
+    ///
+    /// Values are held in a `Vec` either way, so a single value is not boxed on its own. Adding values makes a single value an array.
+    #[derive(Debug, Clone)]
+    pub struct FoldedSlice<T> {
+        values: Vec<T>,
+        one: bool,
+        when_empty: WhenEmpty,
+    }
+
+    /// No values; Derived, this would require `T: Default`
+    impl<T> Default for FoldedSlice<T> {
+        fn default() -> Self {
+            FoldedSlice { values: Vec::new(), one: false, when_empty: WhenEmpty::Array }
+        }
+    }
+
+    impl<T> FoldedSlice<T> {
+        /// Values that serialize as an array, even if there is only one
+        pub fn many(values: Vec<T>) -> Self {
+            FoldedSlice { values, one: false, when_empty: WhenEmpty::Array }
+        }
+
+        /// Whether this is a single value rather than an array
+        pub fn is_one(&self) -> bool {
+            self.one && self.values.len() == 1
+        }
+
+        pub fn when_empty(&self) -> WhenEmpty {
+            self.when_empty
+        }
+
+        pub fn set_when_empty(&mut self, when_empty: WhenEmpty) {
+            self.when_empty = when_empty;
+        }
+
+        /// Add a value; A single value becomes an array
+        pub fn push(&mut self, value: T) {
+            self.one = false;
+            self.values.push(value);
+        }
+
+        /// Keep only the values for which `keep` returns true
+        pub fn retain(&mut self, keep: impl FnMut(&T) -> bool) {
+            self.values.retain(keep);
+        }
+
+        fn omitted(&self) -> bool {
+            self.values.is_empty() && self.when_empty == WhenEmpty::Omit
+        }
+
+        pub fn into_vec(self) -> Vec<T> {
+            self.values
+        }
+    }
+
+    /// Equality of what values mean rather than how they are represented
+    ///
+    /// Values are compared as serialized: A single value equals an array of just that value, an empty array equals an absent property,
+    /// maps such as `contentMap` compare regardless of key order, and [`DATE_PROPERTIES`] compare by the instant they denote. Arrays are otherwise compared in order.
+    pub trait SemanticEq: Serialize {
+        fn semantic_eq(&self, other: &Self) -> bool {
+            match (serde_json::to_value(self), serde_json::to_value(other)) {
+                (Ok(a), Ok(b)) => equivalent(&unfold(a), &unfold(b)),
+                _ => false
+            }
+        }
+    }
+
+    macro_rules! semantic_eq {
+        ($($name:ident),* $(,)?) => {
+            $(impl SemanticEq for $name {})*
+        };
+    }
+
+    semantic_eq!(
+        MaybeObject, MaybeLink, TaggedLink, TaggedObject, MaybeImage, TaggedImage, MaybeClosed, MaybeCollection, TaggedCollection,
+        MaybeCollectionPage, TaggedCollectionPage, Object, Link, Activity, IntransitiveActivity, Collection, OrderedCollection, CollectionPage,
+        OrderedCollectionPage, Accept, TentativeAccept, Add, Arrive, Create, Delete, Follow, Ignore, Join, Leave, Like, Offer, Invite, Reject,
+        TentativeReject, Remove, Undo, Update, View, Listen, Read, Move, Travel, Announce, Block, Flag, Dislike, Question, Application, Group,
+        Organization, Person, Service, Relationship, Article, Document, Audio, Image, Video, Note, Page, Event, Place, Mention, Profile, Tombstone,
+    );
+
+    impl<T: Serialize> SemanticEq for FoldedSlice<T> {}
+
+    /// Replace single-element arrays by their element, and drop properties that are null or empty arrays
+    pub(crate) fn unfold(value: Value) -> Value {
+        match value {
+            Value::Array(values) => {
+                let mut values = values.into_iter().map(unfold).collect::<Vec<_>>();
+                match values.len() {
+                    1 => values.pop().expect("one value"),
+                    _ => Value::Array(values)
+                }
+            }
+            Value::Object(properties) => Value::Object(properties.into_iter()
+                .map(|(key, value)| (key, unfold(value)))
+                .filter(|(_, value)| !value.is_null() && value.as_array().is_none_or(|values| !values.is_empty()))
+                .collect()),
+            value => value
+        }
+    }
+
+    /// Properties whose values are `xsd:dateTime`s
+    pub const DATE_PROPERTIES: &[&str] = &["closed", "deleted", "endTime", "published", "startTime", "updated"];
+
+    pub(crate) fn equivalent(a: &Value, b: &Value) -> bool {
+        equivalent_in(None, a, b)
+    }
+
+    /// Whether two values of `property` are equivalent; Strings compare as dates only within [`DATE_PROPERTIES`]
+    pub(crate) fn equivalent_in(property: Option<&str>, a: &Value, b: &Value) -> bool {
+        match (a, b) {
+            (Value::Array(a), Value::Array(b)) => a.len() == b.len() && a.iter().zip(b).all(|(a, b)| equivalent_in(property, a, b)),
+            (Value::Object(a), Value::Object(b)) => a.len() == b.len() && a.iter().all(|(key, a)| b.get(key).is_some_and(|b| equivalent_in(Some(key), a, b))),
+            (Value::String(a), Value::String(b)) if a != b => property.is_some_and(|property| DATE_PROPERTIES.contains(&property))
+                && matches!((a.parse::<XsdDateTime>(), b.parse::<XsdDateTime>()), (Ok(a), Ok(b)) if a == b),
+            (a, b) => a == b
+        }
+    }
+
+    /// Whether a property is skipped when serializing: If absent, or empty and set to [`WhenEmpty::Omit`]
+    pub fn is_omitted<T>(values: &Option<FoldedSlice<T>>) -> bool {
+        values.as_ref().is_none_or(FoldedSlice::omitted)
+    }
+
+    impl<T: Serialize> Serialize for FoldedSlice<T> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            match &*self.values {
+                [one] if self.one => one.serialize(serializer),
+                many => many.serialize(serializer)
+            }
+        }
+    }
+
+    /// An array is many values, anything else is one; Failures are located at the offending element, which is dropped in lenient mode
+    impl<'de, T: Deserialize<'de>> Deserialize<'de> for FoldedSlice<T> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            deserializer.deserialize_any(FoldedSliceVisitor(PhantomData))
+        }
+    }
+
+    /// Deserializes arrays element by element and forwards anything else to `T`, so that nothing is buffered or copied
+    struct FoldedSliceVisitor<T>(PhantomData<T>);
+
+    impl<T> FoldedSliceVisitor<T> {
+        fn one<'de, D: Deserializer<'de>>(deserializer: D) -> Result<FoldedSlice<T>, D::Error> where T: Deserialize<'de> {
+            T::deserialize(deserializer).map(FoldedSlice::from)
+        }
+    }
+
+    impl<'de, T: Deserialize<'de>> Visitor<'de> for FoldedSliceVisitor<T> {
+        type Value = FoldedSlice<T>;
+
+        fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
+            formatter.write_str("a value or an array of values")
+        }
+
+        fn visit_bool<E: serde::de::Error>(self, v: bool) -> Result<Self::Value, E> {
+            Self::one(v.into_deserializer())
+        }
+
+        fn visit_i64<E: serde::de::Error>(self, v: i64) -> Result<Self::Value, E> {
+            Self::one(v.into_deserializer())
+        }
+
+        fn visit_u64<E: serde::de::Error>(self, v: u64) -> Result<Self::Value, E> {
+            Self::one(v.into_deserializer())
+        }
+
+        fn visit_f64<E: serde::de::Error>(self, v: f64) -> Result<Self::Value, E> {
+            Self::one(v.into_deserializer())
+        }
+
+        fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Self::Value, E> {
+            Self::one(v.into_deserializer())
+        }
+
+        fn visit_borrowed_str<E: serde::de::Error>(self, v: &'de str) -> Result<Self::Value, E> {
+            Self::one(BorrowedStrDeserializer::new(v))
+        }
+
+        fn visit_string<E: serde::de::Error>(self, v: String) -> Result<Self::Value, E> {
+            Self::one(v.into_deserializer())
+        }
+
+        fn visit_unit<E: serde::de::Error>(self) -> Result<Self::Value, E> {
+            Self::one(().into_deserializer())
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+            Self::one(MapAccessDeserializer::new(map))
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut many = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+            for index in 0.. {
+                let segment = format!("[{}]", index);
+                take_failure();
+                let (result, warnings) = collect(|| seq.next_element::<T>());
+                warnings.into_iter().for_each(|warning| warn(warning.prefixed(&segment)));
+                let error = match result {
+                    Ok(Some(value)) => {
+                        many.push(value);
+                        continue;
+                    }
+                    Ok(None) => break,
+                    Err(error) => take_failure().unwrap_or_else(|| DeserializeError::leaf(error)).prefixed(&segment)
+                };
+                // Elements are taken whole from buffered input, which lenient mode always deserializes from, so the rest can still be read
+                match is_lenient() {
+                    true => warn(error),
+                    false => return Err(fail(error))
+                }
+            }
+            Ok(FoldedSlice::many(many))
+        }
     }
 
     impl<T> From<T> for FoldedSlice<T> {
         fn from(value: T) -> Self {
-            FoldedSlice::One(Box::new(value))
+            FoldedSlice { values: vec![value], one: true, when_empty: WhenEmpty::Array }
         }
     }
 
     impl<T> From<Vec<T>> for FoldedSlice<T> {
-        fn from(many: Vec<T>) -> Self {
-            if many.len() == 1 {
-                // This re-allocates
-                let value = many.into_iter().next().unwrap();
-                FoldedSlice::One(Box::new(value))
-            } else {
-                FoldedSlice::Many(many.into_boxed_slice())
-            }
-            // Reallocation-free implementation
-            // match <Box<[T; 1]>>::try_from(many.into_boxed_slice()) {
-            //     Ok(single) => FoldedSlice::One(unsafe { Box::from_raw(Box::into_raw(single).cast::<T>()) }),
-            //     Err(many) => FoldedSlice::Many(many)
-            // }
+        fn from(values: Vec<T>) -> Self {
+            FoldedSlice { one: values.len() == 1, values, when_empty: WhenEmpty::Array }
+        }
+    }
+
+    impl<T> From<FoldedSlice<T>> for Vec<T> {
+        fn from(folded: FoldedSlice<T>) -> Self {
+            folded.values
+        }
+    }
+
+    impl<T> FromIterator<T> for FoldedSlice<T> {
+        fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+            FoldedSlice::from(iter.into_iter().collect::<Vec<_>>())
+        }
+    }
+
+    impl<T> Extend<T> for FoldedSlice<T> {
+        fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+            let len = self.values.len();
+            self.values.extend(iter);
+            self.one &= self.values.len() == len;
+        }
+    }
+
+    impl<T> IntoIterator for FoldedSlice<T> {
+        type Item = T;
+        type IntoIter = std::vec::IntoIter<T>;
+
+        fn into_iter(self) -> Self::IntoIter {
+            self.values.into_iter()
+        }
+    }
+
+    impl<'a, T> IntoIterator for &'a FoldedSlice<T> {
+        type Item = &'a T;
+        type IntoIter = std::slice::Iter<'a, T>;
+
+        fn into_iter(self) -> Self::IntoIter {
+            self.values.iter()
+        }
+    }
+
+    impl<'a, T> IntoIterator for &'a mut FoldedSlice<T> {
+        type Item = &'a mut T;
+        type IntoIter = std::slice::IterMut<'a, T>;
+
+        fn into_iter(self) -> Self::IntoIter {
+            self.values.iter_mut()
+        }
+    }
+
+    /// Values are equal if they serialize the same
+    impl<T: PartialEq> PartialEq for FoldedSlice<T> {
+        fn eq(&self, other: &Self) -> bool {
+            self.values == other.values && self.is_one() == other.is_one() && self.omitted() == other.omitted()
+        }
+    }
+
+    impl<T: Eq> Eq for FoldedSlice<T> {}
+
+    impl<T: Hash> Hash for FoldedSlice<T> {
+        fn hash<H: Hasher>(&self, state: &mut H) {
+            self.values.hash(state);
+            self.is_one().hash(state);
+            self.omitted().hash(state);
         }
     }
 
@@ -615,10 +1283,13 @@ pub mod util {
         type Target = [T];
 
         fn deref(&self) -> &Self::Target {
-            match self {
-                FoldedSlice::One(one) => std::slice::from_ref(&**one),
-                FoldedSlice::Many(many) => &**many
-            }
+            &self.values
+        }
+    }
+
+    impl<T> DerefMut for FoldedSlice<T> {
+        fn deref_mut(&mut self) -> &mut Self::Target {
+            &mut self.values
         }
     }
-}
\ No newline at end of file
+}
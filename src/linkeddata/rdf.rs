@@ -0,0 +1,1279 @@
+//! Conversion of JSON-LD documents to RDF, and canonicalization of the resulting dataset (RDFC-1.0, formerly URDNA2015)
+//!
+//! Only the subset of JSON-LD 1.0 that is in use by ActivityStreams documents is implemented; @base and relative IRIs are not supported, and properties that do not expand to an absolute IRI are dropped.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+use std::sync::OnceLock;
+use serde::Serialize;
+use serde_json::Value;
+use crate::linkeddata::LinkedData;
+
+pub const ACTIVITYSTREAMS_CONTEXT: &str = "https://www.w3.org/ns/activitystreams";
+pub const SECURITY_V1_CONTEXT: &str = "https://w3id.org/security/v1";
+pub const IDENTITY_V1_CONTEXT: &str = "https://w3id.org/identity/v1";
+
+const RDF_TYPE: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#type";
+const RDF_FIRST: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#first";
+const RDF_REST: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#rest";
+const RDF_NIL: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#nil";
+const RDF_LANG_STRING: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#langString";
+const XSD_BOOLEAN: &str = "http://www.w3.org/2001/XMLSchema#boolean";
+const XSD_DOUBLE: &str = "http://www.w3.org/2001/XMLSchema#double";
+const XSD_INTEGER: &str = "http://www.w3.org/2001/XMLSchema#integer";
+const XSD_STRING: &str = "http://www.w3.org/2001/XMLSchema#string";
+
+#[derive(Debug)]
+pub enum RdfError {
+    /// A remote context was referenced that the document loader could not provide
+    UnknownContext(String),
+    /// A remote context (indirectly) includes itself
+    RecursiveContext(String),
+    InvalidContext(&'static str),
+    InvalidDocument(&'static str),
+    /// Canonicalization exceeded its work limit
+    CanonicalizationLimit,
+}
+
+impl Display for RdfError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RdfError::UnknownContext(iri) => write!(f, "unknown context: {}", iri),
+            RdfError::RecursiveContext(iri) => write!(f, "recursive context inclusion: {}", iri),
+            RdfError::InvalidContext(reason) => write!(f, "invalid context: {}", reason),
+            RdfError::InvalidDocument(reason) => write!(f, "invalid JSON-LD document: {}", reason),
+            RdfError::CanonicalizationLimit => write!(f, "dataset is too complex to canonicalize"),
+        }
+    }
+}
+
+impl Error for RdfError {}
+
+/// Source of remote context documents referenced from `@context`
+pub trait DocumentLoader {
+    fn load_context(&self, iri: &str) -> Result<Value, RdfError>;
+}
+
+/// Loader for the context documents bundled with this crate
+///
+/// Serves the ActivityStreams and Security v1 contexts, and the subset of Identity v1 that is used by signature options.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct BundledContexts;
+
+impl DocumentLoader for BundledContexts {
+    fn load_context(&self, iri: &str) -> Result<Value, RdfError> {
+        static ACTIVITYSTREAMS: OnceLock<Value> = OnceLock::new();
+        static SECURITY_V1: OnceLock<Value> = OnceLock::new();
+        static IDENTITY_V1: OnceLock<Value> = OnceLock::new();
+
+        let (cell, source) = match iri.strip_prefix("http://").or_else(|| iri.strip_prefix("https://")) {
+            Some("www.w3.org/ns/activitystreams") => (&ACTIVITYSTREAMS, include_str!("contexts/activitystreams.jsonld")),
+            Some("w3id.org/security/v1") => (&SECURITY_V1, include_str!("contexts/security-v1.jsonld")),
+            Some("w3id.org/identity/v1") => (&IDENTITY_V1, include_str!("contexts/identity-v1.jsonld")),
+            _ => return Err(RdfError::UnknownContext(iri.to_string()))
+        };
+        Ok(cell.get_or_init(|| serde_json::from_str(source).expect("bundled context documents are valid JSON")).clone())
+    }
+}
+
+/// Default bound on the work done during canonicalization, counted in Hash N-Degree Quads invocations and blank node permutations
+///
+/// Crafted ("poison") graphs can make canonicalization take exponential time; Real-world activities need only a handful of invocations.
+pub const DEFAULT_CANONICALIZATION_LIMIT: usize = 4096;
+
+/// An RDF term
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Term {
+    Iri(String),
+    /// Blank node identifier, including the `_:` prefix
+    BlankNode(String),
+    Literal { value: String, datatype: String, language: Option<String> },
+}
+
+impl Display for Term {
+    /// Format this term as it appears in N-Quads
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Term::Iri(iri) => write!(f, "<{}>", iri),
+            Term::BlankNode(label) => write!(f, "{}", label),
+            Term::Literal { value, language: Some(language), .. } => write!(f, "\"{}\"@{}", escape_literal(value), language),
+            Term::Literal { value, datatype, language: None } if datatype == XSD_STRING => write!(f, "\"{}\"", escape_literal(value)),
+            Term::Literal { value, datatype, language: None } => write!(f, "\"{}\"^^<{}>", escape_literal(value), datatype),
+        }
+    }
+}
+
+/// Escape a literal per canonical N-Quads; Only quotes, backslashes and control characters are escaped
+fn escape_literal(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for char in value.chars() {
+        match char {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\u{8}' => escaped.push_str("\\b"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\u{c}' => escaped.push_str("\\f"),
+            '\r' => escaped.push_str("\\r"),
+            '\u{0}'..='\u{1f}' | '\u{7f}' => escaped.push_str(&format!("\\u{:04X}", char as u32)),
+            char => escaped.push(char)
+        }
+    }
+    escaped
+}
+
+/// An RDF triple, in the default graph if `graph` is `None`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Quad {
+    pub subject: Term,
+    pub predicate: Term,
+    pub object: Term,
+    pub graph: Option<Term>,
+}
+
+impl Display for Quad {
+    /// Format this quad as an N-Quads statement, without trailing newline
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.graph {
+            Some(graph) => write!(f, "{} {} {} {} .", self.subject, self.predicate, self.object, graph),
+            None => write!(f, "{} {} {} .", self.subject, self.predicate, self.object),
+        }
+    }
+}
+
+/// A set of quads
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Dataset {
+    pub quads: Vec<Quad>,
+}
+
+impl Dataset {
+    /// Deserialize a JSON-LD document to RDF
+    pub fn from_json_ld(document: &Value, loader: &dyn DocumentLoader) -> Result<Dataset, RdfError> {
+        let expanded = expansion::expand(document, loader)?;
+        Ok(Dataset { quads: to_rdf::to_quads(&expanded)? })
+    }
+
+    /// Canonicalize this dataset per RDFC-1.0 (which produces the same result as URDNA2015)
+    ///
+    /// Blank nodes are relabeled `_:c14n0`, `_:c14n1`, ..., and quads are deduplicated and sorted in canonical N-Quads order.
+    pub fn canonicalize(&self) -> Result<Dataset, RdfError> {
+        self.canonicalize_with_limit(DEFAULT_CANONICALIZATION_LIMIT)
+    }
+
+    /// Canonicalize this dataset, giving up with [RdfError::CanonicalizationLimit] after `limit` units of work
+    pub fn canonicalize_with_limit(&self, limit: usize) -> Result<Dataset, RdfError> {
+        let mut quads = canonicalize::rdfc10(&self.quads, limit)?
+            .into_iter()
+            .map(|quad| (quad.to_string(), quad))
+            .collect::<Vec<_>>();
+        quads.sort_by(|(a, _), (b, _)| a.cmp(b));
+        quads.dedup_by(|(a, _), (b, _)| a == b);
+        Ok(Dataset { quads: quads.into_iter().map(|(_, quad)| quad).collect() })
+    }
+
+    /// Serialize this dataset as N-Quads, one statement per line in the current order
+    pub fn to_nquads(&self) -> String {
+        self.quads.iter().map(|quad| format!("{}\n", quad)).collect()
+    }
+}
+
+/// Convert a JSON-LD document to canonical N-Quads
+pub fn canonical_nquads(document: &Value, loader: &dyn DocumentLoader) -> Result<String, RdfError> {
+    Ok(Dataset::from_json_ld(document, loader)?.canonicalize()?.to_nquads())
+}
+
+/// Content hash of a JSON-LD document; The hex-encoded SHA-256 digest of its canonical N-Quads
+///
+/// Documents that are semantically identical (regardless of key order, compacted form or blank node labels) have the same content hash.
+pub fn content_hash(document: &Value, loader: &dyn DocumentLoader) -> Result<String, RdfError> {
+    Ok(sha256_hex(canonical_nquads(document, loader)?.as_bytes()))
+}
+
+impl<T: Serialize + Debug, C: Serialize + Debug> LinkedData<T, C> {
+    /// Serialize this document and convert it to RDF
+    pub fn to_rdf(&self, loader: &dyn DocumentLoader) -> Result<Dataset, RdfError> {
+        let document = serde_json::to_value(self).map_err(|_| RdfError::InvalidDocument("could not serialize document"))?;
+        Dataset::from_json_ld(&document, loader)
+    }
+
+    pub fn canonical_nquads(&self, loader: &dyn DocumentLoader) -> Result<String, RdfError> {
+        Ok(self.to_rdf(loader)?.canonicalize()?.to_nquads())
+    }
+
+    /// See [content_hash]
+    pub fn content_hash(&self, loader: &dyn DocumentLoader) -> Result<String, RdfError> {
+        Ok(sha256_hex(self.canonical_nquads(loader)?.as_bytes()))
+    }
+}
+
+fn is_keyword(value: &str) -> bool {
+    matches!(value, "@context" | "@id" | "@value" | "@language" | "@type" | "@container" | "@list" | "@set" | "@reverse" | "@index" | "@base" | "@vocab" | "@graph")
+}
+
+fn is_blank_node(value: &str) -> bool {
+    value.starts_with("_:")
+}
+
+fn is_absolute_iri(value: &str) -> bool {
+    value.contains(':')
+}
+
+/// Sha256 digest, as lowercase hexadecimal
+pub(crate) fn sha256_hex(data: &[u8]) -> String {
+    use sha2::{Digest, Sha256};
+    Sha256::digest(data).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Issues sequential blank node identifiers, remembering the order in which existing identifiers were relabeled
+#[derive(Debug, Clone)]
+struct IdentifierIssuer {
+    prefix: &'static str,
+    counter: usize,
+    issued: HashMap<String, String>,
+    order: Vec<String>,
+}
+
+impl IdentifierIssuer {
+    fn new(prefix: &'static str) -> Self {
+        IdentifierIssuer { prefix, counter: 0, issued: HashMap::new(), order: Vec::new() }
+    }
+
+    fn issue(&mut self, existing: Option<&str>) -> String {
+        if let Some(issued) = existing.and_then(|existing| self.issued.get(existing)) {
+            return issued.clone();
+        }
+        let identifier = format!("{}{}", self.prefix, self.counter);
+        self.counter += 1;
+        if let Some(existing) = existing {
+            self.issued.insert(existing.to_string(), identifier.clone());
+            self.order.push(existing.to_string());
+        }
+        identifier
+    }
+
+    fn get(&self, existing: &str) -> Option<&String> {
+        self.issued.get(existing)
+    }
+}
+
+mod context {
+    use std::collections::HashMap;
+    use serde_json::{Map, Value};
+    use crate::linkeddata::rdf::{DocumentLoader, is_absolute_iri, is_keyword, RdfError};
+
+    #[derive(Debug, Clone, Default)]
+    pub(super) struct Context {
+        pub vocab: Option<String>,
+        pub language: Option<String>,
+        /// Term definitions; `None` for terms explicitly mapped to null
+        pub terms: HashMap<String, Option<TermDefinition>>,
+    }
+
+    #[derive(Debug, Clone)]
+    pub(super) struct TermDefinition {
+        pub iri: String,
+        pub reverse: bool,
+        pub type_mapping: Option<String>,
+        pub language_mapping: Option<Option<String>>,
+        pub container: Option<String>,
+    }
+
+    impl Context {
+        pub fn definition(&self, term: &str) -> Option<&TermDefinition> {
+            self.terms.get(term).and_then(Option::as_ref)
+        }
+
+        pub fn container(&self, term: Option<&str>) -> Option<&str> {
+            term.and_then(|term| self.definition(term)).and_then(|definition| definition.container.as_deref())
+        }
+
+        /// Update this context with a local context
+        pub fn process(&self, local: &Value, loader: &dyn DocumentLoader, remote: &mut Vec<String>) -> Result<Context, RdfError> {
+            let mut result = self.clone();
+            let contexts = match local {
+                Value::Array(contexts) => contexts.as_slice(),
+                local => std::slice::from_ref(local)
+            };
+            for context in contexts {
+                match context {
+                    Value::Null => result = Context::default(),
+                    Value::String(iri) => {
+                        if remote.contains(iri) {
+                            return Err(RdfError::RecursiveContext(iri.clone()));
+                        }
+                        let document = loader.load_context(iri)?;
+                        let inner = document.get("@context").ok_or(RdfError::InvalidContext("remote context has no @context"))?;
+                        remote.push(iri.clone());
+                        result = result.process(inner, loader, remote)?;
+                        remote.pop();
+                    }
+                    Value::Object(local) => {
+                        match local.get("@vocab") {
+                            None => {}
+                            Some(Value::Null) => result.vocab = None,
+                            Some(Value::String(vocab)) if is_absolute_iri(vocab) => result.vocab = Some(vocab.clone()),
+                            Some(_) => return Err(RdfError::InvalidContext("invalid @vocab"))
+                        }
+                        match local.get("@language") {
+                            None => {}
+                            Some(Value::Null) => result.language = None,
+                            Some(Value::String(language)) => result.language = Some(language.to_lowercase()),
+                            Some(_) => return Err(RdfError::InvalidContext("invalid @language"))
+                        }
+                        let mut defined = HashMap::new();
+                        for term in local.keys().filter(|term| !term.starts_with('@')) {
+                            result.create_term_definition(local, term, &mut defined)?;
+                        }
+                    }
+                    _ => return Err(RdfError::InvalidContext("context must be null, a string or an object"))
+                }
+            }
+            Ok(result)
+        }
+
+        fn create_term_definition(&mut self, local: &Map<String, Value>, term: &str, defined: &mut HashMap<String, bool>) -> Result<(), RdfError> {
+            match defined.get(term) {
+                Some(true) => return Ok(()),
+                Some(false) => return Err(RdfError::InvalidContext("cyclic IRI mapping")),
+                None => {}
+            }
+            defined.insert(term.to_string(), false);
+            self.terms.remove(term);
+
+            let value = match &local[term] {
+                Value::String(iri) => {
+                    let mut value = Map::new();
+                    value.insert("@id".to_string(), Value::String(iri.clone()));
+                    value
+                }
+                Value::Object(value) if value.get("@id") != Some(&Value::Null) => value.clone(),
+                Value::Null | Value::Object(_) => {
+                    self.terms.insert(term.to_string(), None);
+                    defined.insert(term.to_string(), true);
+                    return Ok(());
+                }
+                _ => return Err(RdfError::InvalidContext("invalid term definition"))
+            };
+
+            let mut definition = TermDefinition { iri: String::new(), reverse: false, type_mapping: None, language_mapping: None, container: None };
+            if let Some(type_mapping) = value.get("@type") {
+                let type_mapping = type_mapping.as_str().ok_or(RdfError::InvalidContext("invalid type mapping"))?;
+                match self.expand_iri_defining(type_mapping, true, local, defined)? {
+                    Some(type_mapping) if type_mapping == "@id" || type_mapping == "@vocab" || is_absolute_iri(&type_mapping) => definition.type_mapping = Some(type_mapping),
+                    _ => return Err(RdfError::InvalidContext("invalid type mapping"))
+                }
+            }
+
+            if let Some(reverse) = value.get("@reverse") {
+                let reverse = reverse.as_str().ok_or(RdfError::InvalidContext("invalid reverse property"))?;
+                match self.expand_iri_defining(reverse, true, local, defined)? {
+                    Some(iri) if is_absolute_iri(&iri) => definition.iri = iri,
+                    _ => return Err(RdfError::InvalidContext("invalid reverse property"))
+                }
+                definition.reverse = true;
+            } else if let Some(id) = value.get("@id").filter(|id| id.as_str() != Some(term)) {
+                let id = id.as_str().ok_or(RdfError::InvalidContext("invalid IRI mapping"))?;
+                match self.expand_iri_defining(id, true, local, defined)? {
+                    Some(iri) if is_keyword(&iri) || is_absolute_iri(&iri) => definition.iri = iri,
+                    _ => return Err(RdfError::InvalidContext("invalid IRI mapping"))
+                }
+            } else if let Some((prefix, suffix)) = term.split_once(':') {
+                if local.contains_key(prefix) {
+                    self.create_term_definition(local, prefix, defined)?;
+                }
+                definition.iri = match self.definition(prefix) {
+                    Some(prefix) => format!("{}{}", prefix.iri, suffix),
+                    None => term.to_string()
+                };
+            } else if let Some(vocab) = &self.vocab {
+                definition.iri = format!("{}{}", vocab, term);
+            } else {
+                return Err(RdfError::InvalidContext("invalid IRI mapping"));
+            }
+
+            match value.get("@container") {
+                None | Some(Value::Null) => {}
+                Some(Value::String(container)) if matches!(container.as_str(), "@list" | "@set" | "@index" | "@language") => definition.container = Some(container.clone()),
+                Some(_) => return Err(RdfError::InvalidContext("invalid container mapping"))
+            }
+
+            if !value.contains_key("@type") {
+                match value.get("@language") {
+                    None => {}
+                    Some(Value::Null) => definition.language_mapping = Some(None),
+                    Some(Value::String(language)) => definition.language_mapping = Some(Some(language.to_lowercase())),
+                    Some(_) => return Err(RdfError::InvalidContext("invalid language mapping"))
+                }
+            }
+
+            self.terms.insert(term.to_string(), Some(definition));
+            defined.insert(term.to_string(), true);
+            Ok(())
+        }
+
+        /// IRI expansion while processing a local context; Terms and prefixes used before their definition in the local context are defined first.
+        fn expand_iri_defining(&mut self, value: &str, vocab: bool, local: &Map<String, Value>, defined: &mut HashMap<String, bool>) -> Result<Option<String>, RdfError> {
+            if !is_keyword(value) {
+                if local.contains_key(value) && defined.get(value) != Some(&true) {
+                    self.create_term_definition(local, value, defined)?;
+                }
+                if let Some((prefix, _)) = value.split_once(':') {
+                    if local.contains_key(prefix) && defined.get(prefix) != Some(&true) {
+                        self.create_term_definition(local, prefix, defined)?;
+                    }
+                }
+            }
+            Ok(self.expand_iri(value, vocab))
+        }
+
+        /// Expand a term, compact IRI or (with `vocab`) vocabulary-relative IRI; Returns `None` for terms mapped to null
+        pub fn expand_iri(&self, value: &str, vocab: bool) -> Option<String> {
+            if is_keyword(value) {
+                return Some(value.to_string());
+            }
+            if vocab {
+                if let Some(definition) = self.terms.get(value) {
+                    return definition.as_ref().map(|definition| definition.iri.clone());
+                }
+            }
+            if let Some((prefix, suffix)) = value.split_once(':') {
+                if prefix == "_" || suffix.starts_with("//") {
+                    return Some(value.to_string());
+                }
+                return match self.definition(prefix) {
+                    Some(prefix) => Some(format!("{}{}", prefix.iri, suffix)),
+                    None => Some(value.to_string())
+                };
+            }
+            match &self.vocab {
+                Some(prefix) if vocab => Some(format!("{}{}", prefix, value)),
+                _ => Some(value.to_string())
+            }
+        }
+    }
+}
+
+mod expansion {
+    use serde_json::{Map, Value};
+    use crate::linkeddata::rdf::{DocumentLoader, is_absolute_iri, is_keyword, RdfError};
+    use crate::linkeddata::rdf::context::Context;
+
+    fn is_list(value: &Value) -> bool {
+        value.get("@list").is_some()
+    }
+
+    /// Add a value to an (array-valued) property of an expanded node
+    pub(super) fn append_values(node: &mut Map<String, Value>, property: &str, value: Value) {
+        let values = node.entry(property.to_string()).or_insert_with(|| Value::Array(Vec::new()));
+        if let Value::Array(values) = values {
+            match value {
+                Value::Array(value) => values.extend(value),
+                value => values.push(value)
+            }
+        }
+    }
+
+    /// Expand a JSON-LD document, returning the expanded top-level nodes
+    pub(super) fn expand(document: &Value, loader: &dyn DocumentLoader) -> Result<Vec<Value>, RdfError> {
+        let mut expanded = expand_element(&Context::default(), None, document, loader)?;
+        if let Value::Object(map) = &mut expanded {
+            if map.len() == 1 {
+                if let Some(graph) = map.remove("@graph") {
+                    expanded = graph;
+                }
+            }
+        }
+        Ok(match expanded {
+            Value::Null => Vec::new(),
+            Value::Array(expanded) => expanded,
+            expanded => vec![expanded]
+        })
+    }
+
+    fn expand_element(active: &Context, active_property: Option<&str>, element: &Value, loader: &dyn DocumentLoader) -> Result<Value, RdfError> {
+        match element {
+            Value::Null => Ok(Value::Null),
+            Value::Array(items) => {
+                let list_container = active.container(active_property) == Some("@list");
+                let mut result = Vec::with_capacity(items.len());
+                for item in items {
+                    match expand_element(active, active_property, item, loader)? {
+                        expanded if list_container && (expanded.is_array() || is_list(&expanded)) => return Err(RdfError::InvalidDocument("list of lists")),
+                        Value::Array(expanded) => result.extend(expanded),
+                        Value::Null => {}
+                        expanded => result.push(expanded)
+                    }
+                }
+                Ok(Value::Array(result))
+            }
+            Value::Object(element) => expand_object(active, active_property, element, loader),
+            scalar => match active_property {
+                None | Some("@graph") => Ok(Value::Null),
+                Some(property) => Ok(expand_value(active, property, scalar))
+            }
+        }
+    }
+
+    fn expand_value(active: &Context, active_property: &str, value: &Value) -> Value {
+        let definition = active.definition(active_property);
+        let mut result = Map::new();
+        match (definition.and_then(|definition| definition.type_mapping.as_deref()), value) {
+            (Some("@id"), Value::String(iri)) => {
+                result.insert("@id".to_string(), Value::String(active.expand_iri(iri, false).unwrap_or_default()));
+                return Value::Object(result);
+            }
+            (Some("@vocab"), Value::String(iri)) => {
+                result.insert("@id".to_string(), Value::String(active.expand_iri(iri, true).unwrap_or_default()));
+                return Value::Object(result);
+            }
+            _ => {}
+        }
+        result.insert("@value".to_string(), value.clone());
+        match definition.and_then(|definition| definition.type_mapping.as_deref()) {
+            Some(type_mapping) if type_mapping != "@id" && type_mapping != "@vocab" => {
+                result.insert("@type".to_string(), Value::String(type_mapping.to_string()));
+            }
+            _ if value.is_string() => {
+                let language = match definition.and_then(|definition| definition.language_mapping.as_ref()) {
+                    Some(language) => language.as_ref(),
+                    None => active.language.as_ref()
+                };
+                if let Some(language) = language {
+                    result.insert("@language".to_string(), Value::String(language.clone()));
+                }
+            }
+            _ => {}
+        }
+        Value::Object(result)
+    }
+
+    fn expand_object(active: &Context, active_property: Option<&str>, element: &Map<String, Value>, loader: &dyn DocumentLoader) -> Result<Value, RdfError> {
+        let processed;
+        let active = match element.get("@context") {
+            Some(local) => {
+                processed = active.process(local, loader, &mut Vec::new())?;
+                &processed
+            }
+            None => active
+        };
+
+        let mut result = Map::new();
+        let mut keys = element.keys().collect::<Vec<_>>();
+        keys.sort();
+        for key in keys {
+            let value = &element[key];
+            if key == "@context" {
+                continue;
+            }
+            let property = match active.expand_iri(key, true) {
+                Some(property) if is_keyword(&property) || is_absolute_iri(&property) => property,
+                _ => continue
+            };
+
+            if is_keyword(&property) {
+                if active_property == Some("@reverse") {
+                    return Err(RdfError::InvalidDocument("invalid reverse property map"));
+                }
+                if result.contains_key(&property) {
+                    return Err(RdfError::InvalidDocument("colliding keywords"));
+                }
+                let expanded = match property.as_str() {
+                    "@id" => match value {
+                        Value::String(id) => Value::String(active.expand_iri(id, false).unwrap_or_default()),
+                        _ => return Err(RdfError::InvalidDocument("invalid @id value"))
+                    },
+                    "@type" => match value {
+                        Value::String(type_iri) => Value::String(active.expand_iri(type_iri, true).unwrap_or_default()),
+                        Value::Array(types) => Value::Array(types.iter()
+                            .map(|type_iri| type_iri.as_str()
+                                .map(|type_iri| Value::String(active.expand_iri(type_iri, true).unwrap_or_default()))
+                                .ok_or(RdfError::InvalidDocument("invalid type value"))
+                            )
+                            .collect::<Result<_, _>>()?),
+                        _ => return Err(RdfError::InvalidDocument("invalid type value"))
+                    },
+                    "@graph" => expand_element(active, Some("@graph"), value, loader)?,
+                    "@value" => match value {
+                        Value::Null => {
+                            result.insert(property, Value::Null);
+                            continue;
+                        }
+                        Value::Array(_) | Value::Object(_) => return Err(RdfError::InvalidDocument("invalid value object value")),
+                        value => value.clone()
+                    },
+                    "@language" => match value {
+                        Value::String(language) => Value::String(language.to_lowercase()),
+                        _ => return Err(RdfError::InvalidDocument("invalid language-tagged string"))
+                    },
+                    "@index" => match value {
+                        Value::String(_) => value.clone(),
+                        _ => return Err(RdfError::InvalidDocument("invalid @index value"))
+                    },
+                    "@list" => {
+                        if matches!(active_property, None | Some("@graph")) {
+                            continue;
+                        }
+                        let expanded = match expand_element(active, active_property, value, loader)? {
+                            Value::Array(items) => items,
+                            Value::Null => Vec::new(),
+                            item => vec![item]
+                        };
+                        if expanded.iter().any(is_list) {
+                            return Err(RdfError::InvalidDocument("list of lists"));
+                        }
+                        Value::Array(expanded)
+                    }
+                    "@set" => expand_element(active, active_property, value, loader)?,
+                    "@reverse" => {
+                        if !value.is_object() {
+                            return Err(RdfError::InvalidDocument("invalid @reverse value"));
+                        }
+                        if let Value::Object(mut reverse) = expand_element(active, Some("@reverse"), value, loader)? {
+                            // Double reversal produces forward properties
+                            if let Some(Value::Object(forward)) = reverse.remove("@reverse") {
+                                for (property, items) in forward {
+                                    append_values(&mut result, &property, items);
+                                }
+                            }
+                            if !reverse.is_empty() {
+                                let reverse_map = result.entry("@reverse").or_insert_with(|| Value::Object(Map::new()));
+                                if let Value::Object(reverse_map) = reverse_map {
+                                    for (property, items) in reverse {
+                                        append_values(reverse_map, &property, items);
+                                    }
+                                }
+                            }
+                        }
+                        continue;
+                    }
+                    _ => continue
+                };
+                if !expanded.is_null() {
+                    result.insert(property, expanded);
+                }
+                continue;
+            }
+
+            let definition = active.definition(key);
+            let container = definition.and_then(|definition| definition.container.as_deref());
+            let mut expanded = match (container, value) {
+                (Some("@language"), Value::Object(language_map)) => {
+                    let mut languages = language_map.keys().collect::<Vec<_>>();
+                    languages.sort();
+                    let mut expanded = Vec::new();
+                    for language in languages {
+                        let items = match &language_map[language] {
+                            Value::Array(items) => items.as_slice(),
+                            item => std::slice::from_ref(item)
+                        };
+                        for item in items {
+                            let item = item.as_str().ok_or(RdfError::InvalidDocument("invalid language map value"))?;
+                            let mut value = Map::new();
+                            value.insert("@value".to_string(), Value::String(item.to_string()));
+                            value.insert("@language".to_string(), Value::String(language.to_lowercase()));
+                            expanded.push(Value::Object(value));
+                        }
+                    }
+                    Value::Array(expanded)
+                }
+                (Some("@index"), Value::Object(index_map)) => {
+                    let mut indexes = index_map.keys().collect::<Vec<_>>();
+                    indexes.sort();
+                    let mut expanded = Vec::new();
+                    for index in indexes {
+                        let items = match expand_element(active, Some(key), &index_map[index], loader)? {
+                            Value::Array(items) => items,
+                            Value::Null => Vec::new(),
+                            item => vec![item]
+                        };
+                        for mut item in items {
+                            if let Value::Object(item) = &mut item {
+                                item.entry("@index").or_insert_with(|| Value::String(index.clone()));
+                            }
+                            expanded.push(item);
+                        }
+                    }
+                    Value::Array(expanded)
+                }
+                _ => expand_element(active, Some(key), value, loader)?
+            };
+            if expanded.is_null() {
+                continue;
+            }
+            if container == Some("@list") && !is_list(&expanded) {
+                let items = match expanded {
+                    Value::Array(items) => items,
+                    item => vec![item]
+                };
+                let mut list = Map::new();
+                list.insert("@list".to_string(), Value::Array(items));
+                expanded = Value::Object(list);
+            }
+            if definition.is_some_and(|definition| definition.reverse) {
+                let reverse_map = result.entry("@reverse").or_insert_with(|| Value::Object(Map::new()));
+                if let Value::Object(reverse_map) = reverse_map {
+                    let items = match expanded {
+                        Value::Array(items) => items,
+                        item => vec![item]
+                    };
+                    for item in items {
+                        if item.get("@value").is_some() || is_list(&item) {
+                            return Err(RdfError::InvalidDocument("invalid reverse property value"));
+                        }
+                        append_values(reverse_map, &property, item);
+                    }
+                }
+            } else {
+                append_values(&mut result, &property, expanded);
+            }
+        }
+
+        let mut result = if result.contains_key("@value") {
+            if result.keys().any(|key| !matches!(key.as_str(), "@value" | "@language" | "@type" | "@index")) || (result.contains_key("@language") && result.contains_key("@type")) {
+                return Err(RdfError::InvalidDocument("invalid value object"));
+            }
+            match &result["@value"] {
+                Value::Null => return Ok(Value::Null),
+                Value::String(_) => {}
+                _ if result.contains_key("@language") => return Err(RdfError::InvalidDocument("invalid language-tagged value")),
+                _ => {}
+            }
+            if let Some(type_iri) = result.get("@type") {
+                if !type_iri.as_str().is_some_and(is_absolute_iri) {
+                    return Err(RdfError::InvalidDocument("invalid typed value"));
+                }
+            }
+            Value::Object(result)
+        } else if result.contains_key("@list") || result.contains_key("@set") {
+            if result.len() > 2 || (result.len() == 2 && !result.contains_key("@index")) {
+                return Err(RdfError::InvalidDocument("invalid set or list object"));
+            }
+            match result.remove("@set") {
+                Some(set) => set,
+                None => Value::Object(result)
+            }
+        } else {
+            if let Some(type_iri) = result.get_mut("@type") {
+                if !type_iri.is_array() {
+                    *type_iri = Value::Array(vec![type_iri.take()]);
+                }
+            }
+            Value::Object(result)
+        };
+
+        if let Value::Object(map) = &result {
+            if map.len() == 1 && map.contains_key("@language") {
+                result = Value::Null;
+            }
+        }
+        if matches!(active_property, None | Some("@graph")) {
+            if let Value::Object(map) = &result {
+                if map.is_empty() || map.contains_key("@value") || map.contains_key("@list") || (map.len() == 1 && map.contains_key("@id")) {
+                    result = Value::Null;
+                }
+            }
+        }
+        Ok(result)
+    }
+}
+
+mod to_rdf {
+    use std::collections::BTreeMap;
+    use serde_json::{Map, Value};
+    use crate::linkeddata::rdf::*;
+    use crate::linkeddata::rdf::expansion::append_values;
+
+    /// Nodes by graph name and subject, with property values stored as arrays
+    type NodeMap = BTreeMap<String, BTreeMap<String, Map<String, Value>>>;
+
+    const DEFAULT_GRAPH: &str = "@default";
+
+    enum Subject<'a> {
+        None,
+        Node(&'a str),
+        /// Reverse property; The referencing node is added to the referenced node's properties
+        Reverse(&'a Value),
+    }
+
+    fn is_list(value: &Value) -> bool {
+        value.get("@list").is_some()
+    }
+
+    fn add_unique(node: &mut Map<String, Value>, property: &str, value: Value) {
+        let values = node.entry(property.to_string()).or_insert_with(|| Value::Array(Vec::new()));
+        if let Value::Array(values) = values {
+            if !values.contains(&value) {
+                values.push(value);
+            }
+        }
+    }
+
+    fn relabel(value: &str, issuer: &mut IdentifierIssuer) -> String {
+        match is_blank_node(value) {
+            true => issuer.issue(Some(value)),
+            false => value.to_string()
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]    // Mirrors the parameters of the JSON-LD node map generation algorithm
+    fn generate_node_map(element: &Value, node_map: &mut NodeMap, active_graph: &str, active_subject: &Subject, active_property: Option<&str>, mut list: Option<&mut Vec<Value>>, issuer: &mut IdentifierIssuer) -> Result<(), RdfError> {
+        let element = match element {
+            Value::Array(items) => {
+                for item in items {
+                    generate_node_map(item, node_map, active_graph, active_subject, active_property, list.as_deref_mut(), issuer)?;
+                }
+                return Ok(());
+            }
+            Value::Object(element) => element,
+            _ => return Err(RdfError::InvalidDocument("expanded element is not a node, value or list"))
+        };
+
+        if element.contains_key("@value") {
+            let mut value = element.clone();
+            if let Some(Value::String(type_iri)) = value.get_mut("@type") {
+                *type_iri = relabel(type_iri, issuer);
+            }
+            match (list, active_subject, active_property) {
+                (Some(list), _, _) => list.push(Value::Object(value)),
+                (None, Subject::Node(subject), Some(property)) => {
+                    let node = node_map.entry(active_graph.to_string()).or_default().entry(subject.to_string()).or_default();
+                    add_unique(node, property, Value::Object(value));
+                }
+                _ => {}
+            }
+            return Ok(());
+        }
+
+        if let Some(items) = element.get("@list") {
+            let mut result = Vec::new();
+            generate_node_map(items, node_map, active_graph, active_subject, active_property, Some(&mut result), issuer)?;
+            let mut list_object = Map::new();
+            list_object.insert("@list".to_string(), Value::Array(result));
+            match (list, active_subject, active_property) {
+                (Some(list), _, _) => list.push(Value::Object(list_object)),
+                (None, Subject::Node(subject), Some(property)) => {
+                    let node = node_map.entry(active_graph.to_string()).or_default().entry(subject.to_string()).or_default();
+                    append_values(node, property, Value::Object(list_object));
+                }
+                _ => {}
+            }
+            return Ok(());
+        }
+
+        let id = match element.get("@id").and_then(Value::as_str) {
+            Some(id) => relabel(id, issuer),
+            None => issuer.issue(None)
+        };
+        {
+            let graph = node_map.entry(active_graph.to_string()).or_default();
+            graph.entry(id.clone()).or_insert_with(|| {
+                let mut node = Map::new();
+                node.insert("@id".to_string(), Value::String(id.clone()));
+                node
+            });
+        }
+
+        let mut reference = Map::new();
+        reference.insert("@id".to_string(), Value::String(id.clone()));
+        let reference = Value::Object(reference);
+        match (active_subject, active_property) {
+            (Subject::Reverse(referencing), Some(property)) => {
+                let node = node_map.get_mut(active_graph).and_then(|graph| graph.get_mut(&id)).expect("node was inserted above");
+                add_unique(node, property, (*referencing).clone());
+            }
+            (Subject::Node(subject), Some(property)) => match list {
+                Some(list) => list.push(reference.clone()),
+                None => {
+                    let node = node_map.entry(active_graph.to_string()).or_default().entry(subject.to_string()).or_default();
+                    add_unique(node, property, reference.clone());
+                }
+            },
+            _ => {}
+        }
+
+        if let Some(types) = element.get("@type").and_then(Value::as_array) {
+            for type_iri in types.iter().filter_map(Value::as_str) {
+                let type_iri = relabel(type_iri, issuer);
+                let node = node_map.get_mut(active_graph).and_then(|graph| graph.get_mut(&id)).expect("node was inserted above");
+                add_unique(node, "@type", Value::String(type_iri));
+            }
+        }
+
+        if let Some(index) = element.get("@index") {
+            let node = node_map.get_mut(active_graph).and_then(|graph| graph.get_mut(&id)).expect("node was inserted above");
+            node.insert("@index".to_string(), index.clone());
+        }
+
+        if let Some(Value::Object(reverse)) = element.get("@reverse") {
+            for (property, values) in reverse {
+                generate_node_map(values, node_map, active_graph, &Subject::Reverse(&reference), Some(property), None, issuer)?;
+            }
+        }
+
+        if let Some(graph) = element.get("@graph") {
+            generate_node_map(graph, node_map, &id, &Subject::None, None, None, issuer)?;
+        }
+
+        let mut properties = element.keys()
+            .filter(|property| !matches!(property.as_str(), "@id" | "@type" | "@index" | "@reverse" | "@graph"))
+            .collect::<Vec<_>>();
+        properties.sort();
+        for property in properties {
+            let value = &element[property];
+            let property_label = relabel(property, issuer);
+            {
+                let node = node_map.get_mut(active_graph).and_then(|graph| graph.get_mut(&id)).expect("node was inserted above");
+                node.entry(property_label.clone()).or_insert_with(|| Value::Array(Vec::new()));
+            }
+            generate_node_map(value, node_map, active_graph, &Subject::Node(&id), Some(&property_label), None, issuer)?;
+        }
+        Ok(())
+    }
+
+    fn node_term(id: &str) -> Option<Term> {
+        if is_blank_node(id) {
+            Some(Term::BlankNode(id.to_string()))
+        } else if is_absolute_iri(id) {
+            Some(Term::Iri(id.to_string()))
+        } else {
+            None
+        }
+    }
+
+    /// Render a number in the canonical lexical form used by JSON-LD; Integers as xsd:integer, otherwise as xsd:double in exponent notation
+    fn number_lexical(number: &serde_json::Number, datatype: Option<&str>) -> (String, &'static str) {
+        if datatype != Some(XSD_DOUBLE) {
+            if let Some(integer) = number.as_i64() {
+                return (integer.to_string(), XSD_INTEGER);
+            }
+            if let Some(integer) = number.as_u64() {
+                return (integer.to_string(), XSD_INTEGER);
+            }
+        }
+        let float = number.as_f64().unwrap_or(f64::NAN);
+        if datatype != Some(XSD_DOUBLE) && float.fract() == 0.0 && float.abs() < 1e21 {
+            return (format!("{:.0}", float), XSD_INTEGER);
+        }
+        let formatted = format!("{:.15e}", float);
+        let (mantissa, exponent) = formatted.split_once('e').unwrap_or((&formatted, "0"));
+        let mut mantissa = mantissa.trim_end_matches('0').to_string();
+        if mantissa.ends_with('.') {
+            mantissa.push('0');
+        }
+        (format!("{}E{}", mantissa, exponent), XSD_DOUBLE)
+    }
+
+    fn object_term(item: &Value) -> Option<Term> {
+        if let Some(id) = item.get("@id") {
+            return node_term(id.as_str()?);
+        }
+        let datatype = item.get("@type").and_then(Value::as_str);
+        let (value, datatype) = match item.get("@value")? {
+            Value::Bool(bool) => (bool.to_string(), datatype.unwrap_or(XSD_BOOLEAN).to_string()),
+            Value::Number(number) => {
+                let (value, default_datatype) = number_lexical(number, datatype);
+                (value, datatype.unwrap_or(default_datatype).to_string())
+            }
+            Value::String(string) => match item.get("@language").and_then(Value::as_str) {
+                Some(language) => return Some(Term::Literal { value: string.clone(), datatype: RDF_LANG_STRING.to_string(), language: Some(language.to_string()) }),
+                None => (string.clone(), datatype.unwrap_or(XSD_STRING).to_string())
+            },
+            _ => return None
+        };
+        Some(Term::Literal { value, datatype, language: None })
+    }
+
+    fn list_to_rdf(items: &[Value], quads: &mut Vec<Quad>, graph: &Option<Term>, issuer: &mut IdentifierIssuer) -> Term {
+        let nodes = items.iter().map(|_| issuer.issue(None)).collect::<Vec<_>>();
+        for (index, item) in items.iter().enumerate() {
+            let subject = Term::BlankNode(nodes[index].clone());
+            let object = match is_list(item) {
+                true => Some(list_to_rdf(item["@list"].as_array().map_or(&[][..], Vec::as_slice), quads, graph, issuer)),
+                false => object_term(item)
+            };
+            if let Some(object) = object {
+                quads.push(Quad { subject: subject.clone(), predicate: Term::Iri(RDF_FIRST.to_string()), object, graph: graph.clone() });
+            }
+            let rest = match nodes.get(index + 1) {
+                Some(next) => Term::BlankNode(next.clone()),
+                None => Term::Iri(RDF_NIL.to_string())
+            };
+            quads.push(Quad { subject, predicate: Term::Iri(RDF_REST.to_string()), object: rest, graph: graph.clone() });
+        }
+        match nodes.first() {
+            Some(head) => Term::BlankNode(head.clone()),
+            None => Term::Iri(RDF_NIL.to_string())
+        }
+    }
+
+    /// Convert expanded JSON-LD to RDF quads
+    pub(super) fn to_quads(expanded: &[Value]) -> Result<Vec<Quad>, RdfError> {
+        let mut issuer = IdentifierIssuer::new("_:b");
+        let mut node_map = NodeMap::new();
+        node_map.insert(DEFAULT_GRAPH.to_string(), BTreeMap::new());
+        for element in expanded {
+            generate_node_map(element, &mut node_map, DEFAULT_GRAPH, &Subject::None, None, None, &mut issuer)?;
+        }
+
+        let mut quads = Vec::new();
+        for (graph_name, graph) in &node_map {
+            let graph_term = match graph_name.as_str() {
+                DEFAULT_GRAPH => None,
+                graph_name => match node_term(graph_name) {
+                    Some(term) => Some(term),
+                    None => continue
+                }
+            };
+            for (subject, node) in graph {
+                let Some(subject) = node_term(subject) else { continue };
+                let mut properties = node.keys().collect::<Vec<_>>();
+                properties.sort();
+                for property in properties {
+                    let values = node[property].as_array().map_or(&[][..], Vec::as_slice);
+                    if property == "@type" {
+                        for type_iri in values.iter().filter_map(Value::as_str) {
+                            if let Some(object) = node_term(type_iri) {
+                                quads.push(Quad { subject: subject.clone(), predicate: Term::Iri(RDF_TYPE.to_string()), object, graph: graph_term.clone() });
+                            }
+                        }
+                        continue;
+                    }
+                    if is_keyword(property) || is_blank_node(property) || !is_absolute_iri(property) {
+                        continue;
+                    }
+                    for item in values {
+                        let object = match is_list(item) {
+                            true => Some(list_to_rdf(item["@list"].as_array().map_or(&[][..], Vec::as_slice), &mut quads, &graph_term, &mut issuer)),
+                            false => object_term(item)
+                        };
+                        if let Some(object) = object {
+                            quads.push(Quad { subject: subject.clone(), predicate: Term::Iri(property.clone()), object, graph: graph_term.clone() });
+                        }
+                    }
+                }
+            }
+        }
+        Ok(quads)
+    }
+}
+
+mod canonicalize {
+    use std::cell::Cell;
+    use std::collections::{BTreeMap, HashMap};
+    use crate::linkeddata::rdf::{IdentifierIssuer, Quad, RdfError, sha256_hex, Term};
+
+    struct State<'a> {
+        blank_node_quads: HashMap<String, Vec<&'a Quad>>,
+        canonical_issuer: IdentifierIssuer,
+        /// Remaining work; Hash N-Degree Quads invocations and permutations
+        budget: Cell<usize>,
+    }
+
+    fn blank_nodes(quad: &Quad) -> impl Iterator<Item=&String> {
+        [Some(&quad.subject), Some(&quad.object), quad.graph.as_ref()]
+            .into_iter()
+            .flatten()
+            .filter_map(|term| match term {
+                Term::BlankNode(label) => Some(label),
+                _ => None
+            })
+    }
+
+    fn relabel_term(term: &Term, label: impl Fn(&str) -> String) -> Term {
+        match term {
+            Term::BlankNode(existing) => Term::BlankNode(label(existing)),
+            term => term.clone()
+        }
+    }
+
+    impl<'a> State<'a> {
+        fn spend(&self, work: usize) -> Result<(), RdfError> {
+            match self.budget.get().checked_sub(work) {
+                Some(remaining) => {
+                    self.budget.set(remaining);
+                    Ok(())
+                }
+                None => Err(RdfError::CanonicalizationLimit)
+            }
+        }
+
+        fn hash_first_degree_quads(&self, reference: &str) -> String {
+            let label = |existing: &str| if existing == reference { "_:a".to_string() } else { "_:z".to_string() };
+            let mut nquads = self.blank_node_quads[reference].iter()
+                .map(|quad| Quad {
+                    subject: relabel_term(&quad.subject, label),
+                    predicate: quad.predicate.clone(),
+                    object: relabel_term(&quad.object, label),
+                    graph: quad.graph.as_ref().map(|graph| relabel_term(graph, label)),
+                })
+                .map(|quad| format!("{}\n", quad))
+                .collect::<Vec<_>>();
+            nquads.sort();
+            sha256_hex(nquads.concat().as_bytes())
+        }
+
+        fn hash_related_blank_node(&self, related: &str, quad: &Quad, issuer: &IdentifierIssuer, position: char) -> String {
+            let identifier = match self.canonical_issuer.get(related).or_else(|| issuer.get(related)) {
+                Some(identifier) => identifier.clone(),
+                None => self.hash_first_degree_quads(related)
+            };
+            let mut input = position.to_string();
+            if position != 'g' {
+                input.push_str(&quad.predicate.to_string());
+            }
+            input.push_str(&identifier);
+            sha256_hex(input.as_bytes())
+        }
+
+        fn hash_n_degree_quads(&self, identifier: &str, issuer: &IdentifierIssuer) -> Result<(String, IdentifierIssuer), RdfError> {
+            self.spend(1)?;
+            let mut issuer = issuer.clone();
+            let mut hash_to_related = BTreeMap::<String, Vec<String>>::new();
+            for quad in &self.blank_node_quads[identifier] {
+                let components = [(&quad.subject, 's'), (&quad.object, 'o')].into_iter()
+                    .chain(quad.graph.as_ref().map(|graph| (graph, 'g')));
+                for (term, position) in components {
+                    if let Term::BlankNode(related) = term {
+                        if related != identifier {
+                            let hash = self.hash_related_blank_node(related, quad, &issuer, position);
+                            hash_to_related.entry(hash).or_default().push(related.clone());
+                        }
+                    }
+                }
+            }
+
+            let mut data_to_hash = String::new();
+            for (related_hash, blank_nodes) in hash_to_related {
+                data_to_hash.push_str(&related_hash);
+                let mut chosen_path = String::new();
+                let mut chosen_issuer = None;
+                self.spend((1..=blank_nodes.len()).try_fold(1usize, usize::checked_mul).unwrap_or(usize::MAX))?;
+                for permutation in permutations(&blank_nodes) {
+                    let mut issuer_copy = issuer.clone();
+                    let mut path = String::new();
+                    let mut recursion_list = Vec::new();
+                    let mut skip = false;
+                    for related in &permutation {
+                        match self.canonical_issuer.get(related) {
+                            Some(canonical) => path.push_str(canonical),
+                            None => {
+                                if issuer_copy.get(related).is_none() {
+                                    recursion_list.push(related.clone());
+                                }
+                                path.push_str(&issuer_copy.issue(Some(related)));
+                            }
+                        }
+                        if !chosen_path.is_empty() && path.len() >= chosen_path.len() && path > chosen_path {
+                            skip = true;
+                            break;
+                        }
+                    }
+                    if skip {
+                        continue;
+                    }
+                    for related in &recursion_list {
+                        let (hash, result_issuer) = self.hash_n_degree_quads(related, &issuer_copy)?;
+                        issuer_copy = result_issuer;
+                        path.push_str(&issuer_copy.issue(Some(related)));
+                        path.push('<');
+                        path.push_str(&hash);
+                        path.push('>');
+                        if !chosen_path.is_empty() && path.len() >= chosen_path.len() && path > chosen_path {
+                            skip = true;
+                            break;
+                        }
+                    }
+                    if skip {
+                        continue;
+                    }
+                    if chosen_path.is_empty() || path < chosen_path {
+                        chosen_path = path;
+                        chosen_issuer = Some(issuer_copy);
+                    }
+                }
+                data_to_hash.push_str(&chosen_path);
+                if let Some(chosen_issuer) = chosen_issuer {
+                    issuer = chosen_issuer;
+                }
+            }
+            Ok((sha256_hex(data_to_hash.as_bytes()), issuer))
+        }
+    }
+
+    fn permutations(items: &[String]) -> Vec<Vec<String>> {
+        if items.len() <= 1 {
+            return vec![items.to_vec()];
+        }
+        let mut result = Vec::new();
+        for index in 0..items.len() {
+            let mut rest = items.to_vec();
+            let first = rest.remove(index);
+            for mut permutation in permutations(&rest) {
+                permutation.insert(0, first.clone());
+                result.push(permutation);
+            }
+        }
+        result
+    }
+
+    /// Relabel the blank nodes of a dataset with canonical identifiers, per the RDFC-1.0 algorithm
+    pub(super) fn rdfc10(quads: &[Quad], limit: usize) -> Result<Vec<Quad>, RdfError> {
+        let mut state = State { blank_node_quads: HashMap::new(), canonical_issuer: IdentifierIssuer::new("_:c14n"), budget: Cell::new(limit) };
+        for quad in quads {
+            for blank_node in blank_nodes(quad) {
+                let entry = state.blank_node_quads.entry(blank_node.clone()).or_default();
+                if !entry.iter().any(|existing| std::ptr::eq(*existing, quad)) {
+                    entry.push(quad);
+                }
+            }
+        }
+
+        let mut hash_to_blank_nodes = BTreeMap::<String, Vec<String>>::new();
+        let mut identifiers = state.blank_node_quads.keys().cloned().collect::<Vec<_>>();
+        identifiers.sort();
+        for identifier in identifiers {
+            let hash = state.hash_first_degree_quads(&identifier);
+            hash_to_blank_nodes.entry(hash).or_default().push(identifier);
+        }
+
+        let mut non_unique = Vec::new();
+        for (_, identifiers) in hash_to_blank_nodes {
+            match identifiers.as_slice() {
+                [unique] => {
+                    state.canonical_issuer.issue(Some(unique));
+                }
+                _ => non_unique.push(identifiers)
+            }
+        }
+
+        for identifiers in non_unique {
+            let mut hash_path_list = Vec::new();
+            for identifier in &identifiers {
+                if state.canonical_issuer.get(identifier).is_some() {
+                    continue;
+                }
+                let mut temporary_issuer = IdentifierIssuer::new("_:b");
+                temporary_issuer.issue(Some(identifier));
+                hash_path_list.push(state.hash_n_degree_quads(identifier, &temporary_issuer)?);
+            }
+            hash_path_list.sort_by(|(a, _), (b, _)| a.cmp(b));
+            for (_, issuer) in hash_path_list {
+                for existing in &issuer.order {
+                    state.canonical_issuer.issue(Some(existing));
+                }
+            }
+        }
+
+        let canonical = |existing: &str| state.canonical_issuer.get(existing).cloned().unwrap_or_else(|| existing.to_string());
+        Ok(quads.iter()
+            .map(|quad| Quad {
+                subject: relabel_term(&quad.subject, canonical),
+                predicate: quad.predicate.clone(),
+                object: relabel_term(&quad.object, canonical),
+                graph: quad.graph.as_ref().map(|graph| relabel_term(graph, canonical)),
+            })
+            .collect())
+    }
+}
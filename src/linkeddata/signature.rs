@@ -0,0 +1,175 @@
+//! Linked Data Signatures; The RsaSignature2017 suite that Mastodon attaches to relayed activities
+//!
+//! The signed data is the concatenation of the hex-encoded SHA-256 digests of the canonicalized signature options and of the canonicalized document (excluding its signature), signed with RSASSA-PKCS1-v1_5 using SHA-256.
+
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use chrono::{DateTime, FixedOffset, SecondsFormat, Utc};
+use rsa::{RsaPrivateKey, RsaPublicKey};
+use rsa::pkcs1::{DecodeRsaPrivateKey, DecodeRsaPublicKey};
+use rsa::pkcs1v15::{Signature, SigningKey, VerifyingKey};
+use rsa::pkcs8::{DecodePrivateKey, DecodePublicKey};
+use sha2::Sha256;
+use rsa::signature::{SignatureEncoding, Signer, Verifier};
+use serde::{Deserialize, Serialize};
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+use crate::linkeddata::LinkedData;
+use crate::linkeddata::marker_types;
+use crate::linkeddata::rdf::{BundledContexts, content_hash, DocumentLoader, IDENTITY_V1_CONTEXT, RdfError};
+
+pub const RSA_SIGNATURE_2017: &str = "RsaSignature2017";
+
+#[derive(Debug)]
+pub enum SignatureError {
+    /// The document has no `signature` property
+    MissingSignature,
+    UnsupportedSignatureType(String),
+    MalformedSignature(&'static str),
+    Canonicalization(RdfError),
+    InvalidKey(String),
+    /// The signature value does not match the document
+    Invalid,
+    Deserialization(serde_json::Error),
+    Serialization(serde_json::Error),
+}
+
+impl Display for SignatureError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SignatureError::MissingSignature => write!(f, "document is not signed"),
+            SignatureError::UnsupportedSignatureType(signature_type) => write!(f, "unsupported signature type: {}", signature_type),
+            SignatureError::MalformedSignature(reason) => write!(f, "malformed signature: {}", reason),
+            SignatureError::Canonicalization(error) => write!(f, "could not canonicalize document: {}", error),
+            SignatureError::InvalidKey(reason) => write!(f, "invalid key: {}", reason),
+            SignatureError::Invalid => write!(f, "signature does not match document"),
+            SignatureError::Deserialization(error) => write!(f, "could not deserialize document: {}", error),
+            SignatureError::Serialization(error) => write!(f, "could not serialize document: {}", error),
+        }
+    }
+}
+
+impl Error for SignatureError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SignatureError::Canonicalization(error) => Some(error),
+            SignatureError::Deserialization(error) => Some(error),
+            SignatureError::Serialization(error) => Some(error),
+            _ => None
+        }
+    }
+}
+
+impl From<RdfError> for SignatureError {
+    fn from(error: RdfError) -> Self {
+        SignatureError::Canonicalization(error)
+    }
+}
+
+/// The `signature` property of a signed document
+#[allow(non_snake_case)]    // Field names map directly to those in the emitted/received JSON
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RsaSignature2017 {
+    #[serde(rename = "type")]
+    pub signature_type: String,
+    pub creator: marker_types::URI,
+    pub created: DateTime<FixedOffset>,
+    pub signatureValue: String,
+}
+
+/// Parse an RSA public key from PEM, in either SubjectPublicKeyInfo ("BEGIN PUBLIC KEY", as used in actor documents) or PKCS#1 form
+pub fn public_key_from_pem(pem: &str) -> Result<RsaPublicKey, SignatureError> {
+    RsaPublicKey::from_public_key_pem(pem)
+        .or_else(|_| RsaPublicKey::from_pkcs1_pem(pem))
+        .map_err(|error| SignatureError::InvalidKey(error.to_string()))
+}
+
+/// Parse an RSA private key from PEM, in either PKCS#8 or PKCS#1 form
+pub fn private_key_from_pem(pem: &str) -> Result<RsaPrivateKey, SignatureError> {
+    RsaPrivateKey::from_pkcs8_pem(pem)
+        .or_else(|_| RsaPrivateKey::from_pkcs1_pem(pem))
+        .map_err(|error| SignatureError::InvalidKey(error.to_string()))
+}
+
+/// Read the signature of a document without verifying it, e.g. to look up the creator's key
+pub fn signature_of(document: &Value) -> Result<RsaSignature2017, SignatureError> {
+    let signature = document.get("signature").ok_or(SignatureError::MissingSignature)?;
+    let signature = RsaSignature2017::deserialize(signature).map_err(|_| SignatureError::MalformedSignature("signature is not an object with type, creator, created and signatureValue"))?;
+    if signature.signature_type != RSA_SIGNATURE_2017 {
+        return Err(SignatureError::UnsupportedSignatureType(signature.signature_type));
+    }
+    Ok(signature)
+}
+
+/// Compute the data to be signed; The digests of the signature options and the unsigned document
+fn signing_input(document: &Map<String, Value>, signature: &Map<String, Value>, loader: &dyn DocumentLoader) -> Result<String, SignatureError> {
+    let mut options = signature.clone();
+    options.remove("type");
+    options.remove("id");
+    options.remove("signatureValue");
+    options.insert("@context".to_string(), Value::String(IDENTITY_V1_CONTEXT.to_string()));
+
+    let mut document = document.clone();
+    document.remove("signature");
+
+    Ok(content_hash(&Value::Object(options), loader)? + &content_hash(&Value::Object(document), loader)?)
+}
+
+/// Sign a JSON-LD document, returning a copy with the `signature` property set
+pub fn sign(document: &Value, creator: &str, created: DateTime<Utc>, key: &RsaPrivateKey, loader: &dyn DocumentLoader) -> Result<Value, SignatureError> {
+    let document = document.as_object().ok_or(SignatureError::MalformedSignature("only JSON objects can be signed"))?;
+    let mut signature = Map::new();
+    signature.insert("type".to_string(), Value::String(RSA_SIGNATURE_2017.to_string()));
+    signature.insert("creator".to_string(), Value::String(creator.to_string()));
+    signature.insert("created".to_string(), Value::String(created.to_rfc3339_opts(SecondsFormat::Secs, true)));
+
+    let input = signing_input(document, &signature, loader)?;
+    let signature_value = SigningKey::<Sha256>::new(key.clone()).sign(input.as_bytes());
+    signature.insert("signatureValue".to_string(), Value::String(BASE64.encode(signature_value.to_bytes())));
+
+    let mut signed = document.clone();
+    signed.insert("signature".to_string(), Value::Object(signature));
+    Ok(Value::Object(signed))
+}
+
+/// Verify the signature of a JSON-LD document against the creator's public key
+///
+/// The document must be the JSON as received; Any re-serialization may alter the signed data.
+pub fn verify(document: &Value, key: &RsaPublicKey, loader: &dyn DocumentLoader) -> Result<RsaSignature2017, SignatureError> {
+    let parsed = signature_of(document)?;
+    let object = document.as_object().ok_or(SignatureError::MissingSignature)?;
+    let signature = object.get("signature").and_then(Value::as_object).ok_or(SignatureError::MissingSignature)?;
+
+    let signature_value = BASE64.decode(&parsed.signatureValue).map_err(|_| SignatureError::MalformedSignature("signatureValue is not base64"))?;
+    let signature_value = Signature::try_from(signature_value.as_slice()).map_err(|_| SignatureError::MalformedSignature("signatureValue is not an RSA signature"))?;
+
+    let input = signing_input(object, signature, loader)?;
+    VerifyingKey::<Sha256>::new(key.clone())
+        .verify(input.as_bytes(), &signature_value)
+        .map_err(|_| SignatureError::Invalid)?;
+    Ok(parsed)
+}
+
+impl<T: Serialize + Debug, C: Serialize + Debug> LinkedData<T, C> {
+    /// Serialize and sign this document, using the bundled context documents
+    pub fn sign_rsa2017(&self, creator: &str, key: &RsaPrivateKey) -> Result<Value, SignatureError> {
+        let document = serde_json::to_value(self).map_err(SignatureError::Serialization)?;
+        sign(&document, creator, Utc::now(), key, &BundledContexts)
+    }
+}
+
+impl<T: DeserializeOwned + Debug, C: DeserializeOwned + Debug> LinkedData<T, C> {
+    /// Verify a received document and parse it, using the bundled context documents
+    ///
+    /// `creator_key` is called with the signature's creator (usually a key ID of the form `actor#main-key`) to retrieve the matching public key.
+    pub fn from_signed_value<F>(document: Value, creator_key: F) -> Result<(Self, RsaSignature2017), SignatureError>
+        where F: FnOnce(&str) -> Result<RsaPublicKey, SignatureError>
+    {
+        let key = creator_key(&signature_of(&document)?.creator)?;
+        let signature = verify(&document, &key, &BundledContexts)?;
+        let data = serde_json::from_value(document).map_err(SignatureError::Deserialization)?;
+        Ok((data, signature))
+    }
+}
@@ -0,0 +1,198 @@
+//! XML Schema datatypes; `xsd:dateTime` as used by the date properties of ActivityStreams
+//!
+//! Parsing accepts the whole xsd lexical space, which RFC 3339 parsers reject in part, and some common deviations from it:
+//! A lowercase `t` or `z`, and more than nine fractional digits (which are truncated).
+
+use std::cmp::Ordering;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, SecondsFormat, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use crate::linkeddata::deserialize::assumed_offset;
+
+/// An `xsd:dateTime`, preserving its lexical form for serialization
+///
+/// Dates compare by the instant they denote; Dates without an offset are taken to be at the offset assumed when parsing them:
+/// UTC, unless parsed with [`XsdDateTime::parse_with_offset`] or within [`with_assumed_offset`](crate::linkeddata::deserialize::with_assumed_offset).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct XsdDateTime {
+    lexical: String,
+    local: NaiveDateTime,
+    offset: Option<FixedOffset>,
+    assumed: FixedOffset,
+}
+
+impl XsdDateTime {
+    /// Parse a date, taking a missing offset to be `assumed`
+    pub fn parse_with_offset(lexical: &str, assumed: FixedOffset) -> Result<Self, String> {
+        match parse(lexical.trim()) {
+            Some((local, offset)) => Ok(XsdDateTime { lexical: lexical.to_string(), local, offset, assumed }),
+            None => Err(format!("invalid xsd:dateTime `{}`", lexical))
+        }
+    }
+
+    /// This date, taking a missing offset to be `assumed` rather than the offset assumed when parsing it
+    pub fn assuming_offset(mut self, assumed: FixedOffset) -> Self {
+        self.assumed = assumed;
+        self
+    }
+
+    /// The form this date was parsed from
+    pub fn lexical(&self) -> &str {
+        &self.lexical
+    }
+
+    /// The date and time as written, without its offset
+    pub fn naive_local(&self) -> NaiveDateTime {
+        self.local
+    }
+
+    /// The offset, if the date has one
+    pub fn offset(&self) -> Option<FixedOffset> {
+        self.offset
+    }
+
+    /// This date, taking a missing offset to be `assumed`
+    pub fn with_assumed_offset(&self, assumed: FixedOffset) -> DateTime<FixedOffset> {
+        let offset = self.offset.unwrap_or(assumed);
+        // Only fails beyond the range of chrono's dates, where the offset is applied the wrong way round instead
+        offset.from_local_datetime(&self.local).single()
+            .unwrap_or_else(|| DateTime::from_naive_utc_and_offset(self.local, offset))
+    }
+
+    /// This date, taking a missing offset to be the one assumed when parsing it
+    pub fn to_fixed_offset(&self) -> DateTime<FixedOffset> {
+        self.with_assumed_offset(self.assumed)
+    }
+
+    pub fn to_utc(&self) -> DateTime<Utc> {
+        self.to_fixed_offset().with_timezone(&Utc)
+    }
+}
+
+/// Parse `digits` decimal digits at the start of `input`
+fn number(input: &str, digits: usize) -> Option<(u32, &str)> {
+    let (number, rest) = input.split_at_checked(digits)?;
+    if !number.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    Some((number.parse().ok()?, rest))
+}
+
+fn parse(lexical: &str) -> Option<(NaiveDateTime, Option<FixedOffset>)> {
+    let (negative, input) = match lexical.strip_prefix('-') {
+        Some(input) => (true, input),
+        None => (false, lexical)
+    };
+    // Years have at least four digits, and no leading zero if they have more
+    let year_digits = input.find(|c: char| !c.is_ascii_digit())?;
+    if year_digits < 4 || (year_digits > 4 && input.starts_with('0')) {
+        return None;
+    }
+    let year = input[..year_digits].parse::<i32>().ok()?;
+    let input = input[year_digits..].strip_prefix('-')?;
+    let (month, input) = number(input, 2)?;
+    let (day, input) = number(input.strip_prefix('-')?, 2)?;
+    let input = input.strip_prefix(['T', 't'])?;
+    let (hour, input) = number(input, 2)?;
+    let (minute, input) = number(input.strip_prefix(':')?, 2)?;
+    let (second, input) = number(input.strip_prefix(':')?, 2)?;
+    let (nanosecond, input) = match input.strip_prefix('.') {
+        Some(fraction) => {
+            let digits = fraction.find(|c: char| !c.is_ascii_digit()).unwrap_or(fraction.len());
+            if digits == 0 {
+                return None;
+            }
+            // Digits beyond nanoseconds are truncated
+            let nanosecond = fraction[..digits.min(9)].parse::<u32>().ok()? * 10u32.pow(9 - digits.min(9) as u32);
+            (nanosecond, &fraction[digits..])
+        }
+        None => (0, input)
+    };
+    let offset = match input {
+        "" => None,
+        "Z" | "z" => Some(FixedOffset::east_opt(0)?),
+        offset => {
+            let (sign, offset) = match offset.split_at_checked(1)? {
+                ("+", offset) => (1, offset),
+                ("-", offset) => (-1, offset),
+                _ => return None
+            };
+            let (hours, offset) = number(offset, 2)?;
+            let (minutes, offset) = number(offset.strip_prefix(':')?, 2)?;
+            if !offset.is_empty() || minutes > 59 || hours > 14 || (hours == 14 && minutes > 0) {
+                return None;
+            }
+            Some(FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60) as i32)?)
+        }
+    };
+    let date = NaiveDate::from_ymd_opt(if negative { -year } else { year }, month, day)?;
+    // 24:00:00 is the first instant of the following day
+    let local = match (hour, minute, second, nanosecond) {
+        (24, 0, 0, 0) => date.succ_opt()?.and_time(NaiveTime::MIN),
+        (hour, minute, second, nanosecond) if second < 60 => date.and_time(NaiveTime::from_hms_nano_opt(hour, minute, second, nanosecond)?),
+        _ => return None
+    };
+    Some((local, offset))
+}
+
+impl FromStr for XsdDateTime {
+    type Err = String;
+
+    fn from_str(lexical: &str) -> Result<Self, Self::Err> {
+        XsdDateTime::parse_with_offset(lexical, assumed_offset())
+    }
+}
+
+impl TryFrom<String> for XsdDateTime {
+    type Error = String;
+
+    fn try_from(lexical: String) -> Result<Self, Self::Error> {
+        XsdDateTime::parse_with_offset(&lexical, assumed_offset())
+    }
+}
+
+impl From<XsdDateTime> for String {
+    fn from(date: XsdDateTime) -> Self {
+        date.lexical
+    }
+}
+
+impl<Tz: TimeZone> From<DateTime<Tz>> for XsdDateTime {
+    fn from(date: DateTime<Tz>) -> Self {
+        let date = date.fixed_offset();
+        XsdDateTime {
+            lexical: date.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+            local: date.naive_local(),
+            offset: Some(*date.offset()),
+            assumed: *date.offset(),
+        }
+    }
+}
+
+impl Display for XsdDateTime {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.lexical)
+    }
+}
+
+impl PartialEq for XsdDateTime {
+    fn eq(&self, other: &Self) -> bool {
+        self.to_utc() == other.to_utc()
+    }
+}
+
+impl Eq for XsdDateTime {}
+
+impl PartialOrd for XsdDateTime {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for XsdDateTime {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.to_utc().cmp(&other.to_utc())
+    }
+}
@@ -1,7 +1,6 @@
 use serde::{Deserialize, Serialize};
-use crate::linkeddata::util::FoldedSlice;
+use turtleactivitypub::linkeddata::util::FoldedSlice;
 
-mod linkeddata;
 
 #[derive(Serialize, Deserialize, Debug)]
 struct Test {
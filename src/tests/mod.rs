@@ -1,7 +1,34 @@
 use std::error::Error;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use chrono::Utc;
 use crate::linkeddata::{AsLinkedData, LinkedData};
 use serde::{Serialize, Deserialize};
+use crate::activitypub::collection::{CollectionItems, CollectionPager, CursorPage};
+use crate::activitypub::inbox::{dispatch, InboxError, InboxHandler};
+use crate::activitypub::outbox::{OutboxError, OutboxStore};
+use crate::activitypub::outbox;
+use crate::activitypub::delivery::{Audience, Deliverer, Delivery, DeliveryError, DeliveryQueue, InMemoryQueue};
+use crate::activitypub::addressing::PUBLIC;
+use crate::activitypub::webfinger::{host_meta, Jrd, local_username, parse_handle, resolve_handle, webfinger_url};
+use crate::activitypub::validate::{has_errors, Rule, Severity, ValidationProfile};
+use crate::activitypub::update::{apply_update, removed_properties, UpdateError, UpdateMode};
+use crate::activitypub::nodeinfo::{NodeInfo, NodeInfoVersion, Software, Usage};
+use crate::activitypub::hydrate::{HydrateProperty, Hydrator};
+use crate::activitypub::http_signature::{digest_header, HttpSignatureKey, SignatureHeader};
+use crate::activitypub::resolver::{ACTIVITY_JSON_ACCEPT, HttpResolver, InMemoryResolver, ResolveError};
 use crate::linkeddata::activity_streams::{MaybeObject, TaggedObject};
+use crate::linkeddata::activity_streams::objects::{Create, Follow, Update};
+use crate::linkeddata::activity_streams::properties::{MaybeCollection, Percentage, PositiveFloat, TaggedCollection, TaggedCollectionPage};
+use crate::linkeddata::borrowed::{MaybeObjectRef, ObjectRef};
+use crate::linkeddata::deserialize;
+use crate::linkeddata::diff::{Change, Diff, PatchError, IMMUTABLE_PROPERTIES};
+use crate::linkeddata::rdf;
+use crate::linkeddata::xsd::XsdDateTime;
+use crate::linkeddata::rdf::{BundledContexts, Dataset, Quad, RdfError, Term};
+use crate::linkeddata::signature;
+use crate::linkeddata::signature::SignatureError;
+use crate::linkeddata::util::{FoldedSlice, SemanticEq, WhenEmpty};
 
 #[derive(Debug, Serialize, Deserialize)]
 struct TestLD {
@@ -21,7 +48,1357 @@ impl AsLinkedData for TestLD {
 #[test]
 fn test_parse() -> Result<(), Box<dyn Error>> {
     let resp = include_str!("./data.json");
-    let value = serde_json::from_str::<LinkedData<TaggedObject, serde_json::Value>>(&*resp).unwrap();
+    let value = serde_json::from_str::<LinkedData<TaggedObject, serde_json::Value>>(resp).unwrap();
     println!("{:#?}", value);
     Ok(())
 }
+
+/// Wrap a plain struct into LinkedData and check the context is emitted alongside its fields
+#[test]
+fn test_into_linked_data() -> Result<(), Box<dyn Error>> {
+    let value = TestLD { a: "Hello!".to_string() }.into_linked_data();
+    let json = serde_json::to_value(&value)?;
+    assert_eq!(json, serde_json::json!({"a": "Hello!", "@context": "TEST"}));
+    Ok(())
+}
+
+/// Sign ./create.json with RsaSignature2017, then verify it and check that tampering is detected
+#[test]
+fn test_rsa_signature_2017() -> Result<(), Box<dyn Error>> {
+    let key = signature::private_key_from_pem(include_str!("./test-key.pem"))?;
+    let public_key = signature::public_key_from_pem(include_str!("./test-key.pub.pem"))?;
+    let document = serde_json::from_str::<serde_json::Value>(include_str!("./create.json"))?;
+    let creator = "https://mastodon.example/users/alice#main-key";
+
+    let signed = signature::sign(&document, creator, Utc::now(), &key, &BundledContexts)?;
+    let (value, signature) = LinkedData::<TaggedObject, serde_json::Value>::from_signed_value(signed.clone(), |key_id| {
+        assert_eq!(key_id, creator);
+        Ok(public_key.clone())
+    })?;
+    assert!(matches!(value.data, TaggedObject::Create(_)));
+    assert_eq!(signature.creator, creator);
+
+    let mut tampered = signed;
+    tampered["object"]["content"] = serde_json::Value::String("<p>Goodbye world</p>".to_string());
+    assert!(matches!(signature::verify(&tampered, &public_key, &BundledContexts), Err(SignatureError::Invalid)));
+    Ok(())
+}
+
+/// Verify a signed Delete of the form Mastodon relays; Known answer whose canonical N-Quads were derived by hand and whose signature was made with OpenSSL, independently of this crate
+#[test]
+fn test_rsa_signature_2017_known_answer() -> Result<(), Box<dyn Error>> {
+    let public_key = signature::public_key_from_pem(include_str!("./test-key.pub.pem"))?;
+    let document = serde_json::from_str::<serde_json::Value>(include_str!("./rsa-signature-2017.json"))?;
+
+    let options = serde_json::json!({
+        "@context": "https://w3id.org/identity/v1",
+        "creator": "https://mastodon.example/users/alice#main-key",
+        "created": "2024-05-01T12:00:00Z"
+    });
+    assert_eq!(rdf::canonical_nquads(&options, &BundledContexts)?, concat!(
+        "_:c14n0 <http://purl.org/dc/terms/created> \"2024-05-01T12:00:00Z\"^^<http://www.w3.org/2001/XMLSchema#dateTime> .\n",
+        "_:c14n0 <http://purl.org/dc/terms/creator> <https://mastodon.example/users/alice#main-key> .\n",
+    ));
+    let mut unsigned = document.clone();
+    unsigned.as_object_mut().unwrap().remove("signature");
+    assert_eq!(rdf::canonical_nquads(&unsigned, &BundledContexts)?, concat!(
+        "<https://mastodon.example/users/alice#delete> <http://www.w3.org/1999/02/22-rdf-syntax-ns#type> <https://www.w3.org/ns/activitystreams#Delete> .\n",
+        "<https://mastodon.example/users/alice#delete> <https://www.w3.org/ns/activitystreams#actor> <https://mastodon.example/users/alice> .\n",
+        "<https://mastodon.example/users/alice#delete> <https://www.w3.org/ns/activitystreams#object> <https://mastodon.example/users/alice> .\n",
+        "<https://mastodon.example/users/alice#delete> <https://www.w3.org/ns/activitystreams#to> <https://www.w3.org/ns/activitystreams#Public> .\n",
+    ));
+
+    let signature = signature::verify(&document, &public_key, &BundledContexts)?;
+    assert_eq!(signature.creator, "https://mastodon.example/users/alice#main-key");
+    let mut tampered = document;
+    tampered["object"] = serde_json::json!("https://mastodon.example/users/bob");
+    assert!(matches!(signature::verify(&tampered, &public_key, &BundledContexts), Err(SignatureError::Invalid)));
+    Ok(())
+}
+
+/// Semantically identical documents must canonicalize identically, regardless of key order, single-value arrays and blank node placement
+#[test]
+fn test_content_hash() -> Result<(), Box<dyn Error>> {
+    let document = serde_json::from_str::<serde_json::Value>(include_str!("./create.json"))?;
+    let mut reordered = serde_json::Map::new();
+    for (key, value) in document.as_object().unwrap().iter().rev() {
+        reordered.insert(key.clone(), value.clone());
+    }
+    reordered.insert("to".to_string(), serde_json::json!("https://www.w3.org/ns/activitystreams#Public"));
+    let reordered = serde_json::Value::Object(reordered);
+    assert_eq!(rdf::content_hash(&document, &BundledContexts)?, rdf::content_hash(&reordered, &BundledContexts)?);
+
+    let mut changed = document.clone();
+    changed["object"]["content"] = serde_json::json!("<p>Goodbye world</p>");
+    assert_ne!(rdf::content_hash(&document, &BundledContexts)?, rdf::content_hash(&changed, &BundledContexts)?);
+    Ok(())
+}
+
+/// Check the canonical N-Quads of a small document with anonymous (blank node) objects
+#[test]
+fn test_canonical_nquads() -> Result<(), Box<dyn Error>> {
+    let document = serde_json::json!({
+        "@context": "https://www.w3.org/ns/activitystreams",
+        "id": "https://example.invalid/note",
+        "type": "Note",
+        "tag": [
+            {"type": "Mention", "href": "https://example.invalid/b", "name": "@b"},
+            {"type": "Mention", "href": "https://example.invalid/a", "name": "@a"}
+        ],
+        "contentMap": {"en": "Hi\n\"there\""}
+    });
+    let expected = concat!(
+        "<https://example.invalid/note> <http://www.w3.org/1999/02/22-rdf-syntax-ns#type> <https://www.w3.org/ns/activitystreams#Note> .\n",
+        "<https://example.invalid/note> <https://www.w3.org/ns/activitystreams#content> \"Hi\\n\\\"there\\\"\"@en .\n",
+        "<https://example.invalid/note> <https://www.w3.org/ns/activitystreams#tag> _:c14n0 .\n",
+        "<https://example.invalid/note> <https://www.w3.org/ns/activitystreams#tag> _:c14n1 .\n",
+        "_:c14n0 <http://www.w3.org/1999/02/22-rdf-syntax-ns#type> <https://www.w3.org/ns/activitystreams#Mention> .\n",
+        "_:c14n0 <https://www.w3.org/ns/activitystreams#href> <https://example.invalid/b> .\n",
+        "_:c14n0 <https://www.w3.org/ns/activitystreams#name> \"@b\" .\n",
+        "_:c14n1 <http://www.w3.org/1999/02/22-rdf-syntax-ns#type> <https://www.w3.org/ns/activitystreams#Mention> .\n",
+        "_:c14n1 <https://www.w3.org/ns/activitystreams#href> <https://example.invalid/a> .\n",
+        "_:c14n1 <https://www.w3.org/ns/activitystreams#name> \"@a\" .\n",
+    );
+    assert_eq!(rdf::canonical_nquads(&document, &BundledContexts)?, expected);
+    Ok(())
+}
+
+/// A ring of indistinguishable blank nodes requires N-degree hashing, and must respect the canonicalization work limit
+#[test]
+fn test_canonicalization_limit() {
+    let ring = (0..6)
+        .map(|index| Quad {
+            subject: Term::BlankNode(format!("_:n{}", index)),
+            predicate: Term::Iri("https://example.invalid/next".to_string()),
+            object: Term::BlankNode(format!("_:n{}", (index + 1) % 6)),
+            graph: None,
+        })
+        .collect();
+    let dataset = Dataset { quads: ring };
+    assert!(matches!(dataset.canonicalize_with_limit(1), Err(RdfError::CanonicalizationLimit)));
+    let canonical = dataset.canonicalize().unwrap();
+    assert_eq!(canonical.quads.len(), 6);
+    assert!(canonical.to_nquads().starts_with("_:c14n0 <https://example.invalid/next> _:c14n"));
+}
+
+/// Resolve URI references through the in-memory resolver, rejecting documents with a foreign id
+#[test]
+fn test_in_memory_resolver() -> Result<(), Box<dyn Error>> {
+    let mut resolver = InMemoryResolver::new();
+    resolver.insert("https://EXAMPLE_ID.INVALID", serde_json::from_str(include_str!("./data.json"))?);
+    resolver.insert("https://example.invalid/spoofed", serde_json::json!({"id": "https://elsewhere.invalid/note", "type": "Note"}));
+
+    let note = MaybeObject::URI("https://EXAMPLE_ID.INVALID".to_string()).resolve_object(&resolver)?;
+    assert!(matches!(note, TaggedObject::Note(_)));
+    assert!(matches!(MaybeObject::URI("https://example.invalid/spoofed".to_string()).resolve_object(&resolver), Err(ResolveError::OriginMismatch { .. })));
+    assert!(matches!(MaybeObject::URI("https://example.invalid/missing".to_string()).resolve_object(&resolver), Err(ResolveError::NotFound(_))));
+    assert_eq!(resolver.fetched().len(), 3);
+    Ok(())
+}
+
+fn thread_resolver() -> Result<InMemoryResolver, Box<dyn Error>> {
+    let mut resolver = InMemoryResolver::new();
+    resolver.insert("https://example.invalid/a", serde_json::json!({"id": "https://example.invalid/a", "type": "Note", "inReplyTo": "https://example.invalid/b", "attributedTo": "https://example.invalid/alice"}));
+    resolver.insert("https://example.invalid/b", serde_json::json!({"id": "https://example.invalid/b", "type": "Note", "inReplyTo": "https://example.invalid/a", "attributedTo": "https://example.invalid/alice"}));
+    resolver.insert("https://example.invalid/alice", serde_json::json!({"id": "https://example.invalid/alice", "type": "Person", "name": "Alice"}));
+    Ok(resolver)
+}
+
+fn in_reply_to(object: &TaggedObject) -> &MaybeObject {
+    &object.object_fields().unwrap().inReplyTo.as_deref().unwrap()[0]
+}
+
+/// Hydrate a reply cycle; The back-reference to the root must be left as a URI, and the shared author fetched once
+#[test]
+fn test_hydrate_thread() -> Result<(), Box<dyn Error>> {
+    let resolver = thread_resolver()?;
+    let mut note = MaybeObject::URI("https://example.invalid/a".to_string()).resolve_object(&resolver)?;
+    let errors = note.hydrate(&resolver, 8, HydrateProperty::THREAD);
+    assert!(errors.is_empty());
+
+    let MaybeObject::TaggedObject(reply) = in_reply_to(&note) else { panic!("inReplyTo was not hydrated") };
+    assert_eq!(reply.id(), Some("https://example.invalid/b"));
+    assert!(matches!(in_reply_to(reply), MaybeObject::URI(uri) if uri == "https://example.invalid/a"));
+    assert!(matches!(&reply.object_fields().unwrap().attributedTo.as_deref().unwrap()[0], MaybeObject::TaggedObject(TaggedObject::Person(_))));
+    assert_eq!(resolver.fetched(), ["https://example.invalid/a", "https://example.invalid/b", "https://example.invalid/alice"]);
+    Ok(())
+}
+
+#[test]
+fn test_hydrate_depth_and_budget() -> Result<(), Box<dyn Error>> {
+    let resolver = thread_resolver()?;
+    let mut note = MaybeObject::URI("https://example.invalid/a".to_string()).resolve_object(&resolver)?;
+    assert!(note.hydrate(&resolver, 1, &[HydrateProperty::InReplyTo]).is_empty());
+    let MaybeObject::TaggedObject(reply) = in_reply_to(&note) else { panic!("inReplyTo was not hydrated") };
+    assert!(matches!(in_reply_to(reply), MaybeObject::URI(_)));
+    assert!(matches!(&note.object_fields().unwrap().attributedTo.as_deref().unwrap()[0], MaybeObject::URI(_)));
+
+    let mut note = MaybeObject::URI("https://example.invalid/a".to_string()).resolve_object(&resolver)?;
+    let mut hydrator = Hydrator::new(&resolver, HydrateProperty::THREAD).with_fetch_budget(1);
+    hydrator.hydrate(&mut note, 8);
+    assert_eq!(hydrator.remaining_budget(), 0);
+    assert!(!hydrator.errors().is_empty());
+    assert!(hydrator.errors().iter().all(|(uri, error)| uri == "https://example.invalid/alice" && matches!(error, ResolveError::FetchBudgetExhausted)));
+    assert!(matches!(in_reply_to(&note), MaybeObject::TaggedObject(_)));
+    Ok(())
+}
+
+/// Flatten ./create.json, then restore it by hydrating from the extracted objects
+#[test]
+fn test_flatten() -> Result<(), Box<dyn Error>> {
+    let mut create = serde_json::from_str::<TaggedObject>(include_str!("./create.json"))?;
+    let extracted = create.flatten(HydrateProperty::ALL);
+    assert_eq!(extracted.len(), 1);
+    let note = &extracted["https://mastodon.example/users/alice/statuses/1"];
+    // The attachment has no id, so it stays embedded
+    assert!(matches!(&note.object_fields().unwrap().attachment.as_deref().unwrap()[0], MaybeObject::TaggedObject(TaggedObject::Document(_))));
+    let object = &create.activity_fields().unwrap().object.as_deref().unwrap()[0];
+    assert!(matches!(object, MaybeObject::URI(uri) if uri == "https://mastodon.example/users/alice/statuses/1"));
+
+    let mut resolver = InMemoryResolver::new();
+    for (id, object) in &extracted {
+        resolver.insert(id, serde_json::to_value(object)?);
+    }
+    assert!(create.hydrate(&resolver, 1, &[HydrateProperty::Object]).is_empty());
+    assert!(matches!(&create.activity_fields().unwrap().object.as_deref().unwrap()[0], MaybeObject::TaggedObject(TaggedObject::Note(_))));
+    assert!(create.flatten(HydrateProperty::ALL).contains_key("https://mastodon.example/users/alice/statuses/1"));
+    Ok(())
+}
+
+fn outbox_resolver() -> InMemoryResolver {
+    let mut resolver = InMemoryResolver::new();
+    resolver.insert("https://example.invalid/outbox", serde_json::json!({
+        "id": "https://example.invalid/outbox", "type": "OrderedCollection", "totalItems": 3,
+        "first": "https://example.invalid/outbox?page=1", "last": "https://example.invalid/outbox?page=2"
+    }));
+    resolver.insert("https://example.invalid/outbox?page=1", serde_json::json!({
+        "id": "https://example.invalid/outbox?page=1", "type": "OrderedCollectionPage", "startIndex": 0,
+        "partOf": "https://example.invalid/outbox", "next": "https://example.invalid/outbox?page=2",
+        "orderedItems": ["https://example.invalid/1", "https://example.invalid/2"]
+    }));
+    resolver.insert("https://example.invalid/outbox?page=2", serde_json::json!({
+        "id": "https://example.invalid/outbox?page=2", "type": "OrderedCollectionPage", "startIndex": 2,
+        "partOf": "https://example.invalid/outbox", "prev": "https://example.invalid/outbox?page=1",
+        "orderedItems": ["https://example.invalid/3"]
+    }));
+    resolver
+}
+
+fn collect_indexed(mut items: CollectionItems) -> Result<Vec<(Option<u64>, String)>, ResolveError> {
+    let mut collected = Vec::new();
+    while let Some(item) = items.next() {
+        match item? {
+            MaybeObject::URI(uri) => collected.push((items.index(), uri)),
+            item => panic!("unexpected item {:?}", item)
+        }
+    }
+    Ok(collected)
+}
+
+/// Walk a paged outbox forwards and backwards
+#[test]
+fn test_collection_items() -> Result<(), Box<dyn Error>> {
+    let resolver = outbox_resolver();
+    let outbox = MaybeCollection::URI("https://example.invalid/outbox".to_string());
+    let forward = collect_indexed(outbox.iter_items(&resolver)?)?;
+    assert_eq!(forward, [
+        (Some(0), "https://example.invalid/1".to_string()),
+        (Some(1), "https://example.invalid/2".to_string()),
+        (Some(2), "https://example.invalid/3".to_string()),
+    ]);
+
+    let outbox = MaybeCollection::URI("https://example.invalid/outbox".to_string()).resolve_collection(&resolver)?;
+    let mut backward = collect_indexed(outbox.iter_items_rev(&resolver))?;
+    backward.reverse();
+    assert_eq!(forward, backward);
+    Ok(())
+}
+
+/// Inline pages, page cycles, and failing page fetches
+#[test]
+fn test_collection_items_edge_cases() -> Result<(), Box<dyn Error>> {
+    let resolver = outbox_resolver();
+    let inline = serde_json::from_value::<TaggedCollection>(serde_json::json!({
+        "type": "Collection",
+        "first": {"id": "https://example.invalid/page", "type": "CollectionPage", "items": ["https://example.invalid/a"], "next": "https://example.invalid/page"}
+    }))?;
+    let items = inline.iter_items(&resolver).collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(items.len(), 1);
+    assert!(resolver.fetched().is_empty());
+
+    let broken = serde_json::from_value::<TaggedCollection>(serde_json::json!({"type": "OrderedCollection", "first": "https://example.invalid/missing"}))?;
+    let mut items = broken.iter_items(&resolver);
+    assert!(matches!(items.next(), Some(Err(ResolveError::NotFound(_)))));
+    assert!(items.next().is_none());
+
+    // Indices past u64::MAX are unknown rather than overflowing
+    let page = serde_json::json!({
+        "type": "OrderedCollectionPage",
+        "startIndex": u64::MAX,
+        "orderedItems": ["https://example.invalid/a", "https://example.invalid/b"]
+    });
+    let mut items = serde_json::from_value::<TaggedCollection>(page.clone())?.iter_items(&resolver);
+    items.next().transpose()?;
+    assert_eq!(items.index(), Some(u64::MAX));
+    items.next().transpose()?;
+    assert_eq!(items.index(), None);
+    let mut items = serde_json::from_value::<TaggedCollection>(page)?.iter_items_rev(&resolver);
+    items.next().transpose()?;
+    assert_eq!(items.index(), None);
+    Ok(())
+}
+
+/// Serve a collection with offset paging, then walk it with CollectionItems
+#[test]
+fn test_collection_pager_offsets() -> Result<(), Box<dyn Error>> {
+    let notes = (1..=5).map(|n| format!("https://example.invalid/notes/{}", n)).collect::<Vec<_>>();
+    let pager = CollectionPager::new("https://example.invalid/outbox", 2);
+
+    let collection = serde_json::to_value(pager.offset_collection(notes.len() as u64))?;
+    assert_eq!(collection["type"], "OrderedCollection");
+    assert_eq!(collection["totalItems"], 5);
+    assert_eq!(collection["first"], "https://example.invalid/outbox?offset=0");
+    assert_eq!(collection["last"], "https://example.invalid/outbox?offset=4");
+
+    let last = serde_json::to_value(pager.slice_page(&notes, 4, |note| MaybeObject::URI(note.clone())))?;
+    assert_eq!(last["id"], "https://example.invalid/outbox?offset=4");
+    assert_eq!(last["partOf"], "https://example.invalid/outbox");
+    assert_eq!(last["startIndex"], 4);
+    assert_eq!(last["prev"], "https://example.invalid/outbox?offset=2");
+    assert!(last["next"].is_null());
+    assert_eq!(last["orderedItems"], serde_json::json!("https://example.invalid/notes/5"));
+
+    let mut resolver = InMemoryResolver::new();
+    resolver.insert("https://example.invalid/outbox", collection);
+    for offset in [0, 2, 4] {
+        let page = pager.offset_page(notes.iter().cloned().map(MaybeObject::URI), offset);
+        resolver.insert(&pager.offset_page_id(offset), serde_json::to_value(page)?);
+    }
+    let expected = notes.iter().enumerate().map(|(index, note)| (Some(index as u64), note.clone())).collect::<Vec<_>>();
+    let outbox = MaybeCollection::URI("https://example.invalid/outbox".to_string());
+    assert_eq!(collect_indexed(outbox.iter_items(&resolver)?)?, expected);
+    let outbox = MaybeCollection::URI("https://example.invalid/outbox".to_string()).resolve_collection(&resolver)?;
+    let mut backward = collect_indexed(outbox.iter_items_rev(&resolver))?;
+    backward.reverse();
+    assert_eq!(backward, expected);
+
+    // Offsets from clients may be anything
+    let past_end = serde_json::to_value(pager.slice_page(&notes, u64::MAX, |note| MaybeObject::URI(note.clone())))?;
+    assert_eq!(past_end["startIndex"], u64::MAX);
+    assert!(past_end["next"].is_null());
+    assert_eq!(past_end["orderedItems"], serde_json::json!([]));
+    let past_end = serde_json::to_value(pager.offset_page(notes.iter().cloned().map(MaybeObject::URI), u64::MAX - 1))?;
+    assert!(past_end["next"].is_null());
+    Ok(())
+}
+
+#[test]
+fn test_collection_pager_cursors() -> Result<(), Box<dyn Error>> {
+    let pager = CollectionPager::new("https://example.invalid/users/alice/followers", 10);
+    let collection = serde_json::to_value(pager.cursor_collection(None, None))?;
+    assert_eq!(collection["first"], "https://example.invalid/users/alice/followers?page=true");
+    assert!(collection["last"].is_null());
+
+    let page = pager.cursor_page(Some("42"), |cursor, limit| {
+        assert_eq!((cursor, limit), (Some("42"), 10));
+        CursorPage { items: vec![MaybeObject::URI("https://example.invalid/users/bob".to_string())], next: Some("7 8".to_string()), prev: None }
+    });
+    let page = serde_json::to_value(page)?;
+    assert_eq!(page["id"], "https://example.invalid/users/alice/followers?page=true&cursor=42");
+    assert_eq!(page["next"], "https://example.invalid/users/alice/followers?page=true&cursor=7+8");
+    assert!(page["startIndex"].is_null());
+    Ok(())
+}
+
+/// Ordered collections accept `items` as well as `orderedItems`, preferring and only emitting `orderedItems`
+#[test]
+fn test_ordered_items() -> Result<(), Box<dyn Error>> {
+    let page = serde_json::from_value::<TaggedCollectionPage>(serde_json::json!({
+        "type": "OrderedCollectionPage", "startIndex": 0, "items": ["https://example.invalid/1", "https://example.invalid/2"]
+    }))?;
+    assert!(page.is_ordered());
+    assert_eq!(page.ordered_items().map(<[_]>::len), Some(2));
+    let page = serde_json::to_value(page)?;
+    assert_eq!(page["orderedItems"], serde_json::json!(["https://example.invalid/1", "https://example.invalid/2"]));
+    assert!(page.get("items").is_none());
+
+    let page = serde_json::from_value::<TaggedCollectionPage>(serde_json::json!({
+        "type": "OrderedCollectionPage", "items": ["https://example.invalid/1"], "orderedItems": ["https://example.invalid/2"]
+    }))?;
+    assert_eq!(page.ordered_items(), Some(&[MaybeObject::URI("https://example.invalid/2".to_string())][..]));
+
+    let collection = serde_json::from_value::<TaggedCollection>(serde_json::json!({"type": "Collection", "items": ["https://example.invalid/1"]}))?;
+    assert!(!collection.is_ordered());
+    assert!(collection.ordered_items().is_none());
+    assert_eq!(collection.items().map(<[_]>::len), Some(1));
+    Ok(())
+}
+
+/// Records side effects and handled activities
+#[derive(Default)]
+struct RecordingInbox {
+    log: Vec<String>,
+}
+
+impl InboxHandler for RecordingInbox {
+    type Error = InboxError;
+
+    fn is_local(&self, id: &str) -> bool {
+        id.starts_with("https://local.invalid/")
+    }
+
+    fn pending_follow(&self, follow: &str) -> Option<String> {
+        match follow {
+            "https://local.invalid/follows/1" => Some("https://remote.invalid/bob".to_string()),
+            "https://local.invalid/follows/2" => Some("https://remote.invalid/carol".to_string()),
+            _ => None
+        }
+    }
+
+    fn add_following(&mut self, follow: &str, followed: &str) -> Result<(), InboxError> {
+        self.log.push(format!("following {} {}", follow, followed));
+        Ok(())
+    }
+
+    fn add_to_collection(&mut self, actor: &str, target: &str, object: &str) -> Result<(), InboxError> {
+        self.log.push(format!("add {} {} {}", actor, target, object));
+        Ok(())
+    }
+
+    fn remove_follower(&mut self, actor: &str, follower: &str) -> Result<(), InboxError> {
+        self.log.push(format!("unfollowed {} {}", actor, follower));
+        Ok(())
+    }
+
+    fn add_like(&mut self, object: &str, like: &str) -> Result<(), InboxError> {
+        self.log.push(format!("like {} {}", object, like));
+        Ok(())
+    }
+
+    fn remove_like(&mut self, object: &str, like: &str) -> Result<(), InboxError> {
+        self.log.push(format!("unlike {} {}", object, like));
+        Ok(())
+    }
+
+    fn delete_object(&mut self, object: &str) -> Result<(), InboxError> {
+        self.log.push(format!("delete {}", object));
+        Ok(())
+    }
+
+    fn on_create(&mut self, create: &Create) -> Result<(), InboxError> {
+        self.log.push(format!("create {}", create.0.object_fields.id.as_deref().unwrap_or_default()));
+        Ok(())
+    }
+
+    fn on_follow(&mut self, follow: &Follow) -> Result<(), InboxError> {
+        self.log.push(format!("follow request {}", follow.0.object_fields.id.as_deref().unwrap_or_default()));
+        Ok(())
+    }
+}
+
+fn received(activity: serde_json::Value) -> Result<LinkedData<TaggedObject, serde_json::Value>, serde_json::Error> {
+    let mut document = activity;
+    document["@context"] = serde_json::json!("https://www.w3.org/ns/activitystreams");
+    serde_json::from_value(document)
+}
+
+#[test]
+fn test_inbox_dispatch() -> Result<(), Box<dyn Error>> {
+    let mut inbox = RecordingInbox::default();
+    dispatch(&mut inbox, &serde_json::from_str::<LinkedData<TaggedObject, serde_json::Value>>(include_str!("./create.json"))?)?;
+    dispatch(&mut inbox, &received(serde_json::json!({
+        "id": "https://remote.invalid/follows/1", "type": "Follow", "actor": "https://remote.invalid/bob", "object": "https://local.invalid/alice"
+    }))?)?;
+    dispatch(&mut inbox, &received(serde_json::json!({
+        "id": "https://remote.invalid/accepts/1", "type": "Accept", "actor": "https://remote.invalid/bob",
+        "object": {"id": "https://local.invalid/follows/1", "type": "Follow", "actor": "https://local.invalid/alice", "object": "https://remote.invalid/bob"}
+    }))?)?;
+    let like = serde_json::json!({"id": "https://remote.invalid/likes/1", "type": "Like", "actor": "https://remote.invalid/bob", "object": "https://local.invalid/notes/1"});
+    dispatch(&mut inbox, &received(like.clone())?)?;
+    dispatch(&mut inbox, &received(serde_json::json!({"id": "https://remote.invalid/undo/1", "type": "Undo", "actor": "https://remote.invalid/bob", "object": like}))?)?;
+    dispatch(&mut inbox, &received(serde_json::json!({
+        "id": "https://remote.invalid/undo/2", "type": "Undo", "actor": "https://remote.invalid/bob",
+        "object": {"id": "https://remote.invalid/follows/1", "type": "Follow", "actor": "https://remote.invalid/bob", "object": "https://local.invalid/alice"}
+    }))?)?;
+    dispatch(&mut inbox, &received(serde_json::json!({"type": "Delete", "actor": "https://remote.invalid/bob", "object": "https://remote.invalid/notes/2"}))?)?;
+    dispatch(&mut inbox, &received(serde_json::json!({"type": "Accept", "actor": "https://remote.invalid/carol", "object": "https://local.invalid/follows/2"}))?)?;
+    dispatch(&mut inbox, &received(serde_json::json!({
+        "type": "Add", "actor": "https://remote.invalid/bob", "object": "https://remote.invalid/notes/3", "target": "https://remote.invalid/bob/featured"
+    }))?)?;
+    assert_eq!(inbox.log, [
+        "create https://mastodon.example/users/alice/statuses/1/activity",
+        "follow request https://remote.invalid/follows/1",
+        "following https://local.invalid/follows/1 https://remote.invalid/bob",
+        "like https://local.invalid/notes/1 https://remote.invalid/likes/1",
+        "unlike https://local.invalid/notes/1 https://remote.invalid/likes/1",
+        "unfollowed https://local.invalid/alice https://remote.invalid/bob",
+        "delete https://remote.invalid/notes/2",
+        "following https://local.invalid/follows/2 https://remote.invalid/carol",
+        "add https://remote.invalid/bob https://remote.invalid/bob/featured https://remote.invalid/notes/3",
+    ]);
+    Ok(())
+}
+
+/// Activities that may not be performed by their actor are rejected without side effects
+#[test]
+fn test_inbox_dispatch_unauthorized() -> Result<(), Box<dyn Error>> {
+    let mut inbox = RecordingInbox::default();
+    let delete = received(serde_json::json!({"type": "Delete", "actor": "https://remote.invalid/bob", "object": "https://local.invalid/notes/1"}))?;
+    assert!(matches!(dispatch(&mut inbox, &delete), Err(InboxError::Unauthorized(_))));
+    let undo = received(serde_json::json!({
+        "type": "Undo", "actor": "https://remote.invalid/mallory",
+        "object": {"type": "Like", "actor": "https://remote.invalid/bob", "object": "https://local.invalid/notes/1"}
+    }))?;
+    assert!(matches!(dispatch(&mut inbox, &undo), Err(InboxError::Unauthorized(_))));
+    let accept = received(serde_json::json!({
+        "type": "Accept", "actor": "https://remote.invalid/mallory",
+        "object": {"type": "Follow", "actor": "https://local.invalid/alice", "object": "https://remote.invalid/bob"}
+    }))?;
+    assert!(matches!(dispatch(&mut inbox, &accept), Err(InboxError::Unauthorized(_))));
+    let accept = received(serde_json::json!({"type": "Accept", "actor": "https://remote.invalid/mallory", "object": "https://local.invalid/follows/1"}))?;
+    assert!(matches!(dispatch(&mut inbox, &accept), Err(InboxError::Unauthorized(_))));
+    let accept = received(serde_json::json!({
+        "type": "Accept", "actor": "https://remote.invalid/mallory",
+        "object": {"id": "https://local.invalid/follows/1", "type": "Follow", "actor": "https://local.invalid/alice"}
+    }))?;
+    assert!(matches!(dispatch(&mut inbox, &accept), Err(InboxError::Unauthorized(_))));
+    let accept = received(serde_json::json!({
+        "type": "Accept", "actor": "https://remote.invalid/mallory",
+        "object": {"id": "https://local.invalid/follows/1", "type": "Follow", "actor": "https://local.invalid/alice", "object": "https://remote.invalid/mallory"}
+    }))?;
+    assert!(matches!(dispatch(&mut inbox, &accept), Err(InboxError::Unauthorized(_))));
+    for kind in ["Like", "Announce"] {
+        let planted = received(serde_json::json!({
+            "id": "https://other.invalid/activities/1", "type": kind, "actor": "https://remote.invalid/bob", "object": "https://local.invalid/notes/1"
+        }))?;
+        assert!(matches!(dispatch(&mut inbox, &planted), Err(InboxError::Unauthorized(_))));
+    }
+    let undo = received(serde_json::json!({
+        "type": "Undo", "actor": "https://remote.invalid/bob",
+        "object": {"id": "https://other.invalid/likes/1", "type": "Like", "actor": "https://remote.invalid/bob", "object": "https://local.invalid/notes/1"}
+    }))?;
+    assert!(matches!(dispatch(&mut inbox, &undo), Err(InboxError::Unauthorized(_))));
+    // Adds to collections of others are left to on_add
+    dispatch(&mut inbox, &received(serde_json::json!({
+        "type": "Add", "actor": "https://remote.invalid/bob", "object": "https://remote.invalid/notes/3", "target": "https://local.invalid/alice/featured"
+    }))?)?;
+    let note = received(serde_json::json!({"type": "Note", "content": "Not an activity"}))?;
+    assert!(matches!(dispatch(&mut inbox, &note), Err(InboxError::NotAnActivity)));
+    assert!(inbox.log.is_empty());
+    Ok(())
+}
+
+#[derive(Default)]
+struct MemoryOutbox {
+    next_id: u32,
+    stored: Vec<serde_json::Value>,
+}
+
+impl OutboxStore for MemoryOutbox {
+    type Error = OutboxError;
+
+    fn new_id(&mut self, actor: &str, _object: &TaggedObject) -> Result<String, OutboxError> {
+        self.next_id += 1;
+        Ok(format!("{}/objects/{}", actor, self.next_id))
+    }
+
+    fn store(&mut self, _actor: &str, activity: &TaggedObject) -> Result<(), OutboxError> {
+        self.stored.push(serde_json::to_value(activity).unwrap());
+        Ok(())
+    }
+}
+
+/// A bare object is wrapped in a Create, with ids assigned, addressing copied and bcc stripped for delivery
+#[test]
+fn test_outbox_wraps_object() -> Result<(), Box<dyn Error>> {
+    let mut store = MemoryOutbox::default();
+    let note = serde_json::from_value::<TaggedObject>(serde_json::json!({
+        "id": "https://client.invalid/chosen-by-client", "type": "Note", "content": "Hello",
+        "to": "https://www.w3.org/ns/activitystreams#Public", "bcc": "https://remote.invalid/bob"
+    }))?;
+    let processed = outbox::process(&mut store, "https://local.invalid/alice", note)?;
+    assert_eq!(processed.recipients, ["https://www.w3.org/ns/activitystreams#Public", "https://remote.invalid/bob"]);
+
+    // The author keeps the blind recipients; Only the delivered activity is stripped of them
+    let stored = &store.stored[0];
+    assert_eq!(stored["bcc"], "https://remote.invalid/bob");
+    assert_eq!(stored["object"]["bcc"], "https://remote.invalid/bob");
+    let activity = serde_json::to_value(&processed.activity)?;
+    assert_eq!(activity["type"], "Create");
+    assert_eq!(activity["id"], "https://local.invalid/alice/objects/2");
+    assert_eq!(activity["actor"], "https://local.invalid/alice");
+    assert_eq!(activity["to"], "https://www.w3.org/ns/activitystreams#Public");
+    assert!(activity["bcc"].is_null());
+    assert_eq!(activity["object"]["id"], "https://local.invalid/alice/objects/1");
+    assert_eq!(activity["object"]["attributedTo"], "https://local.invalid/alice");
+    assert!(activity["object"]["bcc"].is_null());
+    Ok(())
+}
+
+#[test]
+fn test_outbox_activity() -> Result<(), Box<dyn Error>> {
+    let mut store = MemoryOutbox::default();
+    let like = serde_json::from_value::<TaggedObject>(serde_json::json!({
+        "id": "https://client.invalid/like", "type": "Like", "object": "https://remote.invalid/notes/1", "cc": ["https://remote.invalid/bob"]
+    }))?;
+    let processed = outbox::process(&mut store, "https://local.invalid/alice", like)?;
+    assert_eq!(processed.activity.id(), Some("https://local.invalid/alice/objects/1"));
+    assert_eq!(processed.recipients, ["https://remote.invalid/bob"]);
+
+    let spoofed = serde_json::from_value::<TaggedObject>(serde_json::json!({"type": "Like", "actor": "https://local.invalid/mallory", "object": "https://remote.invalid/notes/1"}))?;
+    assert!(matches!(outbox::process(&mut store, "https://local.invalid/alice", spoofed), Err(OutboxError::ActorMismatch(_))));
+    assert_eq!(store.stored.len(), 1);
+    Ok(())
+}
+
+/// Fetch from a local stand-in server, checking the Accept header and the HTTP signature of the request
+#[test]
+fn test_http_resolver_signed_fetch() -> Result<(), Box<dyn Error>> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let address = listener.local_addr()?;
+    let server = std::thread::spawn(move || -> std::io::Result<String> {
+        let (mut stream, _) = listener.accept()?;
+        let mut request = Vec::new();
+        let mut buffer = [0; 4096];
+        while !request.ends_with(b"\r\n\r\n") {
+            let read = stream.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            request.extend_from_slice(&buffer[..read]);
+        }
+        let body = format!("{{\"id\": \"http://{}/note\", \"type\": \"Note\", \"content\": \"Hello!\"}}", address);
+        write!(stream, "HTTP/1.1 200 OK\r\nContent-Type: application/activity+json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", body.len(), body)?;
+        Ok(String::from_utf8_lossy(&request).into_owned())
+    });
+
+    let key = HttpSignatureKey {
+        key_id: "https://example.invalid/actor#main-key".to_string(),
+        private_key: signature::private_key_from_pem(include_str!("./test-key.pem"))?,
+    };
+    let resolver = HttpResolver::new().signed(key);
+    let note = MaybeObject::URI(format!("http://{}/note", address)).resolve_object(&resolver)?;
+    assert!(matches!(note, TaggedObject::Note(_)));
+
+    let request = server.join().unwrap()?;
+    let headers = request.lines()
+        .skip(1)
+        .filter_map(|line| line.split_once(": "))
+        .collect::<Vec<_>>();
+    let header = |name: &str| headers.iter().find(|(header, _)| header.eq_ignore_ascii_case(name)).map(|(_, value)| *value);
+    assert_eq!(header("accept"), Some(ACTIVITY_JSON_ACCEPT));
+    let signature_header = SignatureHeader::parse(header("signature").unwrap())?;
+    assert_eq!(signature_header.key_id, "https://example.invalid/actor#main-key");
+    let public_key = signature::public_key_from_pem(include_str!("./test-key.pub.pem"))?;
+    signature_header.verify("GET", "/note", &headers, &public_key)?;
+    Ok(())
+}
+
+/// Reject responses over the size limit, whether or not they announce their length
+#[test]
+fn test_http_resolver_body_limit() -> Result<(), Box<dyn Error>> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let address = listener.local_addr()?;
+    let server = std::thread::spawn(move || -> std::io::Result<()> {
+        for announce_length in [true, false] {
+            let (mut stream, _) = listener.accept()?;
+            let mut request = Vec::new();
+            let mut buffer = [0; 4096];
+            while !request.ends_with(b"\r\n\r\n") {
+                let read = stream.read(&mut buffer)?;
+                if read == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buffer[..read]);
+            }
+            let body = format!("{{\"type\": \"Note\", \"content\": \"{}\"}}", "a".repeat(2048));
+            let length = match announce_length {
+                true => format!("Content-Length: {}\r\n", body.len()),
+                false => String::new()
+            };
+            // The client may hang up before the whole body is written
+            let _ = write!(stream, "HTTP/1.1 200 OK\r\nContent-Type: application/activity+json\r\n{}Connection: close\r\n\r\n{}", length, body);
+        }
+        Ok(())
+    });
+
+    let resolver = HttpResolver::new().max_body_size(1024);
+    for _ in 0..2 {
+        let result = MaybeObject::URI(format!("http://{}/note", address)).resolve_object(&resolver);
+        assert!(matches!(result, Err(ResolveError::TooLarge(1024))));
+    }
+    server.join().unwrap()?;
+    Ok(())
+}
+
+/// Resolve followers and direct recipients to inboxes, deduplicating shared inboxes
+#[test]
+fn test_audience_resolution() -> Result<(), Box<dyn Error>> {
+    let mut resolver = InMemoryResolver::new();
+    resolver.insert("https://local.invalid/alice/followers", serde_json::json!({
+        "id": "https://local.invalid/alice/followers", "type": "OrderedCollection",
+        "orderedItems": ["https://remote.invalid/bob", "https://remote.invalid/carol", "https://other.invalid/dave", "https://local.invalid/alice"]
+    }));
+    resolver.insert("https://remote.invalid/bob", serde_json::json!({
+        "id": "https://remote.invalid/bob", "type": "Person", "inbox": "https://remote.invalid/bob/inbox", "endpoints": {"sharedInbox": "https://remote.invalid/inbox"}
+    }));
+    resolver.insert("https://remote.invalid/carol", serde_json::json!({
+        "id": "https://remote.invalid/carol", "type": "Person", "inbox": "https://remote.invalid/carol/inbox", "endpoints": {"sharedInbox": "https://remote.invalid/inbox"}
+    }));
+    resolver.insert("https://other.invalid/dave", serde_json::json!({"id": "https://other.invalid/dave", "type": "Person", "inbox": "https://other.invalid/dave/inbox"}));
+
+    let recipients = [
+        "https://www.w3.org/ns/activitystreams#Public", "https://local.invalid/alice/followers", "https://remote.invalid/bob", "https://local.invalid/alice", "https://remote.invalid/missing"
+    ].map(str::to_string);
+    let audience = Audience::resolve(&resolver, "https://local.invalid/alice", &recipients, true);
+    assert_eq!(audience.inboxes, ["https://remote.invalid/inbox", "https://other.invalid/dave/inbox"]);
+    assert!(matches!(audience.errors.as_slice(), [(missing, ResolveError::NotFound(_))] if missing == "https://remote.invalid/missing"));
+    assert!(!resolver.fetched().iter().any(|uri| uri.contains("Public") || uri == "https://local.invalid/alice"));
+
+    let audience = Audience::resolve(&resolver, "https://local.invalid/alice", &recipients[2..3], false);
+    assert_eq!(audience.inboxes, ["https://remote.invalid/bob/inbox"]);
+
+    // The followers collection and its first two members
+    let audience = Audience::resolve_with_budget(&resolver, "https://local.invalid/alice", &recipients, true, 3);
+    assert_eq!(audience.inboxes, ["https://remote.invalid/inbox"]);
+    assert!(matches!(audience.errors.as_slice(), [(dave, ResolveError::FetchBudgetExhausted)] if dave == "https://other.invalid/dave"));
+    Ok(())
+}
+
+/// Read an HTTP request; Its head and body
+fn read_request(stream: &mut TcpStream) -> std::io::Result<(String, Vec<u8>)> {
+    let mut request = Vec::new();
+    let mut buffer = [0; 4096];
+    let head_end = loop {
+        if let Some(end) = request.windows(4).position(|window| window == b"\r\n\r\n") {
+            break end + 4;
+        }
+        let read = stream.read(&mut buffer)?;
+        if read == 0 {
+            break request.len();
+        }
+        request.extend_from_slice(&buffer[..read]);
+    };
+    let head = String::from_utf8_lossy(&request[..head_end]).into_owned();
+    let length = head.lines()
+        .filter_map(|line| line.split_once(": "))
+        .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.parse::<usize>().ok())
+        .unwrap_or(0);
+    let mut body = request.split_off(head_end);
+    while body.len() < length {
+        let read = stream.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        body.extend_from_slice(&buffer[..read]);
+    }
+    Ok((head, body))
+}
+
+/// Deliver to a local stand-in inbox that fails once, and to one that is gone; Every request must be signed and carry a matching digest
+#[test]
+fn test_delivery_retries() -> Result<(), Box<dyn Error>> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let address = listener.local_addr()?;
+    let server = std::thread::spawn(move || -> std::io::Result<Vec<(String, Vec<u8>)>> {
+        let mut requests = Vec::new();
+        for _ in 0..3 {
+            let (mut stream, _) = listener.accept()?;
+            let (head, body) = read_request(&mut stream)?;
+            let status = match head.split_whitespace().nth(1) {
+                Some("/gone") => "410 Gone",
+                _ if requests.iter().any(|(head, _): &(String, Vec<u8>)| head.starts_with("POST /inbox ")) => "202 Accepted",
+                _ => "503 Service Unavailable"
+            };
+            write!(stream, "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status)?;
+            requests.push((head, body));
+        }
+        Ok(requests)
+    });
+
+    let key = HttpSignatureKey {
+        key_id: "https://local.invalid/alice#main-key".to_string(),
+        private_key: signature::private_key_from_pem(include_str!("./test-key.pem"))?,
+    };
+    let deliverer = Deliverer::new(key).with_retry(3, chrono::Duration::zero());
+    let activity = serde_json::json!({"id": "https://local.invalid/alice/objects/1", "type": "Like", "actor": "https://local.invalid/alice", "object": "https://remote.invalid/notes/1"});
+    let mut queue = InMemoryQueue::new();
+    for delivery in Delivery::to_inboxes(&activity, &[format!("http://{}/inbox", address), format!("http://{}/gone", address)], Utc::now()) {
+        queue.push(delivery);
+    }
+    // Retries are left for the next run, even without backoff
+    let report = deliverer.run(&mut queue, Utc::now());
+    assert!(report.delivered.is_empty());
+    assert!(matches!(report.retrying.as_slice(), [(_, DeliveryError::Status(503))]));
+    assert!(matches!(report.failed.as_slice(), [(_, DeliveryError::Status(410))]));
+    assert_eq!(queue.len(), 1);
+    let report = deliverer.run(&mut queue, Utc::now());
+    assert_eq!(report.delivered, [format!("http://{}/inbox", address)]);
+    assert!(queue.is_empty());
+
+    let public_key = signature::public_key_from_pem(include_str!("./test-key.pub.pem"))?;
+    for (head, body) in server.join().unwrap()? {
+        let path = head.split_whitespace().nth(1).unwrap().to_string();
+        let headers = head.lines().skip(1).filter_map(|line| line.split_once(": ")).collect::<Vec<_>>();
+        let header = |name: &str| headers.iter().find(|(header, _)| header.eq_ignore_ascii_case(name)).map(|(_, value)| *value);
+        assert_eq!(header("digest"), Some(digest_header(&body).as_str()));
+        assert_eq!(serde_json::from_slice::<serde_json::Value>(&body)?, activity);
+        SignatureHeader::parse(header("signature").unwrap())?.verify("POST", &path, &headers, &public_key)?;
+    }
+
+    // Backoff is capped, however many attempts were made
+    let closed = TcpListener::bind("127.0.0.1:0")?.local_addr()?;
+    let deliverer = deliverer.with_retry(u32::MAX, chrono::Duration::weeks(1000)).with_max_backoff(chrono::Duration::hours(1));
+    let now = Utc::now();
+    for mut delivery in Delivery::to_inboxes(&activity, &[format!("http://{}/inbox", closed)], now) {
+        delivery.attempts = 100;
+        queue.push(delivery);
+    }
+    let report = deliverer.run(&mut queue, now);
+    assert!(matches!(report.retrying.as_slice(), [(_, DeliveryError::Http(_))]));
+    assert!(queue.pop_due(now + chrono::Duration::minutes(59)).is_none());
+    assert_eq!(queue.pop_due(now + chrono::Duration::hours(1)).map(|delivery| delivery.attempts), Some(101));
+    Ok(())
+}
+
+#[test]
+fn test_visibility() -> Result<(), Box<dyn Error>> {
+    let followers = "https://local.invalid/alice/followers";
+    let note = |to: serde_json::Value, cc: serde_json::Value| serde_json::from_value::<TaggedObject>(serde_json::json!({
+        "type": "Note", "attributedTo": "https://local.invalid/alice", "to": to, "cc": cc
+    }));
+    let public = note(serde_json::json!(PUBLIC), serde_json::json!([followers]))?;
+    let unlisted = note(serde_json::json!([followers]), serde_json::json!("as:Public"))?;
+    let followers_only = note(serde_json::json!([followers]), serde_json::json!(["https://remote.invalid/bob"]))?;
+    let direct = note(serde_json::json!(["https://remote.invalid/bob"]), serde_json::json!([]))?;
+
+    assert!(public.is_public() && !public.is_unlisted() && !public.is_followers_only(followers) && !public.is_direct(followers));
+    assert!(!unlisted.is_public() && unlisted.is_unlisted() && !unlisted.is_followers_only(followers) && !unlisted.is_direct(followers));
+    assert!(!followers_only.is_public() && !followers_only.is_unlisted() && followers_only.is_followers_only(followers) && !followers_only.is_direct(followers));
+    assert!(!direct.is_public() && !direct.is_unlisted() && !direct.is_followers_only(followers) && direct.is_direct(followers));
+
+    let follows_alice = |collection: &str| collection == followers;
+    assert!(followers_only.is_visible_to("https://remote.invalid/carol", follows_alice));
+    assert!(!followers_only.is_visible_to("https://remote.invalid/carol", |_| false));
+    assert!(followers_only.is_visible_to("https://remote.invalid/bob", |_| false));
+    assert!(direct.is_visible_to("https://local.invalid/alice", |_| false));
+    assert!(!direct.is_visible_to("https://remote.invalid/carol", follows_alice));
+    assert!(unlisted.is_visible_to("https://remote.invalid/carol", |_| false));
+    Ok(())
+}
+
+/// Serve a JRD for a local actor, and resolve its handle through it; Actors on other hosts must be confirmed by them
+#[test]
+fn test_webfinger() -> Result<(), Box<dyn Error>> {
+    assert_eq!(parse_handle("@alice@local.invalid"), Some(("alice", "local.invalid")));
+    assert_eq!(parse_handle("acct:alice@local.invalid"), Some(("alice", "local.invalid")));
+    assert_eq!(parse_handle("alice"), None);
+    assert_eq!(parse_handle("alice@local.invalid:8443"), Some(("alice", "local.invalid:8443")));
+    for handle in ["alice@local.invalid/path", "alice@local.invalid?q", "alice@user@local.invalid", "alice@local.invalid#x", "alice@", "alice@local invalid"] {
+        assert_eq!(parse_handle(handle), None, "{}", handle);
+    }
+    assert_eq!(local_username("acct:alice@LOCAL.invalid", "local.invalid"), Some("alice"));
+    assert_eq!(local_username("acct:alice@remote.invalid", "local.invalid"), None);
+
+    let jrd = Jrd::for_actor("alice", "local.invalid", "https://local.invalid/users/alice", Some("https://local.invalid/@alice"));
+    let served = serde_json::to_value(&jrd)?;
+    assert_eq!(served["subject"], "acct:alice@local.invalid");
+    assert_eq!(served["links"][0], serde_json::json!({"rel": "self", "type": "application/activity+json", "href": "https://local.invalid/users/alice"}));
+
+    let url = webfinger_url("alice", "local.invalid")?;
+    assert_eq!(url, "https://local.invalid/.well-known/webfinger?resource=acct%3Aalice%40local.invalid");
+    // Served only as a raw document, so it must be requested as a JRD rather than as ActivityStreams
+    let mut resolver = InMemoryResolver::new();
+    resolver.insert_raw(&url, serde_json::to_vec(&served)?);
+    resolver.insert("https://local.invalid/users/alice", serde_json::json!({"id": "https://local.invalid/users/alice", "type": "Person", "name": "Alice"}));
+    let actor = resolve_handle(&resolver, "@alice@local.invalid")?;
+    assert!(matches!(actor, TaggedObject::Person(_)));
+    assert!(matches!(resolve_handle(&resolver, "@bob@local.invalid"), Err(ResolveError::NotFound(_))));
+
+    // Another host may only point a handle at an actor that the actor's host confirms
+    resolver.insert_raw(&webfinger_url("x", "evil.invalid")?, serde_json::to_vec(&Jrd::for_actor("x", "evil.invalid", "https://local.invalid/users/alice", None))?);
+    assert!(matches!(resolve_handle(&resolver, "@x@evil.invalid"), Err(ResolveError::OriginMismatch { .. })));
+    let delegated = Jrd::for_actor("alice", "example.invalid", "https://local.invalid/users/alice", None);
+    resolver.insert_raw(&webfinger_url("alice", "example.invalid")?, serde_json::to_vec(&delegated)?);
+    assert!(matches!(resolve_handle(&resolver, "@alice@example.invalid"), Err(ResolveError::OriginMismatch { .. })));
+    resolver.insert_raw("https://local.invalid/.well-known/webfinger?resource=acct%3Aalice%40example.invalid", serde_json::to_vec(&delegated)?);
+    assert_eq!(resolve_handle(&resolver, "@alice@example.invalid")?.id(), Some("https://local.invalid/users/alice"));
+    Ok(())
+}
+
+/// Discover a non-standard WebFinger endpoint through host-meta, in XRD and in JSON form
+#[test]
+fn test_host_meta() -> Result<(), Box<dyn Error>> {
+    let xrd = r#"<?xml version="1.0" encoding="UTF-8"?>
+<XRD xmlns="http://docs.oasis-open.org/ns/xri/xrd-1.0">
+    <Subject>https://xrd.invalid</Subject>
+    <Property type="http://example.invalid/ns/software">example</Property>
+    <Link rel="lrdd" type="application/jrd+json" template="https://xrd.invalid/fingers?q={uri}">
+        <Title xml:lang="en">Resource Descriptor</Title>
+    </Link>
+</XRD>"#;
+    let jrd = Jrd::from_xrd(xrd)?;
+    assert_eq!(jrd.subject.as_deref(), Some("https://xrd.invalid"));
+    assert_eq!(jrd.properties.get("http://example.invalid/ns/software"), Some(&Some("example".to_string())));
+    assert_eq!(jrd.links[0].titles.get("en").map(String::as_str), Some("Resource Descriptor"));
+    assert_eq!(jrd.lrdd_template(), Some("https://xrd.invalid/fingers?q={uri}"));
+    assert!(matches!(Jrd::from_xrd("<html/>"), Err(ResolveError::UnexpectedType(_))));
+    assert!(matches!(Jrd::from_xrd("<XRD"), Err(ResolveError::Xml(_))));
+
+    let mut resolver = InMemoryResolver::new();
+    resolver.insert_raw("https://xrd.invalid/.well-known/host-meta", xrd.as_bytes().to_vec());
+    resolver.insert("https://xrd.invalid/fingers?q=acct%3Aalice%40xrd.invalid", serde_json::to_value(Jrd::for_actor("alice", "xrd.invalid", "https://xrd.invalid/alice", None))?);
+    resolver.insert("https://xrd.invalid/alice", serde_json::json!({"id": "https://xrd.invalid/alice", "type": "Person"}));
+    let actor = resolve_handle(&resolver, "@alice@xrd.invalid")?;
+    assert_eq!(actor.id(), Some("https://xrd.invalid/alice"));
+
+    resolver.insert("https://json.invalid/.well-known/host-meta.json", serde_json::json!({
+        "links": [{"rel": "lrdd", "template": "https://json.invalid/wf/{uri}"}]
+    }));
+    assert_eq!(host_meta(&resolver, "json.invalid")?.lrdd_template(), Some("https://json.invalid/wf/{uri}"));
+    resolver.insert("https://json.invalid/wf/acct%3Abob%40json.invalid", serde_json::to_value(Jrd::for_actor("bob", "json.invalid", "https://json.invalid/bob", None))?);
+    resolver.insert("https://json.invalid/bob", serde_json::json!({"id": "https://json.invalid/bob", "type": "Service"}));
+    assert!(matches!(resolve_handle(&resolver, "bob@json.invalid")?, TaggedObject::Service(_)));
+    Ok(())
+}
+
+/// Generate NodeInfo, and discover it through the well-known document
+#[test]
+fn test_nodeinfo() -> Result<(), Box<dyn Error>> {
+    let software = Software { name: "turtleactivitypub".to_string(), version: "0.1.0".to_string(), repository: Some("https://example.invalid/repo".to_string()), homepage: None };
+    let nodeinfo = NodeInfo::activitypub(software, Usage::default(), false);
+    let mut resolver = InMemoryResolver::new();
+    resolver.insert("https://local.invalid/.well-known/nodeinfo", serde_json::to_value(NodeInfo::well_known("https://local.invalid/"))?);
+    resolver.insert("https://local.invalid/nodeinfo/2.0", serde_json::to_value(nodeinfo.clone().for_version(NodeInfoVersion::V2_0))?);
+    resolver.insert("https://local.invalid/nodeinfo/2.1", serde_json::to_value(&nodeinfo)?);
+
+    let discovered = NodeInfo::discover(&resolver, "local.invalid")?;
+    assert_eq!(discovered.version, "2.1");
+    assert_eq!(discovered.software.repository.as_deref(), Some("https://example.invalid/repo"));
+    assert_eq!(resolver.fetched().last().map(String::as_str), Some("https://local.invalid/nodeinfo/2.1"));
+
+    // A server only offering 2.0
+    resolver.insert("https://remote.invalid/.well-known/nodeinfo", serde_json::json!({
+        "links": [{"rel": "http://nodeinfo.diaspora.software/ns/schema/2.0", "href": "https://remote.invalid/nodeinfo/2.0"}]
+    }));
+    resolver.insert("https://remote.invalid/nodeinfo/2.0", serde_json::json!({
+        "version": "2.0", "software": {"name": "mastodon", "version": "4.2.0"}, "protocols": ["activitypub"],
+        "services": {"outbound": [], "inbound": []}, "usage": {"users": {"total": 10, "activeMonth": 2, "activeHalfyear": 5}, "localPosts": 100},
+        "openRegistrations": true, "metadata": {}
+    }));
+    let discovered = NodeInfo::discover(&resolver, "remote.invalid")?;
+    assert_eq!(discovered.software.name, "mastodon");
+    assert_eq!(discovered.usage.users.activeMonth, Some(2));
+    let served = serde_json::to_value(nodeinfo.for_version(NodeInfoVersion::V2_0))?;
+    assert!(served["software"].get("repository").is_none());
+    Ok(())
+}
+
+/// Report spec violations that deserialization accepts, with paths to the offending properties
+#[test]
+fn test_validate() -> Result<(), Box<dyn Error>> {
+    let create = serde_json::from_str::<TaggedObject>(include_str!("./create.json"))?;
+    assert!(!has_errors(&create.validate(ValidationProfile::Strict)));
+
+    let like = serde_json::from_value::<TaggedObject>(serde_json::json!({"type": "Like", "id": "https://remote.invalid/likes/1", "object": "https://local.invalid/notes/1"}))?;
+    let diagnostics = like.validate(ValidationProfile::Lenient);
+    assert_eq!(diagnostics.iter().map(|diagnostic| (diagnostic.path.as_str(), diagnostic.rule)).collect::<Vec<_>>(), [("$", Rule::MissingActor)]);
+
+    let question = serde_json::from_value::<TaggedObject>(serde_json::json!({
+        "type": "Question", "id": "https://remote.invalid/questions/1", "actor": "https://remote.invalid/users/alice",
+        "oneOf": [{"type": "Note", "name": "Yes"}], "anyOf": [{"type": "Note", "name": "No"}],
+        "attachment": [{"type": "Image", "url": "relative/path.png"}, {"type": "Tombstone", "deleted": "2999-01-01T00:00:00Z"}]
+    }))?;
+    let diagnostics = question.validate(ValidationProfile::Lenient);
+    let found = |path: &str, rule: Rule| diagnostics.iter().any(|diagnostic| diagnostic.path == path && diagnostic.rule == rule);
+    assert!(found("$", Rule::QuestionChoices));
+    assert!(found("$.attachment[0].url", Rule::AbsoluteUri));
+    assert!(found("$.attachment[1].deleted", Rule::DeletedInFuture));
+    assert_eq!(diagnostics.len(), 3);
+
+    // SHOULD requirements are only errors in the strict profile
+    let note = serde_json::from_value::<TaggedObject>(serde_json::json!({"type": "Note", "duration": "5 minutes", "content": "Hi"}))?;
+    let lenient = note.validate(ValidationProfile::Lenient);
+    assert!(lenient.iter().all(|diagnostic| diagnostic.severity == Severity::Warning));
+    assert_eq!(lenient.iter().map(|diagnostic| diagnostic.rule).collect::<Vec<_>>(), [Rule::MissingId, Rule::InvalidDuration]);
+    assert!(has_errors(&note.validate(ValidationProfile::Strict)));
+    assert_eq!(lenient[1].to_string(), "warning at $.duration: duration is not an xsd:duration");
+
+    let note = serde_json::from_value::<TaggedObject>(serde_json::json!({"type": "Note", "id": "https://local.invalid/notes/1", "duration": "P1DT2H30.5S"}))?;
+    assert!(note.validate(ValidationProfile::Strict).is_empty());
+    Ok(())
+}
+
+/// Locate deserialization failures deep inside untagged and tagged properties
+#[test]
+fn test_deserialize_error_paths() -> Result<(), Box<dyn Error>> {
+    let activity = serde_json::json!({
+        "type": "Create", "actor": "https://remote.invalid/users/alice",
+        "object": {"type": "Note", "attachment": [{"type": "Image", "url": "https://remote.invalid/a.png"}, {"type": "Document", "published": "yesterday"}]}
+    });
+    let error = deserialize::from_value::<TaggedObject>(&activity).unwrap_err();
+    assert_eq!(error.path, "$.object");
+    assert_eq!(error.attempts.iter().map(|attempt| attempt.variant).collect::<Vec<_>>(), ["URI", "TaggedLink", "TaggedObject"]);
+    assert_eq!(error.attempts[1].error.path, "$.object.type");
+    let deepest = error.deepest();
+    assert_eq!(deepest.path, "$.object.attachment[1].published");
+    assert!(error.to_string().contains("TaggedLink: $.object.type: unknown type `Note` for TaggedLink"));
+
+    // Serde's own error carries the located message as well
+    let error = serde_json::from_value::<TaggedObject>(activity).unwrap_err();
+    assert!(error.to_string().contains("$.object.attachment[1].published"));
+    Ok(())
+}
+
+/// A chain of activities and replies, `depth` objects deep, each with a few valid siblings; The innermost Note has an invalid `published`
+fn nested_replies(depth: usize) -> serde_json::Value {
+    let siblings = serde_json::json!([
+        {"type": "Image", "url": "https://remote.invalid/a.png"},
+        {"type": "Note", "content": "Sibling", "tag": [{"type": "Mention", "href": "https://remote.invalid/users/bob"}]}
+    ]);
+    let mut object = serde_json::json!({"type": "Note", "published": 5});
+    for level in 0..depth {
+        object = match level % 2 {
+            0 => serde_json::json!({"type": "Create", "actor": "https://remote.invalid/users/alice", "attachment": siblings, "object": object}),
+            _ => serde_json::json!({"type": "Note", "attachment": siblings, "inReplyTo": object}),
+        };
+    }
+    object
+}
+
+/// Locating a failure, or dropping it in lenient mode, takes one pass however deeply it is nested; Retrying properties at each level took time exponential in the depth
+#[test]
+fn test_deserialize_error_depth() -> Result<(), Box<dyn Error>> {
+    // Unoptimized builds need a larger stack than that of test threads for this depth
+    let located = std::thread::Builder::new().stack_size(64 * 1024 * 1024).spawn(|| {
+        let depth = 32;
+        let expected = format!("${}.published", ".inReplyTo.object".repeat(depth / 2));
+        let error = deserialize::from_value::<TaggedObject>(&nested_replies(depth)).unwrap_err();
+        assert_eq!(error.deepest().path, expected);
+        let error = serde_json::from_value::<TaggedObject>(nested_replies(depth)).unwrap_err();
+        assert!(error.to_string().contains(&expected));
+        let (_, warnings) = deserialize::from_value_with::<TaggedObject>(&nested_replies(depth), deserialize::Mode::Lenient).unwrap();
+        assert_eq!(warnings.iter().map(|warning| warning.path.as_str()).collect::<Vec<_>>(), [expected.as_str()]);
+    })?;
+    located.join().map_err(|_| "failure was not located")?;
+    Ok(())
+}
+
+/// Drop invalid property values in lenient mode, keeping the rest of the object
+#[test]
+fn test_lenient_deserialize() -> Result<(), Box<dyn Error>> {
+    let activity = serde_json::json!({
+        "type": "Create", "id": "https://remote.invalid/activities/1", "actor": "https://remote.invalid/users/alice", "published": "last tuesday",
+        "object": {"type": "Note", "content": "Hello", "published": "2022-01-01T00:00:00Z", "location": [
+            {"type": "Place", "name": "Here", "accuracy": 150.0, "radius": -1.0, "latitude": 52.0},
+            {"type": "Unknown"},
+            "https://remote.invalid/places/2"
+        ]}
+    });
+    assert!(deserialize::from_value::<TaggedObject>(&activity).is_err());
+
+    let (create, warnings) = deserialize::from_value_with::<TaggedObject>(&activity, deserialize::Mode::Lenient)?;
+    let mut paths = warnings.iter().map(|warning| warning.path.as_str()).collect::<Vec<_>>();
+    paths.sort();
+    assert_eq!(paths, ["$.object.location[0].accuracy", "$.object.location[0].radius", "$.object.location[1]", "$.published"]);
+    let activity_fields = create.activity_fields().ok_or("not an activity")?;
+    assert!(activity_fields.object_fields.published.is_none());
+    assert_eq!(activity_fields.actor.as_deref().map(<[_]>::len), Some(1));
+    let Some(MaybeObject::TaggedObject(note)) = activity_fields.object.as_deref().and_then(<[_]>::first) else { return Err("object not embedded".into()) };
+    let note = note.object_fields().ok_or("not an object")?;
+    assert!(note.published.is_some());
+    let locations = note.location.as_deref().ok_or("no locations")?;
+    assert_eq!(locations.len(), 2);
+    let MaybeObject::TaggedObject(TaggedObject::Place(place)) = &locations[0] else { return Err("place not embedded".into()) };
+    assert!(place.accuracy.is_none() && place.radius.is_none());
+    assert_eq!(place.latitude, Some(52.0));
+
+    // The type determines how the object is read, so it cannot be dropped
+    assert!(deserialize::from_value_with::<TaggedObject>(&serde_json::json!({"type": "Unknown"}), deserialize::Mode::Lenient).is_err());
+    Ok(())
+}
+
+/// Accept the xsd:dateTime lexical space and common deviations, preserving the lexical form and the offset assumed when parsing
+#[test]
+fn test_xsd_date_time() -> Result<(), Box<dyn Error>> {
+    let reference = "2022-06-01T12:00:00Z".parse::<XsdDateTime>()?;
+    for lexical in ["2022-06-01T12:00:00z", "2022-06-01t14:00:00+02:00", "2022-06-01T12:00:00.000000000000Z", "2022-06-01T12:00:00", "2022-05-31T24:00:00-12:00"] {
+        let date = lexical.parse::<XsdDateTime>()?;
+        assert_eq!(date, reference, "{}", lexical);
+        assert_eq!(serde_json::to_value(&date)?, lexical);
+    }
+    assert_eq!("2022-06-01T12:00:00".parse::<XsdDateTime>()?.offset(), None);
+    let east = chrono::FixedOffset::east_opt(2 * 3600).ok_or("invalid offset")?;
+    let assumed = XsdDateTime::parse_with_offset("2022-06-01T14:00:00", east)?;
+    assert_eq!(assumed, reference);
+    assert_eq!(assumed.lexical(), "2022-06-01T14:00:00");
+    assert!("2022-06-01T14:00:00".parse::<XsdDateTime>()? > reference);
+    assert_eq!("2022-06-01T14:00:00".parse::<XsdDateTime>()?.assuming_offset(east), reference);
+    let note = serde_json::json!({"type": "Note", "published": "2022-06-01T14:00:00", "updated": "2022-06-01T12:00:00Z"});
+    for parsed in [
+        deserialize::with_assumed_offset(east, || deserialize::from_value::<TaggedObject>(&note))?,
+        deserialize::with_assumed_offset(east, || serde_json::from_value::<TaggedObject>(note.clone()))?,
+    ] {
+        let fields = parsed.object_fields().ok_or("not an object")?;
+        assert_eq!(fields.published.as_ref(), Some(&reference));
+        assert_eq!(fields.updated.as_ref(), Some(&reference));
+    }
+    let utc = serde_json::from_value::<TaggedObject>(note)?;
+    assert!(utc.object_fields().ok_or("not an object")?.published.as_ref() > Some(&reference));
+    assert_eq!("2022-06-01T12:00:00.123456789123Z".parse::<XsdDateTime>()?.to_utc().timestamp_subsec_nanos(), 123456789);
+    assert!("-0044-03-15T12:00:00Z".parse::<XsdDateTime>()? < reference);
+    for invalid in ["2022-06-01", "2022-06-01T12:00Z", "22-06-01T12:00:00Z", "2022-06-01T12:00:00+15:00", "2022-06-01T24:00:01Z", "2022-02-30T00:00:00Z"] {
+        assert!(invalid.parse::<XsdDateTime>().is_err(), "{}", invalid);
+    }
+
+    let note = serde_json::from_value::<TaggedObject>(serde_json::json!({"type": "Note", "published": "2022-06-01T12:00:00.1234567890z"}))?;
+    assert_eq!(serde_json::to_value(&note)?["published"], "2022-06-01T12:00:00.1234567890z");
+    Ok(())
+}
+
+/// Whether every value in `subset` is present at the same place in `superset`; Arrays must match element by element
+fn is_json_subset(subset: &serde_json::Value, superset: &serde_json::Value) -> bool {
+    use serde_json::Value;
+    match (subset, superset) {
+        (Value::Object(subset), Value::Object(superset)) => subset.iter().all(|(key, value)| superset.get(key).is_some_and(|other| is_json_subset(value, other))),
+        (Value::Array(subset), Value::Array(superset)) => subset.len() == superset.len() && subset.iter().zip(superset).all(|(value, other)| is_json_subset(value, other)),
+        (subset, superset) => subset == superset
+    }
+}
+
+fn contains_null(value: &serde_json::Value) -> bool {
+    match value {
+        serde_json::Value::Null => true,
+        serde_json::Value::Array(values) => values.iter().any(contains_null),
+        serde_json::Value::Object(properties) => properties.values().any(contains_null),
+        _ => false
+    }
+}
+
+/// Serialize fixtures back without null properties, keeping everything the vocabulary knows of; Fixtures follow the documents Mastodon, Pleroma and Misskey send
+#[test]
+fn test_serialize_round_trip() -> Result<(), Box<dyn Error>> {
+    let fixtures = [
+        include_str!("./data.json"), include_str!("./create.json"), include_str!("./mastodon-person.json"),
+        include_str!("./pleroma-create.json"), include_str!("./misskey-create.json"), include_str!("./misskey-like.json"),
+    ];
+    for fixture in fixtures {
+        let original = serde_json::from_str::<serde_json::Value>(fixture)?;
+        let parsed = serde_json::from_value::<LinkedData<TaggedObject, serde_json::Value>>(original.clone())?;
+        let serialized = serde_json::to_value(&parsed)?;
+        assert!(!contains_null(&serialized), "{}", serialized);
+        assert!(is_json_subset(&serialized, &original), "{}", serialized);
+        let reparsed = serde_json::from_value::<LinkedData<TaggedObject, serde_json::Value>>(serialized.clone())?;
+        assert_eq!(serde_json::to_value(&reparsed)?, serialized);
+    }
+
+    let create = serde_json::from_str::<serde_json::Value>(include_str!("./create.json"))?;
+    let serialized = serde_json::to_value(serde_json::from_value::<TaggedObject>(create.clone())?)?;
+    assert_eq!(serialized["object"]["attachment"][0], serde_json::json!({"type": "Document", "mediaType": "image/png", "url": "https://mastodon.example/a.png"}));
+    assert_eq!(serialized["object"]["replies"]["first"]["items"], serde_json::json!([]));
+    assert_eq!(serde_json::to_string(&serde_json::from_value::<TaggedObject>(serde_json::json!({"type": "Note"}))?)?, r#"{"type":"Note"}"#);
+    let pleroma = serde_json::to_value(serde_json::from_str::<TaggedObject>(include_str!("./pleroma-create.json"))?)?;
+    assert_eq!(pleroma["object"]["attachment"][0]["url"], serde_json::json!([{"type": "Link", "href": "https://pleroma.example/media/0f1e2d3c4b5a.jpg", "mediaType": "image/jpeg"}]));
+    let misskey = serde_json::to_value(serde_json::from_str::<TaggedObject>(include_str!("./misskey-create.json"))?)?;
+    assert!(misskey["object"].get("summary").is_none() && misskey["object"].get("inReplyTo").is_none());
+
+    let mut nulls = deserialize::null_properties(&create["object"]);
+    nulls.sort();
+    assert_eq!(nulls, ["inReplyTo", "summary"]);
+    Ok(())
+}
+
+/// Values keep their shape, whether deserialized from a stream or from a buffered value
+#[test]
+fn test_folded_slice() -> Result<(), Box<dyn Error>> {
+    let json = r#"{"type": "Note", "to": "https://example.com/a", "cc": ["https://example.com/b"], "attachment": [{"type": "Link", "href": "https://example.com/c"}, "https://example.com/d"]}"#;
+    let streamed = serde_json::from_str::<TaggedObject>(json)?;
+    let buffered = deserialize::from_str::<TaggedObject>(json)?;
+    for note in [&streamed, &buffered] {
+        let note = note.object_fields().unwrap();
+        assert!(note.to.as_ref().unwrap().is_one());
+        assert!(!note.cc.as_ref().unwrap().is_one());
+        assert!(matches!(&note.attachment.as_deref().unwrap(), [MaybeObject::TaggedLink(_), MaybeObject::URI(uri)] if uri == "https://example.com/d"));
+    }
+    assert_eq!(serde_json::to_value(&streamed)?, serde_json::from_str::<serde_json::Value>(json)?);
+
+    assert!(FoldedSlice::<i32>::from(vec![1]).is_one());
+    assert!(!FoldedSlice::many(vec![1]).is_one());
+    assert_eq!(serde_json::to_string(&FoldedSlice::many(vec![1]))?, "[1]");
+    assert_eq!(Vec::from(FoldedSlice::from(1)), [1]);
+
+    let error = serde_json::from_str::<TaggedObject>(r#"{"type": "Note", "to": ["https://example.com/a", 1]}"#).unwrap_err();
+    assert!(error.to_string().contains("$.to[1]"), "{}", error);
+    Ok(())
+}
+
+/// FoldedSlice works as a collection; Values added to a single value make it an array for good
+#[test]
+fn test_folded_slice_collection() -> Result<(), Box<dyn Error>> {
+    let mut values = FoldedSlice::from(1);
+    values.push(2);
+    values.extend([3, 4]);
+    values.retain(|value| value % 2 == 0);
+    assert_eq!(serde_json::to_string(&values)?, "[2,4]");
+    assert_eq!((&values).into_iter().sum::<i32>(), 6);
+    for value in &mut values {
+        *value *= 10;
+    }
+    assert_eq!(values.clone().into_iter().collect::<Vec<_>>(), [20, 40]);
+    assert_eq!([20, 40].into_iter().collect::<FoldedSlice<_>>(), values);
+    assert_eq!(std::iter::once(1).collect::<FoldedSlice<_>>(), FoldedSlice::from(1));
+    assert_ne!(FoldedSlice::from(1), FoldedSlice::many(vec![1]));
+    assert!(FoldedSlice::<i32>::default().is_empty());
+    struct NoDefault;
+    assert!(FoldedSlice::<NoDefault>::default().is_empty());
+    let mut values = FoldedSlice::from(1);
+    values.push(2);
+    values.retain(|value| *value == 1);
+    assert!(!values.is_one());
+    assert_eq!(serde_json::to_string(&values)?, "[1]");
+    let mut values = FoldedSlice::from(1);
+    values.extend([]);
+    assert_eq!(serde_json::to_string(&values)?, "1");
+    values.extend([2]);
+    values.retain(|value| *value == 1);
+    assert_eq!(serde_json::to_string(&values)?, "[1]");
+
+    let mut note = serde_json::from_str::<TaggedObject>(r#"{"type": "Note", "to": "https://example.com/a"}"#)?;
+    let to = note.object_fields_mut().unwrap().to.as_mut().unwrap();
+    to.retain(|_| false);
+    assert_eq!(serde_json::to_string(&note)?, r#"{"type":"Note","to":[]}"#);
+    note.object_fields_mut().unwrap().to.as_mut().unwrap().set_when_empty(WhenEmpty::Omit);
+    assert_eq!(serde_json::to_string(&note)?, r#"{"type":"Note"}"#);
+    Ok(())
+}
+
+/// Route received documents through views borrowing their strings; Only strings with escapes are copied
+#[test]
+fn test_borrowed() -> Result<(), Box<dyn Error>> {
+    use std::borrow::Cow;
+    let create = serde_json::from_str::<ObjectRef>(include_str!("./create.json"))?;
+    assert!(matches!(create.id, Some(Cow::Borrowed("https://mastodon.example/users/alice/statuses/1/activity"))));
+    assert_eq!(create.kind.as_deref(), Some("Create"));
+    assert!(matches!(create.actor.as_deref(), Some([MaybeObjectRef::URI(Cow::Borrowed("https://mastodon.example/users/alice"))])));
+    assert_eq!(create.recipients().collect::<Vec<_>>(), ["https://www.w3.org/ns/activitystreams#Public", "https://mastodon.example/users/alice/followers"]);
+    let Some([MaybeObjectRef::Object(note)]) = create.object.as_deref() else { return Err("object not embedded".into()) };
+    assert_eq!(note.kind.as_deref(), Some("Note"));
+    assert!(matches!(note.attributedTo.as_deref(), Some([MaybeObjectRef::URI(Cow::Borrowed(_))])));
+    assert!(matches!(&note.content, Some(Cow::Owned(content)) if content == "<p>Hello \"world\"</p>"));
+    assert!(note.inReplyTo.is_none());
+
+    let like = serde_json::from_str::<ObjectRef>(r#"{"type": "Like", "actor": "https:\/\/remote.invalid\/bob", "object": {"type": "Link", "href": "https://local.invalid/notes/1"}}"#)?;
+    assert!(matches!(like.actor.as_deref(), Some([MaybeObjectRef::URI(Cow::Owned(actor))]) if actor == "https://remote.invalid/bob"));
+    assert_eq!(like.object.as_deref().and_then(<[_]>::first).and_then(MaybeObjectRef::id), Some("https://local.invalid/notes/1"));
+    Ok(())
+}
+
+/// Compare objects by meaning with SemanticEq; Only date properties compare by the instant they denote
+#[test]
+fn test_equality() -> Result<(), Box<dyn Error>> {
+    let create = serde_json::from_str::<TaggedObject>(include_str!("./create.json"))?;
+    assert_eq!(create.clone(), create);
+
+    let folded = serde_json::from_str::<TaggedObject>(r#"{"type": "Note", "to": "https://example.com/a", "cc": [], "contentMap": {"en": "Hello", "de": "Hallo"}, "published": "2024-01-01T12:00:00Z"}"#)?;
+    let unfolded = serde_json::from_str::<TaggedObject>(r#"{"type": "Note", "to": ["https://example.com/a"], "contentMap": {"de": "Hallo", "en": "Hello"}, "published": "2024-01-01T13:00:00+01:00"}"#)?;
+    assert_ne!(folded, unfolded);
+    assert!(folded.semantic_eq(&unfolded));
+    let other = serde_json::from_str::<TaggedObject>(r#"{"type": "Note", "to": ["https://example.com/a", "https://example.com/b"]}"#)?;
+    assert!(!folded.semantic_eq(&other));
+    let content = serde_json::from_str::<TaggedObject>(r#"{"type": "Note", "content": "2024-01-01T12:00:00Z", "updated": "2024-01-01T12:00:00Z"}"#)?;
+    let offset = serde_json::from_str::<TaggedObject>(r#"{"type": "Note", "content": "2024-01-01T13:00:00+01:00", "updated": "2024-01-01T13:00:00+01:00"}"#)?;
+    assert!(!content.semantic_eq(&offset));
+    assert_eq!(Diff::between(&content, &offset)?.properties(), ["content"]);
+    assert!(FoldedSlice::from(1).semantic_eq(&FoldedSlice::many(vec![1])));
+
+    assert_eq!(Percentage::try_from(-0.0)?, Percentage::try_from(0.0)?);
+    assert!(Percentage::try_from(50.0)? > Percentage::try_from(25.0)?);
+    let hasher = std::collections::hash_map::RandomState::new();
+    let hashes = [-0.0, 0.0].map(|value| std::hash::BuildHasher::hash_one(&hasher, Percentage::try_from(value).unwrap()));
+    assert_eq!(hashes[0], hashes[1]);
+    assert!(PositiveFloat::try_from(f64::NAN).is_err());
+    Ok(())
+}
+
+/// Diff two versions of an object, apply the diff and its inverse, and reject patches that conflict
+#[test]
+fn test_diff() -> Result<(), Box<dyn Error>> {
+    let old = serde_json::from_str::<TaggedObject>(r#"{"type": "Note", "id": "https://example.com/notes/1", "attributedTo": "https://example.com/users/alice", "content": "Helo", "tag": [{"type": "Mention", "href": "https://example.com/users/bob"}], "to": "https://example.com/users/bob", "attachment": [{"type": "Document", "url": "https://example.com/a.png"}, {"type": "Document", "url": "https://example.com/b.png"}]}"#)?;
+    let new = serde_json::from_str::<TaggedObject>(r#"{"type": "Note", "id": "https://example.com/notes/1", "attributedTo": "https://example.com/users/alice", "content": "Hello", "summary": "Greeting", "to": ["https://example.com/users/bob"], "attachment": [{"type": "Document", "url": "https://example.com/a.png"}, {"type": "Document", "url": "https://example.com/c.png"}]}"#)?;
+
+    let diff = Diff::between(&old, &new)?;
+    assert_eq!(diff.changes.iter().map(ToString::to_string).collect::<Vec<_>>(), [
+        r#"changed $.attachment[1].url: "https://example.com/b.png" to "https://example.com/c.png""#,
+        r#"changed $.content: "Helo" to "Hello""#,
+        r#"added $.summary: "Greeting""#,
+        r#"removed $.tag: {"href":"https://example.com/users/bob","type":"Mention"}"#,
+    ]);
+    assert_eq!(diff.properties(), ["attachment", "content", "summary", "tag"]);
+    assert!(!diff.changes_any(IMMUTABLE_PROPERTIES));
+    assert!(Diff::between(&old, &old.clone())?.is_empty());
+
+    assert!(diff.apply(&old)?.semantic_eq(&new));
+    assert!(diff.inverse().apply(&new)?.semantic_eq(&old));
+    assert!(matches!(diff.apply(&new), Err(PatchError::Conflict(path)) if path.to_string() == "$.attachment[1].url"));
+
+    let mut reattributed = new.clone();
+    reattributed.object_fields_mut().unwrap().attributedTo = Some(FoldedSlice::from(MaybeObject::URI("https://example.com/users/mallory".to_string())));
+    let diff = Diff::between(&new, &reattributed)?;
+    assert!(diff.changes_any(IMMUTABLE_PROPERTIES));
+    assert!(matches!(&diff.changes[..], [Change::Changed { path, .. }] if path.property() == Some("attributedTo")));
+    Ok(())
+}
+
+/// Apply server-to-server and client-to-server Updates, only by actors that own the object
+#[test]
+fn test_apply_update() -> Result<(), Box<dyn Error>> {
+    let stored = serde_json::from_str::<TaggedObject>(r#"{"type": "Note", "id": "https://example.com/notes/1", "attributedTo": "https://example.com/users/alice", "content": "Helo", "summary": "Greeting", "to": "https://example.com/users/bob"}"#)?;
+    let update = |actor: &str, object: serde_json::Value| -> Result<(Update, serde_json::Value), Box<dyn Error>> {
+        let document = serde_json::json!({"type": "Update", "actor": actor, "object": object});
+        match serde_json::from_value::<TaggedObject>(document.clone())? {
+            TaggedObject::Update(update) => Ok((update, document)),
+            _ => unreachable!()
+        }
+    };
+    let alice = "https://example.com/users/alice";
+
+    // Server-to-server Updates replace the object
+    let (replace, _) = update(alice, serde_json::json!({"type": "Note", "id": "https://example.com/notes/1", "attributedTo": alice, "content": "Hello"}))?;
+    let replaced = apply_update(&stored, &replace, UpdateMode::Replace { trust_origin: false })?;
+    assert!(matches!(&replace.0.object.as_deref().unwrap()[0], MaybeObject::TaggedObject(object) if *object == replaced));
+    assert!(replaced.object_fields().unwrap().summary.is_none());
+
+    // Client-to-server Updates merge properties, and remove those that are null
+    let (merge, document) = update(alice, serde_json::json!({"type": "Note", "id": "https://example.com/notes/1", "content": "Hello", "summary": null}))?;
+    let removed = removed_properties(&document);
+    assert_eq!(removed, ["summary"]);
+    let merged = apply_update(&stored, &merge, UpdateMode::Merge { removed: &removed })?;
+    let fields = merged.object_fields().unwrap();
+    assert_eq!(fields.content.as_deref(), Some("Hello"));
+    assert!(fields.summary.is_none());
+    assert_eq!(fields.attributedTo.as_deref().unwrap()[0].id(), Some(alice));
+    assert!(fields.to.is_some());
+
+    let (foreign, _) = update("https://other.example/users/mallory", serde_json::json!({"type": "Note", "id": "https://example.com/notes/1", "content": "Pwned"}))?;
+    assert!(matches!(apply_update(&stored, &foreign, UpdateMode::Replace { trust_origin: false }), Err(UpdateError::Unauthorized(_))));
+    let (impostor, _) = update("https://example.com/users/bob", serde_json::json!({"type": "Note", "id": "https://example.com/notes/1", "content": "Pwned"}))?;
+    assert!(matches!(apply_update(&stored, &impostor, UpdateMode::Merge { removed: &[] }), Err(UpdateError::Unauthorized(_))));
+    let (reattribute, _) = update(alice, serde_json::json!({"type": "Note", "id": "https://example.com/notes/1", "attributedTo": "https://example.com/users/bob"}))?;
+    assert!(matches!(apply_update(&stored, &reattribute, UpdateMode::Merge { removed: &[] }), Err(UpdateError::Immutable(property)) if property == "attributedTo"));
+    let (other, _) = update(alice, serde_json::json!({"type": "Note", "id": "https://example.com/notes/2", "content": "Hello"}))?;
+    assert!(matches!(apply_update(&stored, &other, UpdateMode::Replace { trust_origin: false }), Err(UpdateError::ObjectMismatch { .. })));
+    let (by_uri, _) = update(alice, serde_json::json!("https://example.com/notes/1"))?;
+    assert!(matches!(apply_update(&stored, &by_uri, UpdateMode::Replace { trust_origin: false }), Err(UpdateError::MissingObject)));
+
+    // Objects without attributedTo are updated by themselves, or by their origin if it is trusted
+    let unattributed = serde_json::from_str::<TaggedObject>(r#"{"type": "Collection", "id": "https://example.com/collections/1", "totalItems": 1}"#)?;
+    let (by_origin, _) = update(alice, serde_json::json!({"type": "Collection", "id": "https://example.com/collections/1", "totalItems": 2}))?;
+    assert!(matches!(apply_update(&unattributed, &by_origin, UpdateMode::Replace { trust_origin: false }), Err(UpdateError::Unauthorized(_))));
+    assert!(matches!(apply_update(&unattributed, &by_origin, UpdateMode::Merge { removed: &[] }), Err(UpdateError::Unauthorized(_))));
+    assert!(apply_update(&unattributed, &by_origin, UpdateMode::Replace { trust_origin: true }).is_ok());
+    let person = serde_json::from_str::<TaggedObject>(r#"{"type": "Person", "id": "https://example.com/users/alice", "name": "Alice"}"#)?;
+    let (rename, _) = update(alice, serde_json::json!({"type": "Person", "id": alice, "name": "Alice A."}))?;
+    assert!(apply_update(&person, &rename, UpdateMode::Replace { trust_origin: false }).is_ok());
+    Ok(())
+}